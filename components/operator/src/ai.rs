@@ -1,501 +1,1014 @@
-use cosmwasm_std::{Decimal256};
+use ai_portfolio_types::WeightBounds;
+use cosmwasm_std::{Decimal256, Uint256};
 use serde::{Deserialize, Serialize};
-use std::env;
 use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
 use wstd::{
     http::{Client, HeaderValue, IntoBody, Request, StatusCode},
     io::AsyncRead,
 };
 
-const SYSTEM_PROMPT: &str = r#"You are a monkey throwing darts at a board. The user will provide you a list of names and you provide a number of points for each one. Examples:
-
-NAMES: NTRN, ATOM, USDC
-NTRN 42
-ATOM 83
-USDC 11
-
-NAMES: OSMO, JUNO, DYDX, NTRN
-OSMO 72
-JUNO 3
-DYDX 157
-NTRN 65
-"#;
-
-pub async fn monkey_advisor(denoms: Vec<String>, tvl: Decimal256, seed: u32) -> Result<BTreeMap<String, Decimal256>, String> {
-    // call the monkey to get some rankings from these various denoms
-    // let model = "llama3.1:8b".to_string();
-    let model = "llama3:8b-instruct-q4_0".to_string();
-
-    let llm_config = LlmOptions {
-        context_window: Some(16384),
-        max_tokens: Some(2048),
-        seed,
-        temperature: 0.7,
-        top_p: 0.9,
-    };
-    let llm_client = with_config(model.clone(), llm_config).map_err(|e| e.to_string())?;
-
-    let prompt = format!("NAMES: {}", denoms.join(", "));
-
-    let response =
-        llm_client.chat_completion_text(vec![Message {
-            role: "system".to_string(),
-            content: Some(SYSTEM_PROMPT.to_string()),
-            tool_calls: None,
-            tool_call_id: None,
-            name: None,
-        }, Message {
-            role: "user".to_string(),
-            content: Some(prompt.clone()),
-            tool_calls: None,
-            tool_call_id: None,
-            name: None,
-        }]).await.map_err(|e| e.to_string())?;
-
-    let monkey = parse_response(&response, &denoms)?;
-    Ok(normalize_monkey(monkey, tvl))
+use crate::coingecko::MarketStats;
+use crate::host;
+use wavs_wasi_utils::http;
+
+const SYSTEM_PROMPT: &str = r#"You manage a crypto portfolio held in an on-chain vault. The user gives you the vault's assets as JSON: current holdings, USD prices, 24h and 7d price changes and daily volatility (all in percent), and the minimum and maximum weight allowed for each asset.
+
+Choose target weights for the next rebalance. Favour diversification and risk-adjusted momentum, avoid piling into volatile assets, and don't churn the portfolio without a reason.
+
+Respond with a single JSON object and nothing else:
+{"allocations": [{"denom": "<denom>", "weight": <number between 0 and 1>}], "rationale": "<one or two sentences>"}
+
+Every weight must lie within that asset's min_weight and max_weight, and the weights must sum to 1. Use the denoms exactly as given."#;
+
+// How far the advised weights may sum from one before they are rejected
+const WEIGHT_SUM_TOLERANCE: Decimal256 = Decimal256::percent(1);
+
+const DEFAULT_WEIGHT_BUCKET_BPS: u64 = 500;
+
+/// How the advisor is run, read from the component config.
+///
+/// In consensus mode every operator must turn the same vault state into the
+/// same payload: sampling is greedy, the model must match a pinned digest and
+/// weights are rounded to coarse buckets so small wording differences in the
+/// model's answer don't change the routes. Prices come from the Pyth snapshot
+/// at the trigger time or the vault's stored prices, the prompt leaves out live
+/// market stats, an advisor error fails the payload instead of holding, and
+/// swaps only follow the routes pinned in `consensus_routes`.
+/// The digest is read from Ollama's `/api/tags`, so consensus mode needs
+/// `llm_provider = ollama`.
+#[derive(Clone, Debug)]
+pub struct AdvisorConfig {
+    pub llm: LlmConfig,
+    pub consensus: bool,
+    pub model_digest: Option<String>, // Required in consensus mode
+    pub weight_bucket: Decimal256,    // Consensus weights are whole multiples of this
+}
+
+impl AdvisorConfig {
+    pub fn from_config_vars() -> Result<Self, String> {
+        let consensus = parse_config_var("ai_consensus", false)?;
+
+        let bucket_bps: u64 = parse_config_var("ai_weight_bucket_bps", DEFAULT_WEIGHT_BUCKET_BPS)?;
+        // Whole buckets have to add up to exactly one
+        if bucket_bps == 0 || 10_000 % bucket_bps != 0 {
+            return Err(format!(
+                "ai_weight_bucket_bps must divide 10000, got {bucket_bps}"
+            ));
+        }
+
+        let model_digest = host::config_var("ai_model_digest");
+        if consensus && model_digest.is_none() {
+            return Err("ai_consensus requires ai_model_digest".to_string());
+        }
+
+        let llm = LlmConfig::from_config_vars()?;
+        // Other OpenAI-compatible APIs don't publish model digests
+        if model_digest.is_some() && llm.provider != LlmProvider::Ollama {
+            return Err(format!(
+                "ai_model_digest can only be checked against Ollama, set llm_provider to ollama (got {})",
+                llm.provider
+            ));
+        }
+
+        Ok(Self {
+            llm,
+            consensus,
+            model_digest,
+            weight_bucket: Decimal256::from_ratio(bucket_bps, 10_000u128),
+        })
+    }
+}
+
+/// What the advisor sees of one asset.
+#[derive(Clone, Debug)]
+pub struct AssetSnapshot {
+    pub denom: String,
+    pub symbol: String,
+    pub price_usd: Decimal256,
+    pub holding_value_usd: Decimal256,
+    pub market: Option<MarketStats>,
+    pub bounds: WeightBounds,
+}
+
+/// Target weights from the advisor, summing to one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Advice {
+    pub weights: BTreeMap<String, Decimal256>,
+    pub rationale: String,
 }
 
-fn parse_response(resp: &str, denoms: &[String]) -> Result<BTreeMap<String, u64>, String> {
-    let mut matches = BTreeMap::new();
+pub async fn portfolio_advisor(
+    assets: &[AssetSnapshot],
+    tvl: Decimal256,
+    seed: u32,
+    config: &AdvisorConfig,
+) -> Result<Advice, String> {
+    let mut llm_config = config.llm.clone();
+    // Greedy decoding, so the seed is the only thing left to agree on
+    if config.consensus {
+        llm_config.temperature = 0.0;
+        llm_config.top_p = 1.0;
+    }
+    let llm_client = LlmClient::new(llm_config, seed)?;
+
+    if let Some(digest) = &config.model_digest {
+        llm_client.verify_model_digest(digest).await?;
+    }
+
+    let prompt = build_prompt(assets, tvl)?;
+
+    let response = llm_client
+        .chat_completion_text(vec![
+            Message {
+                role: "system".to_string(),
+                content: Some(SYSTEM_PROMPT.to_string()),
+            },
+            Message {
+                role: "user".to_string(),
+                content: Some(prompt),
+            },
+        ])
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let advice = parse_advice(&response, assets)?;
+    if !config.consensus {
+        return Ok(advice);
+    }
 
-    for line in resp.split_terminator("\n") {
-        let items = line.trim().split(' ').collect::<Vec<&str>>();
-        if items.len() == 2 {
-            if let Ok(v) = u64::from_str_radix(items[1], 10) {
-                matches.insert(items[0].to_string(), v);
+    // Only the bucketed weights go into the payload
+    Ok(Advice {
+        weights: bucket_weights(&advice.weights, assets, config.weight_bucket)?,
+        rationale: advice.rationale,
+    })
+}
+
+/// Rounds weights to whole multiples of `bucket` that still sum to one and stay
+/// within each asset's bounds. Leftover buckets go to the weights that were
+/// rounded down the most, ties broken by denom, so equal inputs always give
+/// equal outputs.
+fn bucket_weights(
+    weights: &BTreeMap<String, Decimal256>,
+    assets: &[AssetSnapshot],
+    bucket: Decimal256,
+) -> Result<BTreeMap<String, Decimal256>, String> {
+    struct Row<'a> {
+        denom: &'a str,
+        exact: Decimal256, // Weight in buckets
+        count: Uint256,
+        lo: Uint256,
+        hi: Uint256,
+    }
+
+    let bounds: BTreeMap<&str, WeightBounds> = assets
+        .iter()
+        .map(|asset| (asset.denom.as_str(), asset.bounds))
+        .collect();
+    let in_buckets = |value: Decimal256| value.checked_div(bucket).map_err(|e| e.to_string());
+
+    let mut rows = Vec::new();
+    for (denom, weight) in weights {
+        let bound = bounds.get(denom.as_str()).copied().unwrap_or_default();
+        let exact = in_buckets(*weight)?;
+        let lo = in_buckets(bound.min)?.to_uint_ceil();
+        let hi = in_buckets(bound.max)?.to_uint_floor();
+        if lo > hi {
+            return Err(format!("No {bucket} bucket fits the bounds for {denom}"));
+        }
+        rows.push(Row {
+            denom,
+            exact,
+            count: exact.to_uint_floor().clamp(lo, hi),
+            lo,
+            hi,
+        });
+    }
+
+    let target = in_buckets(Decimal256::one())?.to_uint_floor();
+    let mut total = rows
+        .iter()
+        .fold(Uint256::zero(), |acc, row| acc + row.count);
+    while total != target {
+        let adding = total < target;
+        // The row furthest from its exact weight in the direction we need to move
+        let next = rows
+            .iter_mut()
+            .filter(|row| {
+                if adding {
+                    row.count < row.hi
+                } else {
+                    row.count > row.lo
+                }
+            })
+            .fold(None::<(Decimal256, &mut Row)>, |best, row| {
+                let count = Decimal256::from_ratio(row.count, Uint256::one());
+                let gap = if adding {
+                    row.exact.saturating_sub(count)
+                } else {
+                    count.saturating_sub(row.exact)
+                };
+                match best {
+                    Some((best_gap, best_row)) if best_gap >= gap => Some((best_gap, best_row)),
+                    _ => Some((gap, row)),
+                }
+            });
+        let Some((_, row)) = next else {
+            return Err(format!(
+                "Weights can't be rounded to {bucket} buckets within bounds"
+            ));
+        };
+
+        if adding {
+            row.count += Uint256::one();
+            total += Uint256::one();
+        } else {
+            row.count -= Uint256::one();
+            total -= Uint256::one();
+        }
+    }
+
+    rows.into_iter()
+        .map(|row| {
+            let weight = bucket
+                .checked_mul(Decimal256::from_ratio(row.count, Uint256::one()))
+                .map_err(|e| e.to_string())?;
+            Ok((row.denom.to_string(), weight))
+        })
+        .collect()
+}
+
+fn build_prompt(assets: &[AssetSnapshot], tvl: Decimal256) -> Result<String, String> {
+    let assets: Vec<serde_json::Value> = assets
+        .iter()
+        .map(|asset| {
+            let weight_now = if tvl.is_zero() {
+                Decimal256::zero()
+            } else {
+                asset.holding_value_usd.checked_div(tvl).unwrap_or_default()
+            };
+            let mut value = serde_json::json!({
+                "denom": asset.denom,
+                "symbol": asset.symbol,
+                "price_usd": asset.price_usd,
+                "holding_value_usd": asset.holding_value_usd,
+                "weight_now": weight_now,
+                "min_weight": asset.bounds.min,
+                "max_weight": asset.bounds.max,
+            });
+            // Rounded so the prompt stays short
+            if let Some(market) = asset.market {
+                value["change_24h_pct"] = serde_json::json!(round_to(market.change_24h, 2));
+                value["change_7d_pct"] = serde_json::json!(round_to(market.change_7d, 2));
+                value["volatility_pct"] = serde_json::json!(round_to(market.volatility, 2));
             }
+            value
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&serde_json::json!({
+        "tvl_usd": tvl,
+        "assets": assets,
+    }))
+    .map_err(|e| e.to_string())
+}
+
+fn round_to(value: f64, places: i32) -> f64 {
+    let scale = 10f64.powi(places);
+    (value * scale).round() / scale
+}
+
+#[derive(Deserialize)]
+struct AdviceResponse {
+    allocations: Vec<AdvisedWeight>,
+    rationale: String,
+}
+
+#[derive(Deserialize)]
+struct AdvisedWeight {
+    denom: String,
+    weight: serde_json::Number,
+}
+
+/// Parses the advisor's JSON and checks it against the assets' weight bounds.
+/// Assets the response leaves out get a weight of zero.
+fn parse_advice(resp: &str, assets: &[AssetSnapshot]) -> Result<Advice, String> {
+    // Models like to wrap the object in prose or code fences
+    let start = resp.find('{').ok_or("No JSON object in response")?;
+    let end = resp.rfind('}').ok_or("No JSON object in response")?;
+    let parsed: AdviceResponse = serde_json::from_str(&resp[start..=end])
+        .map_err(|e| format!("Invalid advice JSON: {e}"))?;
+
+    let bounds: BTreeMap<&str, WeightBounds> = assets
+        .iter()
+        .map(|asset| (asset.denom.as_str(), asset.bounds))
+        .collect();
+
+    let mut weights = BTreeMap::new();
+    for allocation in parsed.allocations {
+        if !bounds.contains_key(allocation.denom.as_str()) {
+            return Err(format!("Unknown denom in advice: {}", allocation.denom));
         }
+        if weights.contains_key(&allocation.denom) {
+            return Err(format!("Duplicate denom in advice: {}", allocation.denom));
+        }
+        let weight = Decimal256::from_str(&allocation.weight.to_string())
+            .map_err(|e| format!("Invalid weight for {}: {e}", allocation.denom))?;
+        weights.insert(allocation.denom, weight);
     }
-    if matches.len() != denoms.len() {
-        return Err(format!("Only found {} of {} tokens in response", matches.len(), denoms.len()))
+
+    for (denom, bound) in &bounds {
+        let weight = weights
+            .entry(denom.to_string())
+            .or_insert_with(Decimal256::zero);
+        if *weight < bound.min || *weight > bound.max {
+            return Err(format!(
+                "Weight {weight} for {denom} is outside [{}, {}]",
+                bound.min, bound.max
+            ));
+        }
     }
-    Ok(matches)
+
+    let total = weights
+        .values()
+        .try_fold(Decimal256::zero(), |acc, weight| acc.checked_add(*weight))
+        .map_err(|e| e.to_string())?;
+    if total.abs_diff(Decimal256::one()) > WEIGHT_SUM_TOLERANCE {
+        return Err(format!("Weights sum to {total}, expected 1"));
+    }
+
+    // Absorb the rounding the tolerance allows
+    for weight in weights.values_mut() {
+        *weight = weight.checked_div(total).map_err(|e| e.to_string())?;
+    }
+
+    Ok(Advice {
+        weights,
+        rationale: parsed.rationale,
+    })
 }
 
-fn normalize_monkey(advice: BTreeMap<String, u64>, tvl: Decimal256) -> BTreeMap<String, Decimal256> {
-    let total: u64 = advice.values().map(|x| *x).sum();
-    let mut targets = BTreeMap::new();
-    for (denom, allocation) in advice {
-        let target_value = tvl
-            .checked_mul(Decimal256::from_ratio(allocation, total)).unwrap();
-        targets.insert(denom.clone(), target_value);
+const DEFAULT_LLM_BASE_URL: &str = "http://localhost:11434/v1"; // Ollama's OpenAI-compatible API
+const DEFAULT_LLM_MODEL: &str = "llama3:8b-instruct-q4_0";
+const DEFAULT_LLM_TEMPERATURE: f32 = 0.7;
+const DEFAULT_LLM_TOP_P: f32 = 0.9;
+const DEFAULT_LLM_MAX_TOKENS: u32 = 2048;
+const DEFAULT_LLM_TIMEOUT_SECONDS: u64 = 120;
+
+/// Which kind of server `llm_base_url` points at. Only Ollama lists model digests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LlmProvider {
+    OpenAi,
+    Ollama,
+}
+
+impl FromStr for LlmProvider {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "openai" => Ok(Self::OpenAi),
+            "ollama" => Ok(Self::Ollama),
+            _ => Err("expected openai or ollama".to_string()),
+        }
     }
-    targets
+}
 
+impl Display for LlmProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::OpenAi => "openai",
+            Self::Ollama => "ollama",
+        })
+    }
 }
 
-// JSON serializable version of LlmOptions
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LlmOptions {
+/// Any OpenAI-compatible `/chat/completions` provider, read from the component config.
+#[derive(Clone, Debug)]
+pub struct LlmConfig {
+    pub provider: LlmProvider,
+    pub base_url: String, // Up to and including the API version, e.g. https://api.openai.com/v1
+    pub model: String,
+    pub api_key_env: Option<String>, // Env var holding the bearer token, e.g. WAVS_ENV_LLM_API_KEY
     pub temperature: f32,
     pub top_p: f32,
-    pub seed: u32,
-    pub max_tokens: Option<u32>,
-    pub context_window: Option<u32>,
+    pub max_tokens: u32,
+    pub timeout: Duration,
+}
+
+impl LlmConfig {
+    pub fn from_config_vars() -> Result<Self, String> {
+        Ok(Self {
+            provider: parse_config_var("llm_provider", LlmProvider::OpenAi)?,
+            base_url: host::config_var("llm_base_url")
+                .unwrap_or_else(|| DEFAULT_LLM_BASE_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            model: host::config_var("llm_model").unwrap_or_else(|| DEFAULT_LLM_MODEL.to_string()),
+            api_key_env: host::config_var("llm_api_key_env"),
+            temperature: parse_config_var("llm_temperature", DEFAULT_LLM_TEMPERATURE)?,
+            top_p: parse_config_var("llm_top_p", DEFAULT_LLM_TOP_P)?,
+            max_tokens: parse_config_var("llm_max_tokens", DEFAULT_LLM_MAX_TOKENS)?,
+            timeout: Duration::from_secs(parse_config_var(
+                "llm_timeout_seconds",
+                DEFAULT_LLM_TIMEOUT_SECONDS,
+            )?),
+        })
+    }
+}
+
+fn parse_config_var<T>(key: &str, default: T) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match host::config_var(key) {
+        Some(value) => value
+            .parse()
+            .map_err(|e| format!("invalid {key} '{value}': {e}")),
+        None => Ok(default),
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct LlmClient {
-    /// The model name to use
-    pub model: String,
-    /// Configuration options for LLM requests
-    pub config: LlmOptions,
-    /// The API URL to send requests to
-    pub api_url: String,
-    /// Optional API key for authenticated services
+    /// Provider, model and sampling settings
+    pub config: LlmConfig,
+    /// Sampling seed, for providers that honour one
+    pub seed: u32,
+    /// Bearer token read from `config.api_key_env`
     pub api_key: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub content: Option<String>,
-    pub tool_calls: Option<Vec<ToolCall>>,
-    pub tool_call_id: Option<String>,
-    pub name: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ToolCall {
-    pub id: String,
-    pub tool_type: String,
-    pub function: ToolCallFunction,
+/// Sends one HTTP request and returns the status and body. The component goes
+/// through wasi:http; tests swap in a plain socket so they can talk to a stub server.
+trait HttpSend {
+    async fn send(
+        &self,
+        req: Request<Vec<u8>>,
+        timeout: Duration,
+    ) -> Result<(StatusCode, Vec<u8>), String>;
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ToolCallFunction {
-    pub name: String,
-    pub arguments: String,
-}
+struct WasiHttp;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Tool {
-    pub tool_type: String,
-    pub function: Function,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Function {
-    pub name: String,
-    pub description: Option<String>,
-    pub parameters: Option<String>,
+impl HttpSend for WasiHttp {
+    async fn send(
+        &self,
+        req: Request<Vec<u8>>,
+        timeout: Duration,
+    ) -> Result<(StatusCode, Vec<u8>), String> {
+        // Non-streaming, so the first byte only arrives once the completion is done
+        let mut client = Client::new();
+        client.set_connect_timeout(timeout);
+        client.set_first_byte_timeout(timeout);
+        client.set_between_bytes_timeout(timeout);
+
+        let mut res = client
+            .send(req.map(IntoBody::into_body))
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut body_buf = Vec::new();
+        res.body_mut()
+            .read_to_end(&mut body_buf)
+            .await
+            .map_err(|e| format!("Failed to read response body: {}", e))?;
+        Ok((res.status(), body_buf))
+    }
 }
 
-pub fn with_config(model: String, config: LlmOptions) -> Result<LlmClient, String> {
-    // Get API key if using OpenAI models
-    let api_key = match model.as_str() {
-        "gpt-3.5-turbo" | "gpt-4" | "gpt-4o" | "gpt-4o-mini" | "gpt-4.1" | "gpt-4-turbo" => {
-            match std::env::var("WAVS_ENV_OPENAI_API_KEY") {
-                Ok(key) => Some(key),
-                Err(_) => None,
+impl LlmClient {
+    pub fn new(config: LlmConfig, seed: u32) -> Result<Self, String> {
+        let api_key = match &config.api_key_env {
+            Some(key) => {
+                Some(env::var(key).map_err(|_| format!("LLM API key env var {key} is not set"))?)
             }
-        }
-        _ => None,
-    };
-
-    // Set API URL based on model type
-    let api_url = match model.as_str() {
-        "gpt-3.5-turbo" | "gpt-4" | "gpt-4o" | "gpt-4o-mini" | "gpt-4.1" | "gpt-4-turbo" => {
-            "https://api.openai.com/v1/chat/completions".to_string()
-        }
-        _ => format!(
-            "{}/api/chat",
-            env::var("WAVS_ENV_OLLAMA_API_URL")
-                .unwrap_or_else(|_| "http://localhost:11434".to_string())
-        ),
-    };
-
-    // Create the new client instance
-    Ok(LlmClient { model, config, api_url, api_key })
-}
+            None => None,
+        };
+
+        Ok(Self {
+            config,
+            seed,
+            api_key,
+        })
+    }
 
-impl LlmClient {
-    fn get_model(&self) -> String {
-        self.model.clone()
+    fn request_body(&self, messages: &[Message]) -> serde_json::Value {
+        serde_json::json!({
+            "model": self.config.model,
+            "messages": messages,
+            "temperature": self.config.temperature,
+            "top_p": self.config.top_p,
+            "seed": self.seed,
+            "max_tokens": self.config.max_tokens,
+            "stream": false,
+        })
     }
 
-    fn get_config(&self) -> LlmOptions {
-        self.config.clone()
+    async fn chat_completion(&self, messages: Vec<Message>) -> Result<Message, String> {
+        host::log(
+            host::LogLevel::Info,
+            &format!(
+                "Sending chat completion request to {}/chat/completions ({})",
+                self.config.base_url, self.config.model
+            ),
+        );
+        self.send_chat_completion(&WasiHttp, &messages).await
     }
 
-    async fn chat_completion(
+    async fn send_chat_completion(
         &self,
-        messages: Vec<Message>,
-        tools: Option<Vec<Tool>>,
+        http: &impl HttpSend,
+        messages: &[Message],
     ) -> Result<Message, String> {
-            // Validate messages
-            if messages.is_empty() {
-                return Err("Messages cannot be empty".into());
-            }
-
-            println!("Sending chat completion request:");
+        if messages.is_empty() {
+            return Err("Messages cannot be empty".into());
+        }
 
-            // Check if OpenAI models have an API key
-            let is_openai_model = matches!(
-                self.model.as_str(),
-                "gpt-3.5-turbo" | "gpt-4" | "gpt-4o" | "gpt-4o-mini" | "gpt-4.1" | "gpt-4-turbo"
+        let url = format!("{}/chat/completions", self.config.base_url);
+        let body = self.request_body(messages);
+
+        let mut req = Request::post(&url)
+            .body(serde_json::to_vec(&body).map_err(|e| e.to_string())?)
+            .map_err(|e| format!("Failed to create request: {}", e))?;
+        req.headers_mut()
+            .insert("Content-Type", HeaderValue::from_static("application/json"));
+        req.headers_mut()
+            .insert("Accept", HeaderValue::from_static("application/json"));
+        if let Some(api_key) = &self.api_key {
+            req.headers_mut().insert(
+                "Authorization",
+                HeaderValue::from_str(&format!("Bearer {}", api_key))
+                    .map_err(|e| format!("Invalid API key format: {}", e))?,
             );
+        }
 
-            println!("is_openai_model: {}", is_openai_model);
-
-            if is_openai_model && self.api_key.is_none() {
-                return Err("OpenAI API key is required for OpenAI models".into());
-            }
-
-            // Calculate max tokens based on tools presence if not explicitly set
-            let max_tokens =
-                self.config.max_tokens.unwrap_or_else(|| if tools.is_some() { 1024 } else { 100 });
-
-            println!("api key: {}", self.api_key.is_some());
-            println!("api url: {}", self.api_url);
-
-            if self.api_url.is_empty() {
-                return Err("API URL is empty".into());
-            }
+        let (status, body_buf) = http
+            .send(req, self.config.timeout)
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+        let body_str = String::from_utf8_lossy(&body_buf);
 
-            // Create request body with configurable settings
-            let body = if self.api_key.is_some() {
-                // OpenAI format
-                let mut request = serde_json::json!({
-                    "model": self.model,
-                    "messages": messages,
-                    "temperature": self.config.temperature,
-                    "top_p": self.config.top_p,
-                    "seed": self.config.seed,
-                    "stream": false,
-                    "max_tokens": max_tokens
-                });
-
-                // Add tools if provided
-                if let Some(tools_list) = tools {
-                    request["tools"] = serde_json::to_value(tools_list).map_err(|e| e.to_string())?;
-                }
+        if status != StatusCode::OK {
+            return Err(format!("API error: status {} - {}", status, body_str));
+        }
 
-                request
-            } else {
-                // Ollama chat format
-                let mut request = serde_json::json!({
-                    "model": self.model,
-                    "messages": messages,
-                    "stream": false,
-                    "options": {
-                        "temperature": self.config.temperature,
-                        "top_p": self.config.top_p,
-                        "seed": self.config.seed,
-                        "num_predict": max_tokens,
-                    }
-                });
+        parse_chat_response(&body_str)
+    }
 
-                // Add context window if specified
-                if let Some(ctx) = self.config.context_window {
-                    request["options"]["num_ctx"] = serde_json::json!(ctx);
-                }
+    async fn chat_completion_text(&self, messages: Vec<Message>) -> Result<String, String> {
+        let response = self.chat_completion(messages).await?;
+        Ok(response.content.unwrap_or_default())
+    }
 
-                // Add tools if provided for Ollama (using the format Ollama expects)
-                if let Some(tools_list) = tools.clone() {
-                    // Standard tools format
-                    request["tools"] = serde_json::to_value(tools_list.clone()).map_err(|e| e.to_string())?;
-
-                    // Also include functions key which some Ollama versions might need
-                    // Convert tools to format compatible with Ollama
-                    let functions = tools_list
-                        .iter()
-                        .map(|tool| {
-                            serde_json::json!({
-                                "name": tool.function.name,
-                                "description": tool.function.description,
-                                "parameters": tool.function.parameters
-                            })
-                        })
-                        .collect::<Vec<_>>();
+    /// Checks the served model against a pinned digest using Ollama's model list,
+    /// so operators can't silently drift onto a different build of the same tag.
+    /// Only Ollama serves `/api/tags`; any other provider fails here.
+    async fn verify_model_digest(&self, expected: &str) -> Result<(), String> {
+        #[derive(Deserialize)]
+        struct TagsResponse {
+            models: Vec<ModelTag>,
+        }
 
-                    request["functions"] = serde_json::json!(functions);
-                }
+        #[derive(Deserialize)]
+        struct ModelTag {
+            name: String,
+            digest: String,
+        }
 
-                request
-            };
+        let model = &self.config.model;
+        let tags_url = format!(
+            "{}/api/tags",
+            self.config
+                .base_url
+                .trim_end_matches('/')
+                .trim_end_matches("/v1")
+        );
+        let request = http::http_request_get(&tags_url).map_err(|e| e.to_string())?;
+        let tags: TagsResponse = http::fetch_json(request).await.map_err(|e| {
+            format!(
+                "Failed to list models from {}, digest checks need an Ollama server: {}",
+                tags_url, e
+            )
+        })?;
+
+        let normalize = |digest: &str| digest.trim_start_matches("sha256:").to_lowercase();
+        let served = tags
+            .models
+            .iter()
+            .find(|tag| &tag.name == model || tag.name == format!("{}:latest", model))
+            .ok_or_else(|| format!("Model {} is not available", model))?;
+
+        if normalize(&served.digest) != normalize(expected) {
+            return Err(format!(
+                "Model {} has digest {}, expected {}",
+                model, served.digest, expected
+            ));
+        }
+        Ok(())
+    }
+}
 
-            println!("Request body: {}", serde_json::to_string_pretty(&body).unwrap_or_default());
+fn parse_chat_response(body: &str) -> Result<Message, String> {
+    #[derive(Deserialize)]
+    struct ChatResponse {
+        choices: Vec<Choice>,
+    }
 
-            // Create request
-            let mut req = Request::post(&self.api_url)
-                .body(serde_json::to_vec(&body).unwrap().into_body())
-                .map_err(|e| format!("Failed to create request: {}", e))?;
+    #[derive(Deserialize)]
+    struct Choice {
+        message: Message,
+    }
 
-            // Add headers
-            req.headers_mut().insert("Content-Type", HeaderValue::from_static("application/json"));
-            req.headers_mut().insert("Accept", HeaderValue::from_static("application/json"));
+    let resp: ChatResponse = serde_json::from_str(body)
+        .map_err(|e| format!("Failed to parse chat completion response: {}", e))?;
 
-            // Add authorization if needed
-            if let Some(api_key) = &self.api_key {
-                req.headers_mut().insert(
-                    "Authorization",
-                    HeaderValue::from_str(&format!("Bearer {}", api_key))
-                        .map_err(|e| format!("Invalid API key format: {}", e))?,
-                );
-            }
+    resp.choices
+        .into_iter()
+        .next()
+        .map(|choice| choice.message)
+        .ok_or_else(|| "No response choices returned".into())
+}
 
-            println!("Sending request to: {}", req.uri());
-
-            // Send request
-            let mut res = Client::new()
-                .send(req)
-                .await
-                .map_err(|e| format!("Request failed: {}", e))?;
-
-            println!("Received response with status: {}", res.status());
-
-            if res.status() != StatusCode::OK {
-                let mut error_body = Vec::new();
-                res.body_mut()
-                    .read_to_end(&mut error_body)
-                    .await
-                    .map_err(|e| format!("Failed to read error response: {}", e))?;
-                let error_msg = format!(
-                    "API error: status {} - {}",
-                    res.status(),
-                    String::from_utf8_lossy(&error_body)
-                );
-                println!("Error: {}", error_msg);
-                return Err(error_msg);
-            }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::future::Future;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+    use std::thread;
+
+    fn decimal(value: &str) -> Decimal256 {
+        Decimal256::from_str(value).unwrap()
+    }
 
-            // Read response body
-            let mut body_buf = Vec::new();
-            res.body_mut()
-                .read_to_end(&mut body_buf)
-                .await
-                .map_err(|e| format!("Failed to read response body: {}", e))?;
+    fn snapshot(denom: &str, min: &str, max: &str) -> AssetSnapshot {
+        AssetSnapshot {
+            denom: denom.to_string(),
+            symbol: denom.to_uppercase(),
+            price_usd: Decimal256::one(),
+            holding_value_usd: Decimal256::zero(),
+            market: None,
+            bounds: WeightBounds {
+                min: decimal(min),
+                max: decimal(max),
+            },
+        }
+    }
 
-            let body_str = String::from_utf8(body_buf)
-                .map_err(|e| format!("Invalid UTF-8 in response: {}", e))?;
+    fn assets() -> Vec<AssetSnapshot> {
+        vec![
+            snapshot("untrn", "0", "0.5"),
+            snapshot("uatom", "0.1", "0.6"),
+            snapshot("uusdc", "0", "1"),
+        ]
+    }
 
-            println!("Raw response: {}", body_str);
+    #[test]
+    fn test_parse_advice() {
+        let response = r#"Here is my allocation:
+```json
+{"allocations": [{"denom": "untrn", "weight": 0.3}, {"denom": "uatom", "weight": 0.5}, {"denom": "uusdc", "weight": 0.2}], "rationale": "ATOM has the strongest trend."}
+```"#;
+        let advice = parse_advice(response, &assets()).unwrap();
+        assert_eq!(advice.weights.len(), 3);
+        assert_eq!(advice.weights["untrn"], decimal("0.3"));
+        assert_eq!(advice.weights["uatom"], decimal("0.5"));
+        assert_eq!(advice.weights["uusdc"], decimal("0.2"));
+        assert_eq!(advice.rationale, "ATOM has the strongest trend.");
+
+        // Omitted assets get zero and near-one sums are normalized
+        let response = r#"{"allocations": [{"denom": "uatom", "weight": 0.5}, {"denom": "uusdc", "weight": 0.495}], "rationale": ""}"#;
+        let advice = parse_advice(response, &assets()).unwrap();
+        assert_eq!(advice.weights["untrn"], Decimal256::zero());
+        let total = advice
+            .weights
+            .values()
+            .fold(Decimal256::zero(), |acc, w| acc + *w);
+        assert!(total.abs_diff(Decimal256::one()) < decimal("0.000000001"));
+    }
 
-            // Parse response based on provider
-            if self.api_key.is_some() {
-                // Parse OpenAI response format
-                #[derive(Deserialize)]
-                struct ChatResponse {
-                    choices: Vec<Choice>,
-                }
+    #[test]
+    fn test_parse_advice_rejects_invalid_allocations() {
+        let cases = [
+            ("no json here", "No JSON object in response"),
+            (
+                r#"{"allocations": [{"denom": "untrn", "weight": 0.6}, {"denom": "uatom", "weight": 0.4}], "rationale": ""}"#,
+                "Weight 0.6 for untrn is outside [0, 0.5]",
+            ),
+            (
+                r#"{"allocations": [{"denom": "uusdc", "weight": 1}], "rationale": ""}"#,
+                "Weight 0 for uatom is outside [0.1, 0.6]",
+            ),
+            (
+                r#"{"allocations": [{"denom": "uosmo", "weight": 1}], "rationale": ""}"#,
+                "Unknown denom in advice: uosmo",
+            ),
+            (
+                r#"{"allocations": [{"denom": "uatom", "weight": 0.3}, {"denom": "uatom", "weight": 0.3}], "rationale": ""}"#,
+                "Duplicate denom in advice: uatom",
+            ),
+            (
+                r#"{"allocations": [{"denom": "uatom", "weight": 0.5}, {"denom": "uusdc", "weight": 0.3}], "rationale": ""}"#,
+                "Weights sum to 0.8, expected 1",
+            ),
+            (
+                r#"{"allocations": [{"denom": "uatom", "weight": -0.5}], "rationale": ""}"#,
+                "Invalid weight for uatom",
+            ),
+        ];
+
+        for (response, expected) in cases {
+            let err = parse_advice(response, &assets()).unwrap_err();
+            assert!(err.starts_with(expected), "{err}");
+        }
+    }
 
-                #[derive(Deserialize)]
-                struct Choice {
-                    message: OpenAIMessage,
-                }
+    #[test]
+    fn test_bucket_weights() {
+        let bucket = decimal("0.05");
+        let weights = |pairs: &[(&str, &str)]| -> BTreeMap<String, Decimal256> {
+            pairs
+                .iter()
+                .map(|(denom, weight)| (denom.to_string(), decimal(weight)))
+                .collect()
+        };
+
+        // Leftover buckets go to the largest remainders, ties to the first denom
+        let bucketed = bucket_weights(
+            &weights(&[("uatom", "0.34"), ("untrn", "0.33"), ("uusdc", "0.33")]),
+            &assets(),
+            bucket,
+        )
+        .unwrap();
+        assert_eq!(
+            bucketed,
+            weights(&[("uatom", "0.35"), ("untrn", "0.35"), ("uusdc", "0.3")])
+        );
+
+        // Rounding never crosses a bound
+        let capped = vec![snapshot("ua", "0", "0.33"), snapshot("ub", "0", "1")];
+        let bucketed =
+            bucket_weights(&weights(&[("ua", "0.33"), ("ub", "0.67")]), &capped, bucket).unwrap();
+        assert_eq!(bucketed, weights(&[("ua", "0.3"), ("ub", "0.7")]));
+
+        let narrow = vec![snapshot("ua", "0.31", "0.34"), snapshot("ub", "0", "1")];
+        let err = bucket_weights(&weights(&[("ua", "0.32"), ("ub", "0.68")]), &narrow, bucket)
+            .unwrap_err();
+        assert_eq!(err, "No 0.05 bucket fits the bounds for ua");
+    }
 
-                #[derive(Deserialize, Clone)]
-                struct OpenAIMessage {
-                    role: String,
-                    #[serde(default)]
-                    content: Option<String>,
-                    #[serde(default)]
-                    tool_calls: Option<Vec<OpenAIToolCall>>,
-                }
+    fn llm_config() -> LlmConfig {
+        LlmConfig {
+            provider: LlmProvider::OpenAi,
+            base_url: "http://127.0.0.1:8080/v1".to_string(),
+            model: "stub-model".to_string(),
+            api_key_env: None,
+            temperature: 0.0,
+            top_p: 1.0,
+            max_tokens: 256,
+            timeout: Duration::from_secs(5),
+        }
+    }
 
-                #[derive(Deserialize, Clone)]
-                struct OpenAIToolCall {
-                    id: String,
-                    #[serde(rename = "type")]
-                    tool_type: String,
-                    function: OpenAIFunction,
-                }
+    #[test]
+    fn test_openai_compatible_request_and_response() {
+        let client = LlmClient::new(llm_config(), 42).unwrap();
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: Some("hi".to_string()),
+        }];
+        assert_eq!(
+            client.request_body(&messages),
+            serde_json::json!({
+                "model": "stub-model",
+                "messages": [{"role": "user", "content": "hi"}],
+                "temperature": 0.0,
+                "top_p": 1.0,
+                "seed": 42,
+                "max_tokens": 256,
+                "stream": false,
+            })
+        );
+
+        // What a minimal OpenAI-compatible stub server answers with
+        let response = r#"{"id": "chatcmpl-1", "object": "chat.completion", "model": "stub-model",
+            "choices": [{"index": 0, "finish_reason": "stop",
+                "message": {"role": "assistant", "content": "{\"allocations\": []}"}}]}"#;
+        let message = parse_chat_response(response).unwrap();
+        assert_eq!(message.content.as_deref(), Some(r#"{"allocations": []}"#));
+
+        assert!(parse_chat_response(r#"{"choices": []}"#).is_err());
+        assert!(parse_chat_response(r#"{"error": {"message": "bad key"}}"#).is_err());
+    }
 
-                #[derive(Deserialize, Clone)]
-                struct OpenAIFunction {
-                    name: String,
-                    arguments: String,
-                }
+    #[test]
+    fn test_missing_api_key_env_is_an_error() {
+        let config = LlmConfig {
+            api_key_env: Some("WAVS_ENV_TEST_UNSET_LLM_KEY".to_string()),
+            ..llm_config()
+        };
+        assert_eq!(
+            LlmClient::new(config, 0).unwrap_err(),
+            "LLM API key env var WAVS_ENV_TEST_UNSET_LLM_KEY is not set"
+        );
+    }
 
-                let resp: ChatResponse = serde_json::from_str(&body_str).map_err(|e| {
-                    format!("Failed to parse OpenAI response: {}", e)
-                })?;
-
-                resp.choices
-                    .first()
-                    .map(|choice| Message {
-                        role: choice.message.role.clone(),
-                        content: choice.message.content.clone(),
-                        tool_calls: choice.message.tool_calls.clone().map(|tool_calls| {
-                            tool_calls
-                                .into_iter()
-                                .map(|tool_call| ToolCall {
-                                    id: tool_call.id,
-                                    tool_type: tool_call.tool_type,
-                                    function: ToolCallFunction {
-                                        name: tool_call.function.name,
-                                        arguments: tool_call.function.arguments,
-                                    },
-                                })
-                                .collect()
-                        }),
-                        tool_call_id: None,
-                        name: None,
-                    })
-                    .ok_or_else(|| "No response choices returned".into())
-            } else {
-                // Parse Ollama chat response format
-                // Create a custom deserialization logic for Ollama responses
-                let parsed_json: serde_json::Value =
-                    serde_json::from_str(&body_str).map_err(|e| {
-                        format!("Failed to parse Ollama response as JSON: {}", e)
-                    })?;
-
-                println!("Successfully parsed Ollama response to JSON Value");
-
-                // Extract message contents
-                let role =
-                    parsed_json["message"]["role"].as_str().unwrap_or("assistant").to_string();
-
-                let content = parsed_json["message"]["content"].as_str().map(|s| s.to_string());
-
-                // Create base message
-                let mut message =
-                    Message { role, content, tool_calls: None, tool_call_id: None, name: None };
-
-                // Process tool calls if present
-                if let Some(tool_calls_array) = parsed_json["message"]["tool_calls"].as_array() {
-                    println!("Found tool calls in Ollama response: {}", tool_calls_array.len());
-
-                    let mut processed_tool_calls = Vec::new();
-
-                    for (idx, tool_call) in tool_calls_array.iter().enumerate() {
-                        if let Some(name) = tool_call["function"]["name"].as_str() {
-                            println!("Processing tool call: {}", name);
-
-                            // Get arguments value (could be object or string)
-                            let args = &tool_call["function"]["arguments"];
-
-                            // Convert arguments to string if they're an object
-                            let arguments = if args.is_object() {
-                                serde_json::to_string(args).unwrap_or_default()
-                            } else if args.is_string() {
-                                args.as_str().unwrap_or_default().to_string()
-                            } else {
-                                serde_json::to_string(args).unwrap_or_default()
-                            };
-
-                            println!("Arguments converted to string: {}", arguments);
-
-                            processed_tool_calls
-                                .push(ToolCall {
-                                id: format!("call_{}", idx),
-                                tool_type: "function".to_string(),
-                                function:
-                                    ToolCallFunction {
-                                        name: name.to_string(),
-                                        arguments,
-                                    },
-                            });
-                        }
-                    }
+    /// Plain HTTP/1.1 over a std socket, standing in for wasi:http outside the component
+    struct StdHttp;
+
+    impl HttpSend for StdHttp {
+        async fn send(
+            &self,
+            req: Request<Vec<u8>>,
+            timeout: Duration,
+        ) -> Result<(StatusCode, Vec<u8>), String> {
+            let authority = req.uri().authority().ok_or("missing host")?.as_str();
+            let mut stream = TcpStream::connect(authority).map_err(|e| e.to_string())?;
+            stream
+                .set_read_timeout(Some(timeout))
+                .map_err(|e| e.to_string())?;
+
+            let mut head = format!(
+                "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+                req.method(),
+                req.uri().path(),
+                authority,
+                req.body().len()
+            );
+            for (name, value) in req.headers() {
+                head.push_str(&format!("{}: {}\r\n", name, value.to_str().unwrap()));
+            }
+            head.push_str("\r\n");
+            stream
+                .write_all(&[head.as_bytes(), req.body()].concat())
+                .map_err(|e| e.to_string())?;
+
+            let mut response = Vec::new();
+            stream
+                .read_to_end(&mut response)
+                .map_err(|e| e.to_string())?;
+            let response = String::from_utf8(response).map_err(|e| e.to_string())?;
+            let (head, body) = response
+                .split_once("\r\n\r\n")
+                .ok_or("malformed response")?;
+            let status = head
+                .split(' ')
+                .nth(1)
+                .and_then(|code| StatusCode::from_bytes(code.as_bytes()).ok())
+                .ok_or("malformed status line")?;
+            Ok((status, body.as_bytes().to_vec()))
+        }
+    }
 
-                    if !processed_tool_calls.is_empty() {
-                        message.tool_calls = Some(processed_tool_calls);
+    /// Answers one request with `status` and `body` after `delay`, and hands back
+    /// the raw request it received
+    fn stub_server(
+        status: &'static str,
+        body: &'static str,
+        delay: Duration,
+    ) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            // Read the headers, then as much body as Content-Length announces
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some((head, received)) = text.split_once("\r\n\r\n") {
+                    let length: usize = head
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(": ")?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.parse().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if received.len() >= length {
+                        break;
                     }
                 }
-
-                Ok(message)
             }
+            thread::sleep(delay);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            String::from_utf8(request).unwrap()
+        });
+        (base_url, handle)
     }
 
-    async fn chat_completion_text(&self, messages: Vec<Message>) -> Result<String, String> {
-        let response = self.chat_completion(messages, None).await?;
-        Ok(response.content.unwrap_or_default())
+    fn stub_client(base_url: String, api_key: Option<&str>, timeout: Duration) -> LlmClient {
+        LlmClient {
+            config: LlmConfig {
+                base_url,
+                timeout,
+                ..llm_config()
+            },
+            seed: 7,
+            api_key: api_key.map(str::to_string),
+        }
     }
 
-}
+    // The stub transport never waits on a pollable, so one poll finishes the request
+    fn complete<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("stub request did not complete"),
+        }
+    }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    fn user_message() -> Vec<Message> {
+        vec![Message {
+            role: "user".to_string(),
+            content: Some("hi".to_string()),
+        }]
+    }
 
     #[test]
-    fn test_parsing() {
-        let response = 
-r#"OOH OOH AH AH! THE MONKEY'S GOT A FEW CHOICE WORDS FOR YA, BUT I'LL TRY TO KEEP IT CLEAN AND JUST THROW SOME DARTS!
-
-FOO 17
-WINNER 98
-SUCK 35
-"#;
-        let denoms = vec!["FOO".to_string(), "WINNER".to_string(), "SUCK".to_string()];
-        let parsed = parse_response(response, &denoms).unwrap();
-        assert_eq!(parsed.len(), 3);
-        assert_eq!(*parsed.get("FOO").unwrap(), 17);
-        assert_eq!(*parsed.get("WINNER").unwrap(), 98);
-        assert_eq!(*parsed.get("SUCK").unwrap(), 35);
-
-
-        let normie = normalize_monkey(parsed, Decimal256::from_atomics(15000u128, 0).unwrap());
-        assert_eq!(normie.len(), 3);
-        // assert_eq!(*normie.get("FOO").unwrap(), Decimal256::from_atomics(1700u128, 0).unwrap());
-        // assert_eq!(*normie.get("WINNER").unwrap(), Decimal256::from_atomics(9800u128, 0).unwrap());
-        // assert_eq!(*normie.get("SUCK").unwrap(), Decimal256::from_atomics(3500u128, 0).unwrap());
-        println!("{}", *normie.get("FOO").unwrap());
-        println!("{}", *normie.get("WINNER").unwrap());
-        println!("{}", *normie.get("SUCK").unwrap());
-    }
-}
\ No newline at end of file
+    fn test_llm_client_against_stub_server() {
+        let (base_url, server) = stub_server(
+            "200 OK",
+            r#"{"choices": [{"message": {"role": "assistant", "content": "{\"allocations\": []}"}}]}"#,
+            Duration::ZERO,
+        );
+        let client = stub_client(base_url, Some("test-key"), Duration::from_secs(5));
+        let message = complete(client.send_chat_completion(&StdHttp, &user_message())).unwrap();
+        assert_eq!(message.content.as_deref(), Some(r#"{"allocations": []}"#));
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /v1/chat/completions HTTP/1.1\r\n"));
+        assert!(request.contains("authorization: Bearer test-key\r\n"));
+        assert!(request.contains(r#""model":"stub-model""#));
+        assert!(request.contains(r#""seed":7"#));
+
+        // Without a key no Authorization header is sent
+        let (base_url, server) = stub_server(
+            "200 OK",
+            r#"{"choices": [{"message": {"role": "assistant", "content": "ok"}}]}"#,
+            Duration::ZERO,
+        );
+        let client = stub_client(base_url, None, Duration::from_secs(5));
+        complete(client.send_chat_completion(&StdHttp, &user_message())).unwrap();
+        assert!(!server.join().unwrap().contains("authorization"));
+    }
+
+    #[test]
+    fn test_llm_client_stub_server_errors() {
+        let (base_url, server) = stub_server(
+            "401 Unauthorized",
+            r#"{"error": {"message": "bad key"}}"#,
+            Duration::ZERO,
+        );
+        let client = stub_client(base_url, Some("wrong-key"), Duration::from_secs(5));
+        assert_eq!(
+            complete(client.send_chat_completion(&StdHttp, &user_message())).unwrap_err(),
+            r#"API error: status 401 Unauthorized - {"error": {"message": "bad key"}}"#
+        );
+        server.join().unwrap();
+
+        let (base_url, server) = stub_server("200 OK", "not json", Duration::ZERO);
+        let client = stub_client(base_url, None, Duration::from_secs(5));
+        let err = complete(client.send_chat_completion(&StdHttp, &user_message())).unwrap_err();
+        assert!(err.starts_with("Failed to parse chat completion response"));
+        server.join().unwrap();
+
+        // The configured timeout bounds how long the client waits for the answer
+        let (base_url, server) = stub_server("200 OK", "{}", Duration::from_millis(500));
+        let client = stub_client(base_url, None, Duration::from_millis(50));
+        let err = complete(client.send_chat_completion(&StdHttp, &user_message())).unwrap_err();
+        assert!(err.starts_with("Request failed: "), "{err}");
+        server.join().unwrap();
+
+        let client = stub_client(
+            "http://127.0.0.1:1/v1".to_string(),
+            None,
+            Duration::from_secs(5),
+        );
+        assert_eq!(
+            complete(client.send_chat_completion(&StdHttp, &[])).unwrap_err(),
+            "Messages cannot be empty"
+        );
+    }
+}
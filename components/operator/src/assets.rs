@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::{host, skip::SkipAPIClient};
+
+/// What the operator knows about a denom outside of the vault.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct AssetInfo {
+    #[serde(default)]
+    pub coingecko_id: Option<String>,
+    pub decimals: u8,
+    pub symbol: String,
+}
+
+/// Denom metadata from the `asset_registry` config var (a JSON object keyed by
+/// denom), falling back to Skip's asset list for denoms it doesn't cover.
+pub struct AssetRegistry {
+    assets: BTreeMap<String, AssetInfo>,
+}
+
+impl AssetRegistry {
+    pub fn from_config_var() -> Result<Self> {
+        match host::config_var("asset_registry") {
+            Some(json) => Self::from_json(&json),
+            None => Ok(Self {
+                assets: BTreeMap::new(),
+            }),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let assets = serde_json::from_str(json)
+            .map_err(|e| anyhow!("invalid asset_registry config var: {e}"))?;
+        Ok(Self { assets })
+    }
+
+    /// Looks up `denoms`, asking Skip only when the config is missing some of them.
+    /// Denoms neither source knows are left out.
+    pub async fn resolve(
+        &self,
+        denoms: &[&str],
+        skip_client: &SkipAPIClient,
+    ) -> BTreeMap<String, AssetInfo> {
+        let mut resolved: BTreeMap<String, AssetInfo> = denoms
+            .iter()
+            .filter_map(|denom| Some((denom.to_string(), self.assets.get(*denom)?.clone())))
+            .collect();
+        if resolved.len() == denoms.len() {
+            return resolved;
+        }
+
+        let skip_assets = match skip_client.fungible_assets().await {
+            Ok(assets) => assets,
+            Err(e) => {
+                host::log(
+                    host::LogLevel::Warn,
+                    &format!("Could not load Skip asset list: {e:#}"),
+                );
+                return resolved;
+            }
+        };
+
+        for asset in skip_assets {
+            if resolved.contains_key(&asset.denom) || !denoms.contains(&asset.denom.as_str()) {
+                continue;
+            }
+            let (Some(decimals), Some(symbol)) = (asset.decimals, asset.symbol) else {
+                continue;
+            };
+            resolved.insert(
+                asset.denom,
+                AssetInfo {
+                    coingecko_id: asset.coingecko_id,
+                    decimals,
+                    symbol,
+                },
+            );
+        }
+
+        resolved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DENOM_DYDX: &str = "ibc/2CB87BCE0937B1D1DFCEE79BE4501AAF3C265E923509AEAC410AD85D27F35130";
+
+    #[test]
+    fn registry_config_keeps_high_precision_decimals() {
+        let registry = AssetRegistry::from_json(&format!(
+            r#"{{
+                "untrn": {{ "coingecko_id": "neutron-3", "decimals": 6, "symbol": "NTRN" }},
+                "{DENOM_DYDX}": {{ "coingecko_id": "dydx-chain", "decimals": 18, "symbol": "DYDX" }}
+            }}"#
+        ))
+        .expect("valid registry config");
+
+        assert_eq!(
+            registry.assets.get(DENOM_DYDX),
+            Some(&AssetInfo {
+                coingecko_id: Some("dydx-chain".to_string()),
+                decimals: 18,
+                symbol: "DYDX".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn registry_config_rejects_malformed_json() {
+        assert!(AssetRegistry::from_json(r#"{ "untrn": { "decimals": 6 } }"#).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Context, Result};
+use cosmwasm_std::{Decimal256, Uint128};
+use layer_climb::{
+    prelude::{Address, CosmosAddr},
+    querier::QueryClient,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    host,
+    prices::{PriceAsset, PriceFuture, PriceSource, SOURCE_ASTROPORT},
+};
+
+/// Prices assets by simulating a swap of one whole unit into the quote denom
+/// on an Astroport pair. Assets are matched by the `astroport` pair address in
+/// the asset registry.
+pub struct AstroportPriceSource<'a> {
+    query_client: &'a QueryClient,
+    quote_denom: String,
+    quote_decimals: u8,
+}
+
+impl<'a> AstroportPriceSource<'a> {
+    pub fn new(query_client: &'a QueryClient, quote_denom: String, quote_decimals: u8) -> Self {
+        Self {
+            query_client,
+            quote_denom,
+            quote_decimals,
+        }
+    }
+
+    async fn quote(&self, asset: &PriceAsset, pair: &str) -> Result<Decimal256> {
+        let one_unit = 10u128
+            .checked_pow(u32::from(asset.decimals))
+            .ok_or_else(|| anyhow!("unsupported decimal precision: {}", asset.decimals))?;
+
+        let pair_addr = Address::Cosmos(
+            CosmosAddr::new_str(pair, None)
+                .map_err(|e| anyhow!("invalid Astroport pair address {pair}: {e}"))?,
+        );
+
+        let simulation: SimulationResponse = self
+            .query_client
+            .contract_smart(
+                &pair_addr,
+                &PairQueryMsg::Simulation {
+                    offer_asset: Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: asset.denom.clone(),
+                        },
+                        amount: Uint128::new(one_unit),
+                    },
+                    ask_asset_info: Some(AssetInfo::NativeToken {
+                        denom: self.quote_denom.clone(),
+                    }),
+                },
+            )
+            .await
+            .context("failed to simulate Astroport swap")?;
+
+        // Add back spread and fees to get the pool's own price
+        let amount_out = simulation
+            .return_amount
+            .checked_add(simulation.spread_amount)?
+            .checked_add(simulation.commission_amount)?;
+
+        Decimal256::from_atomics(amount_out, u32::from(self.quote_decimals))
+            .map_err(|e| anyhow!("failed to convert quote to decimal: {e}"))
+    }
+}
+
+impl PriceSource for AstroportPriceSource<'_> {
+    fn name(&self) -> &'static str {
+        SOURCE_ASTROPORT
+    }
+
+    fn query_prices<'a>(&'a self, assets: &'a [PriceAsset]) -> PriceFuture<'a> {
+        Box::pin(async move {
+            let mut prices = BTreeMap::new();
+            for asset in assets {
+                let Some(pair) = asset.source_id(SOURCE_ASTROPORT) else {
+                    continue;
+                };
+
+                match self.quote(asset, pair).await {
+                    Ok(price) => {
+                        prices.insert(asset.denom.clone(), price);
+                    }
+                    Err(e) => host::log(
+                        host::LogLevel::Warn,
+                        &format!("Astroport could not quote {}: {e:#}", asset.denom),
+                    ),
+                }
+            }
+
+            Ok(prices)
+        })
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum PairQueryMsg {
+    Simulation {
+        offer_asset: Asset,
+        ask_asset_info: Option<AssetInfo>,
+    },
+}
+
+#[derive(Serialize)]
+struct Asset {
+    info: AssetInfo,
+    amount: Uint128,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum AssetInfo {
+    NativeToken { denom: String },
+}
+
+#[derive(Deserialize)]
+struct SimulationResponse {
+    return_amount: Uint128,
+    spread_amount: Uint128,
+    commission_amount: Uint128,
+}
@@ -10,9 +10,23 @@ use serde::Deserialize;
 use wavs_wasi_utils::http;
 use wstd::http::Request;
 
-use crate::host;
+use crate::{
+    host,
+    prices::{PriceAsset, PriceFuture, PriceSource, SOURCE_COINGECKO},
+};
 
 const SIMPLE_PRICE_ENDPOINT: &str = "https://api.coingecko.com/api/v3/simple/price";
+const COINS_ENDPOINT: &str = "https://api.coingecko.com/api/v3/coins";
+
+const MILLIS_PER_DAY: f64 = 86_400_000.0;
+
+/// Recent USD price movement of an asset, in percent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MarketStats {
+    pub change_24h: f64,
+    pub change_7d: f64,
+    pub volatility: f64, // Daily standard deviation of returns over the week
+}
 
 pub struct CoinGeckoApiClient {
     api_key: Option<String>,
@@ -99,27 +113,123 @@ impl CoinGeckoApiClient {
 
         Ok(prices)
     }
+
+    pub async fn market_stats(&self, id: &str) -> Result<MarketStats> {
+        let uri = format!("{COINS_ENDPOINT}/{id}/market_chart?vs_currency=usd&days=7");
+
+        let request = http::http_request_get(&uri)?;
+        let request = if let Some(key) = &self.api_key {
+            Self::add_header_to_request(request, "x-cg-demo-api-key", key.clone())?
+        } else {
+            request
+        };
+
+        host::log(
+            host::LogLevel::Info,
+            &format!("Making CoinGecko API request to: {}", uri),
+        );
+
+        let payload: MarketChartResponse = http::fetch_json(request)
+            .await
+            .context("failed to call CoinGecko API")?;
+
+        market_stats_from_prices(&payload.prices)
+            .ok_or_else(|| anyhow!("not enough price history for {id}"))
+    }
 }
 
-// Map built from https://docs.skip.build/go/api-reference/prod/fungible/get-v2fungibleassets
-// This could be a cached query, but we hardcode here for simplicity
-pub fn get_neutron_asset(denom: &str) -> Option<(String, u8)> {
-    let (denom, decimals) = match denom {
-        "ibc/0E293A7622DC9A6439DB60E6D234B5AF446962E27CA3AB44D0590603DFF6968E" => ("bitcoin", 8),
-        "untrn" => ("neutron-3", 6),
-        "ibc/B559A80D62249C8AA07A380E2A2BEA6E5CA9A6F079C912C3A9E9B494105E4F81" => ("usd-coin", 6),
-        "ibc/C4CFF46FD6DE35CA4CF4CE031E643C8FDC9BA4B99AE598E9B0ED98FE3A2319F9" => ("cosmos", 6),
-        "ibc/2CB87BCE0937B1D1DFCEE79BE4501AAF3C265E923509AEAC410AD85D27F35130" => {
-            ("dydx-chain", 18)
-        }
-        "ibc/4D04085167777659C11784A356D6B0D13D5C7F0CE77F7DB1152FE03A2DE2CBF2" => {
-            ("bridged-wrapped-steth-axelar", 18)
-        }
-        _ => return None,
+// `prices` are `[unix millis, price]` pairs in ascending time order
+fn market_stats_from_prices(prices: &[(f64, f64)]) -> Option<MarketStats> {
+    let (last_time, last_price) = *prices.last()?;
+    let (_, first_price) = *prices.first()?;
+    if prices.len() < 3 || first_price <= 0.0 {
+        return None;
+    }
+
+    let day_ago = prices
+        .iter()
+        .rev()
+        .find(|(time, _)| *time <= last_time - MILLIS_PER_DAY)
+        .map(|(_, price)| *price)
+        .unwrap_or(first_price);
+    if day_ago <= 0.0 {
+        return None;
+    }
+
+    let returns: Vec<f64> = prices
+        .windows(2)
+        .filter(|pair| pair[0].1 > 0.0)
+        .map(|pair| pair[1].1 / pair[0].1 - 1.0)
+        .collect();
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / returns.len() as f64;
+
+    // Scale the per-sample deviation to a day's worth of samples
+    let span_days = (last_time - prices[0].0) / MILLIS_PER_DAY;
+    let samples_per_day = if span_days > 0.0 {
+        returns.len() as f64 / span_days
+    } else {
+        1.0
     };
 
-    Some((denom.to_string(), decimals))
+    Some(MarketStats {
+        change_24h: (last_price / day_ago - 1.0) * 100.0,
+        change_7d: (last_price / first_price - 1.0) * 100.0,
+        volatility: variance.sqrt() * samples_per_day.sqrt() * 100.0,
+    })
+}
+
+impl PriceSource for CoinGeckoApiClient {
+    fn name(&self) -> &'static str {
+        SOURCE_COINGECKO
+    }
+
+    fn query_prices<'a>(&'a self, assets: &'a [PriceAsset]) -> PriceFuture<'a> {
+        Box::pin(async move {
+            let queries: Vec<(String, String, u8)> = assets
+                .iter()
+                .filter_map(|asset| {
+                    let id = asset.source_id(SOURCE_COINGECKO)?;
+                    Some((asset.denom.clone(), id.to_string(), asset.decimals))
+                })
+                .collect();
+
+            CoinGeckoApiClient::query_prices(self, &queries, "usd").await
+        })
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct SimplePriceResponse(HashMap<String, HashMap<String, f64>>);
+
+#[derive(Debug, Deserialize)]
+struct MarketChartResponse {
+    prices: Vec<(f64, f64)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: f64 = 3_600_000.0;
+
+    #[test]
+    fn market_stats_measure_changes_and_volatility() {
+        // Hourly prices rising linearly from 1.00 to 1.12 over the week
+        let prices: Vec<(f64, f64)> = (0..=168)
+            .map(|hour| (hour as f64 * HOUR, 1.0 + hour as f64 * 0.12 / 168.0))
+            .collect();
+
+        let stats = market_stats_from_prices(&prices).expect("enough history");
+        assert!((stats.change_7d - 12.0).abs() < 1e-9);
+        let day_ago = 1.0 + 144.0 * 0.12 / 168.0;
+        assert!((stats.change_24h - (1.12 / day_ago - 1.0) * 100.0).abs() < 1e-9);
+        assert!(stats.volatility >= 0.0 && stats.volatility < 0.1);
+    }
+
+    #[test]
+    fn market_stats_need_history() {
+        assert_eq!(market_stats_from_prices(&[]), None);
+        assert_eq!(market_stats_from_prices(&[(0.0, 1.0), (HOUR, 1.1)]), None);
+    }
+}
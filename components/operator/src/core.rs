@@ -3,18 +3,26 @@ use std::{
     str::FromStr,
 };
 
-use ai_portfolio_types::TradeStrategy;
+use ai_portfolio_types::{TradeStrategy, WeightBounds};
 use anyhow::{anyhow, Context, Result};
 use cosmwasm_std::{Decimal256, Timestamp, Uint128, Uint256};
 use layer_climb::{prelude::Address, querier::QueryClient};
 use vault::{
-    Payload, QueryMsg, SwapOperation as VaultSwapOperation, SwapRoute, VaultQueryMsg, VaultState,
+    Payload, QueryMsg, SwapOperation as VaultSwapOperation, SwapRoute, TargetAllocation,
+    TradingLimitsResponse, VaultQueryMsg, VaultState, WhitelistEntry,
 };
 
-use crate::ai::monkey_advisor;
+use crate::ai::{portfolio_advisor, AdvisorConfig, AssetSnapshot};
 use crate::host;
 use crate::{
-    coingecko::{get_neutron_asset, CoinGeckoApiClient},
+    assets::AssetRegistry,
+    coingecko::CoinGeckoApiClient,
+    prices::{
+        build_price_sources, query_aggregated_prices, PriceAsset, PriceConfig, SOURCE_COINGECKO,
+        SOURCE_PYTH,
+    },
+    pyth::PythHermesClient,
+    routes::{PinnedRoute, PinnedRoutes},
     skip::SkipAPIClient,
 };
 use vault::PriceInfo;
@@ -22,22 +30,18 @@ use vault::PriceInfo;
 pub async fn generate_payload(
     query_client: QueryClient,
     addr: Address,
-    trade_strategy: TradeStrategy,
     timestamp: u64,
     chain_id: String,
+    price_config: &PriceConfig,
+    asset_registry: &AssetRegistry,
+    advisor_config: &AdvisorConfig,
+    pinned_routes: &PinnedRoutes,
 ) -> Result<Payload> {
     host::log(
         host::LogLevel::Info,
         &format!("Starting payload generation for vault: {}", addr),
     );
 
-    trade_strategy.validate()?;
-
-    host::log(
-        host::LogLevel::Info,
-        "Trade strategy validated successfully",
-    );
-
     let vault_state: VaultState = query_client
         .contract_smart(&addr, &QueryMsg::Vault(VaultQueryMsg::GetVaultState {}))
         .await
@@ -52,13 +56,45 @@ pub async fn generate_payload(
         ),
     );
 
+    let trading_limits: TradingLimitsResponse = query_client
+        .contract_smart(&addr, &QueryMsg::Vault(VaultQueryMsg::GetTradingLimits {}))
+        .await
+        .context("failed to query trading limits")?;
+
+    // The vault skips any plan that exceeds these, so size the plan to fit
+    let max_routes = trading_limits.limits.max_routes.map(|max| max as usize);
+    let mut trade_budget = match (
+        trading_limits.max_trade_value_usd,
+        trading_limits.remaining_turnover_usd,
+    ) {
+        (Some(per_payload), Some(remaining)) => Some(min_decimal(per_payload, remaining)),
+        (per_payload, remaining) => per_payload.or(remaining),
+    };
+
     let VaultState {
         funds,
         total_pending_assets: _,
         prices,
         tvl,
+        target_allocation,
     } = vault_state;
 
+    // Only rebalance towards the strategy depositors can read on the vault
+    let trade_strategy = target_allocation.map(trade_strategy_from_allocation);
+    match &trade_strategy {
+        Some(strategy) => {
+            strategy.validate()?;
+            host::log(
+                host::LogLevel::Info,
+                "Trade strategy validated successfully",
+            );
+        }
+        None => host::log(
+            host::LogLevel::Info,
+            "Vault has no target allocation, only updating prices",
+        ),
+    }
+
     let mut price_map: BTreeMap<String, AssetPrice> = BTreeMap::new();
     for price in &prices {
         price_map.insert(
@@ -70,42 +106,52 @@ pub async fn generate_payload(
         );
     }
 
-    let allocation_targets = match &trade_strategy {
-        TradeStrategy::AI => {
-            let denoms: Vec<_> = prices.iter().map(|p| p.denom.clone()).collect();
-            // Monkey advisor will pick some allocations
-            let seed = (timestamp % (u32::MAX as u64)) as u32;
-            monkey_advisor(denoms, tvl, seed).await.map_err(anyhow::Error::msg).context("LLM Query")?
-        } 
-        TradeStrategy::Fixed(map) => {
-            let mut targets = BTreeMap::new();
-            for (denom, allocation) in map {
-                let target_value = tvl
-                    .checked_mul(*allocation)
-                    .context("overflow while calculating allocation target")?;
-                targets.insert(denom.clone(), target_value);
-            }
-            targets
+    let skip_client = SkipAPIClient::new(chain_id.clone());
+
+    let registry = query_asset_registry(&query_client, &addr).await?;
+    let mut price_assets = price_lookup_assets(&price_map, &registry);
+    // Operators fetching live quotes a few seconds apart would sign different payloads
+    let consensus = advisor_config.consensus;
+    if consensus {
+        price_assets.retain(|asset| asset.source_id(SOURCE_PYTH).is_some());
+        if !price_assets.is_empty() {
+            let publish_time = timestamp / 1_000_000_000;
+            host::log(
+                host::LogLevel::Info,
+                &format!(
+                    "Consensus mode: reading {} Pyth prices published at {publish_time}, keeping stored prices for the rest",
+                    price_assets.len()
+                ),
+            );
+            let snapshot = PythHermesClient::new()
+                .query_prices_at(&price_assets, publish_time)
+                .await
+                .context("failed to read the Pyth price snapshot")?;
+            apply_fresh_prices(&mut price_map, &price_assets, snapshot);
+        } else {
+            host::log(
+                host::LogLevel::Info,
+                "Consensus mode: no Pyth feeds, keeping stored prices",
+            );
         }
-    };
-
-    let coingecko_client =
-        CoinGeckoApiClient::new(std::env::var("WAVS_ENV_COINGECKO_API_KEY").ok());
-    let asset_queries = price_lookup_assets(&price_map);
-    if !asset_queries.is_empty() {
+    } else if !price_assets.is_empty() {
+        add_fallback_coingecko_ids(&mut price_assets, asset_registry, &skip_client).await;
         host::log(
             host::LogLevel::Info,
             &format!(
-                "Querying prices for {} assets from CoinGecko",
-                asset_queries.len()
+                "Querying prices for {} assets from {}",
+                price_assets.len(),
+                price_config.sources.join(", ")
             ),
         );
-        let fresh_prices = coingecko_client.query_prices(&asset_queries, "usd").await?;
+        let price_sources = build_price_sources(price_config, &query_client, &chain_id, &registry)?;
+        let fresh_prices =
+            query_aggregated_prices(&price_sources, &price_assets, price_config).await;
         host::log(
             host::LogLevel::Info,
             &format!("Retrieved {} fresh prices", fresh_prices.len()),
         );
-        apply_fresh_prices(&mut price_map, fresh_prices);
+        apply_fresh_prices(&mut price_map, &price_assets, fresh_prices);
     } else {
         host::log(host::LogLevel::Info, "No external price queries needed");
     }
@@ -115,8 +161,51 @@ pub async fn generate_payload(
         .map(|coin| (coin.denom.clone(), coin.amount))
         .collect();
 
+    let mut rebalance = trade_strategy.is_some();
+    let allocation_targets = match &trade_strategy {
+        None => BTreeMap::new(),
+        Some(TradeStrategy::AI(bounds)) => {
+            // Live market stats differ between operators, so consensus prompts go without
+            let market_assets: &[PriceAsset] = if consensus { &[] } else { &price_assets };
+            let snapshots =
+                asset_snapshots(&price_map, &holdings, &registry, market_assets, bounds).await?;
+            let seed = (timestamp % (u32::MAX as u64)) as u32;
+            match portfolio_advisor(&snapshots, tvl, seed, advisor_config).await {
+                Ok(advice) => {
+                    host::log(
+                        host::LogLevel::Info,
+                        &format!("Advisor rationale: {}", advice.rationale),
+                    );
+                    allocation_values(&advice.weights, tvl)?
+                }
+                // An operator that quietly held would disagree with those that got an answer
+                Err(e) if consensus => {
+                    return Err(anyhow!("advisor failed in consensus mode: {e}"));
+                }
+                // The vault only stores weight bounds for an AI strategy, so there are no
+                // weights to fall back to: skip this round rather than trade on a bad answer
+                Err(e) => {
+                    host::log(
+                        host::LogLevel::Warn,
+                        &format!("Rejected advisor response, skipping rebalancing: {e}"),
+                    );
+                    rebalance = false;
+                    BTreeMap::new()
+                }
+            }
+        }
+        Some(TradeStrategy::Fixed(map)) => allocation_values(map, tvl)?,
+    };
+
     let mut denominators: BTreeSet<String> = holdings.keys().cloned().collect();
     denominators.extend(allocation_targets.keys().cloned());
+    // The vault rejects routes touching a non-tradable denom, so leave those holdings be
+    let tradable: BTreeSet<&str> = registry
+        .iter()
+        .filter(|entry| entry.tradable)
+        .map(|entry| entry.denom.as_str())
+        .collect();
+    denominators.retain(|denom| tradable.contains(denom.as_str()));
 
     host::log(
         host::LogLevel::Info,
@@ -131,7 +220,6 @@ pub async fn generate_payload(
         tvl,
     )?;
 
-    let skip_client = SkipAPIClient::new(chain_id);
     let mut swap_routes_vec: Vec<SwapRoute> = Vec::new();
 
     host::log(
@@ -143,8 +231,8 @@ pub async fn generate_payload(
         ),
     );
 
-    if !surplus_list.is_empty() && !deficit_list.is_empty() {
-        for deficit in &mut deficit_list {
+    if rebalance && !surplus_list.is_empty() && !deficit_list.is_empty() {
+        'planning: for deficit in &mut deficit_list {
             while !deficit.usd_remaining.is_zero() {
                 let mut total_surplus = sum_decimal(surplus_list.iter().map(|s| s.usd_remaining))?;
                 if total_surplus.is_zero() {
@@ -174,13 +262,35 @@ pub async fn generate_payload(
                     usd_to_trade = min_decimal(usd_to_trade, surplus.usd_remaining);
                     usd_to_trade = min_decimal(usd_to_trade, deficit.usd_remaining);
 
+                    if max_routes.is_some_and(|max| swap_routes_vec.len() >= max) {
+                        break 'planning;
+                    }
+                    if let Some(budget) = trade_budget {
+                        if budget.is_zero() {
+                            break 'planning;
+                        }
+                        usd_to_trade = min_decimal(usd_to_trade, budget);
+                    }
+
                     if usd_to_trade.is_zero() {
                         continue;
                     }
 
+                    let source = if consensus {
+                        let quote = price_map
+                            .get(&deficit.denom)
+                            .filter(|price| !price.display_price.is_zero());
+                        let pinned = pinned_routes.find(&surplus.denom, &deficit.denom);
+                        let (Some(quote), Some(route)) = (quote, pinned) else {
+                            continue;
+                        };
+                        RouteSource::Pinned { route, quote }
+                    } else {
+                        RouteSource::Skip(&skip_client)
+                    };
+
                     let Some(plan) =
-                        build_swap_route(surplus, deficit, usd_to_trade, &skip_client, timestamp)
-                            .await?
+                        build_swap_route(surplus, deficit, usd_to_trade, source, timestamp).await?
                     else {
                         continue;
                     };
@@ -205,6 +315,10 @@ pub async fn generate_payload(
                         .checked_sub(plan.usd_used)
                         .map_err(|e| anyhow!("failed to update total surplus tracker: {e}"))?;
 
+                    if let Some(budget) = trade_budget.as_mut() {
+                        *budget = budget.saturating_sub(plan.usd_used);
+                    }
+
                     swap_routes_vec.push(plan.route);
                     progressed = true;
 
@@ -241,6 +355,104 @@ pub async fn generate_payload(
     })
 }
 
+fn trade_strategy_from_allocation(allocation: TargetAllocation) -> TradeStrategy {
+    match allocation {
+        TargetAllocation::Ai { weight_bounds } => TradeStrategy::AI(
+            weight_bounds
+                .into_iter()
+                .map(|bound| {
+                    (
+                        bound.denom,
+                        WeightBounds {
+                            min: bound.min_weight,
+                            max: bound.max_weight,
+                        },
+                    )
+                })
+                .collect(),
+        ),
+        TargetAllocation::Fixed { weights } => TradeStrategy::Fixed(
+            weights
+                .into_iter()
+                .map(|weight| (weight.denom, weight.weight))
+                .collect(),
+        ),
+    }
+}
+
+fn allocation_values(
+    weights: &BTreeMap<String, Decimal256>,
+    tvl: Decimal256,
+) -> Result<BTreeMap<String, Decimal256>> {
+    let mut targets = BTreeMap::new();
+    for (denom, weight) in weights {
+        let target_value = tvl
+            .checked_mul(*weight)
+            .context("overflow while calculating allocation target")?;
+        targets.insert(denom.clone(), target_value);
+    }
+    Ok(targets)
+}
+
+// What the advisor may allocate between: priced denoms the vault can trade
+async fn asset_snapshots(
+    price_map: &BTreeMap<String, AssetPrice>,
+    holdings: &BTreeMap<String, Uint256>,
+    registry: &[WhitelistEntry],
+    price_assets: &[PriceAsset],
+    bounds: &BTreeMap<String, WeightBounds>,
+) -> Result<Vec<AssetSnapshot>> {
+    let coingecko_client =
+        CoinGeckoApiClient::new(std::env::var("WAVS_ENV_COINGECKO_API_KEY").ok());
+
+    let mut snapshots = Vec::new();
+    for entry in registry.iter().filter(|entry| entry.tradable) {
+        let Some(price) = price_map.get(&entry.denom) else {
+            continue;
+        };
+
+        let amount = holdings
+            .get(&entry.denom)
+            .copied()
+            .unwrap_or_else(Uint256::zero);
+        let amount_decimal = Decimal256::from_atomics(amount, u32::from(price.decimals))
+            .map_err(|e| anyhow!("failed to convert holdings to decimal: {e}"))?;
+        let holding_value_usd = price
+            .display_price
+            .checked_mul(amount_decimal)
+            .map_err(|e| anyhow!("overflow while evaluating holdings value: {e}"))?;
+
+        let coingecko_id = price_assets
+            .iter()
+            .find(|asset| asset.denom == entry.denom)
+            .and_then(|asset| asset.source_id(SOURCE_COINGECKO));
+        let market = match coingecko_id {
+            Some(id) => match coingecko_client.market_stats(id).await {
+                Ok(stats) => Some(stats),
+                Err(e) => {
+                    host::log(
+                        host::LogLevel::Warn,
+                        &format!("No market data for {}: {e:#}", entry.denom),
+                    );
+                    None
+                }
+            },
+            None => None,
+        };
+
+        snapshots.push(AssetSnapshot {
+            denom: entry.denom.clone(),
+            symbol: entry.symbol.clone(),
+            price_usd: price.display_price,
+            holding_value_usd,
+            market,
+            bounds: bounds.get(&entry.denom).copied().unwrap_or_default(),
+        });
+    }
+
+    Ok(snapshots)
+}
+
 #[derive(Clone)]
 struct AssetPrice {
     display_price: Decimal256,
@@ -341,11 +553,22 @@ fn analyze_positions(
     Ok((surplus_list, deficit_list))
 }
 
+enum RouteSource<'a> {
+    // Skip's live route and output estimate
+    Skip(&'a SkipAPIClient),
+    // A route from config with the output priced at the payload's prices, so every
+    // consensus operator plans the same swap
+    Pinned {
+        route: &'a PinnedRoute,
+        quote: &'a AssetPrice,
+    },
+}
+
 async fn build_swap_route(
     surplus: &HoldingSurplus,
     deficit: &HoldingDeficit,
     usd_to_trade: Decimal256,
-    skip_client: &SkipAPIClient,
+    source: RouteSource<'_>,
     timestamp: u64,
 ) -> Result<Option<SwapPlan>> {
     if usd_to_trade.is_zero() {
@@ -371,18 +594,7 @@ async fn build_swap_route(
         .checked_div(surplus.price)
         .map_err(|e| anyhow!("overflow calculating amount in: {e}"))?;
 
-    let mut trade_amount_uint256 = if surplus.decimals > 0 {
-        let pow10 = 10u128
-            .checked_pow(u32::from(surplus.decimals))
-            .ok_or_else(|| anyhow!("unsupported decimal precision: {}", surplus.decimals))?;
-        let scale = Decimal256::from_ratio(Uint256::from(pow10), Uint256::one());
-        display_amount
-            .checked_mul(scale)
-            .map_err(|e| anyhow!("overflow scaling amount in to base units: {e}"))?
-            .to_uint_floor()
-    } else {
-        display_amount.to_uint_floor()
-    };
+    let mut trade_amount_uint256 = to_base_units(display_amount, surplus.decimals)?;
     if trade_amount_uint256.is_zero() {
         return Ok(None);
     }
@@ -417,38 +629,59 @@ async fn build_swap_route(
         return Ok(None);
     }
 
-    let route_plan = skip_client
-        .plan_route(&surplus.denom, &deficit.denom, amount_in)
-        .await?;
+    let (venue_name, operations, estimated_amount_out) = match source {
+        RouteSource::Skip(skip_client) => {
+            let route_plan = skip_client
+                .plan_route(&surplus.denom, &deficit.denom, amount_in)
+                .await?;
 
-    if !route_plan.does_swap || route_plan.source_asset_chain_id != route_plan.dest_asset_chain_id {
-        return Ok(None);
-    }
+            if !route_plan.does_swap
+                || route_plan.source_asset_chain_id != route_plan.dest_asset_chain_id
+            {
+                return Ok(None);
+            }
 
-    let mut operations: Vec<VaultSwapOperation> = Vec::new();
-    let mut venue_name: Option<String> = None;
-
-    for op in &route_plan.operations {
-        if let Some(swap) = &op.swap {
-            venue_name = Some(swap.swap_in.swap_venue.name.clone());
-            for swap_operation in &swap.swap_in.swap_operations {
-                operations.push(VaultSwapOperation {
-                    pool: swap_operation.pool.clone(),
-                    denom_in: swap_operation.denom_in.clone(),
-                    denom_out: swap_operation.denom_out.clone(),
-                    interface: None,
-                });
+            let mut operations: Vec<VaultSwapOperation> = Vec::new();
+            let mut venue_name: Option<String> = None;
+
+            for op in &route_plan.operations {
+                if let Some(swap) = &op.swap {
+                    venue_name = Some(swap.swap_in.swap_venue.name.clone());
+                    for swap_operation in &swap.swap_in.swap_operations {
+                        operations.push(VaultSwapOperation {
+                            pool: swap_operation.pool.clone(),
+                            denom_in: swap_operation.denom_in.clone(),
+                            denom_out: swap_operation.denom_out.clone(),
+                            interface: None,
+                        });
+                    }
+                }
             }
-        }
-    }
 
-    if operations.is_empty() {
-        return Ok(None);
-    }
+            let estimated_amount_out = Uint128::from_str(&route_plan.estimated_amount_out)
+                .map_err(|e| anyhow!("failed to parse estimated output amount: {e}"))?;
+            (
+                venue_name.unwrap_or_else(|| "unknown".to_string()),
+                operations,
+                estimated_amount_out,
+            )
+        }
+        RouteSource::Pinned { route, quote } => {
+            let display_out = usd_used
+                .checked_div(quote.display_price)
+                .map_err(|e| anyhow!("overflow calculating amount out: {e}"))?;
+            let amount_out = to_base_units(display_out, quote.decimals)?;
+            let estimated_amount_out = Uint128::from_str(&amount_out.to_string())
+                .map_err(|e| anyhow!("amount out exceeds supported range: {e}"))?;
+            (
+                route.venue.clone(),
+                route.operations.clone(),
+                estimated_amount_out,
+            )
+        }
+    };
 
-    let estimated_amount_out = Uint128::from_str(&route_plan.estimated_amount_out)
-        .map_err(|e| anyhow!("failed to parse estimated output amount: {e}"))?;
-    if estimated_amount_out.is_zero() {
+    if operations.is_empty() || estimated_amount_out.is_zero() {
         return Ok(None);
     }
 
@@ -458,7 +691,7 @@ async fn build_swap_route(
     let timeout = base_timestamp.plus_seconds(600);
 
     let swap_route = SwapRoute {
-        swap_venue_name: venue_name.unwrap_or_else(|| "unknown".to_string()),
+        swap_venue_name: venue_name,
         offer_denom: surplus.denom.clone(),
         ask_denom: deficit.denom.clone(),
         amount_in,
@@ -475,6 +708,20 @@ async fn build_swap_route(
     }))
 }
 
+fn to_base_units(display_amount: Decimal256, decimals: u8) -> Result<Uint256> {
+    if decimals == 0 {
+        return Ok(display_amount.to_uint_floor());
+    }
+    let pow10 = 10u128
+        .checked_pow(u32::from(decimals))
+        .ok_or_else(|| anyhow!("unsupported decimal precision: {decimals}"))?;
+    let scale = Decimal256::from_ratio(Uint256::from(pow10), Uint256::one());
+    Ok(display_amount
+        .checked_mul(scale)
+        .map_err(|e| anyhow!("overflow scaling amount to base units: {e}"))?
+        .to_uint_floor())
+}
+
 fn min_decimal(left: Decimal256, right: Decimal256) -> Decimal256 {
     if left <= right {
         left
@@ -510,33 +757,112 @@ fn rebalance_tolerance() -> Decimal256 {
     Decimal256::from_ratio(5u128, 1000u128)
 }
 
-fn price_lookup_assets(price_map: &BTreeMap<String, AssetPrice>) -> Vec<(String, String, u8)> {
-    price_map
-        .keys()
-        .filter_map(|denom| {
-            get_neutron_asset(denom).map(|(id, decimals)| (denom.clone(), id, decimals))
-        })
+async fn query_asset_registry(
+    query_client: &QueryClient,
+    addr: &Address,
+) -> Result<Vec<WhitelistEntry>> {
+    const PAGE_SIZE: u32 = 30;
+
+    let mut registry: Vec<WhitelistEntry> = Vec::new();
+    loop {
+        let page: Vec<WhitelistEntry> = query_client
+            .contract_smart(
+                addr,
+                &QueryMsg::Vault(VaultQueryMsg::GetAssetRegistry {
+                    start_after: registry.last().map(|entry| entry.denom.clone()),
+                    limit: Some(PAGE_SIZE),
+                }),
+            )
+            .await
+            .context("failed to query asset registry")?;
+
+        let done = page.len() < PAGE_SIZE as usize;
+        registry.extend(page);
+        if done {
+            return Ok(registry);
+        }
+    }
+}
+
+// Priced denoms the vault still has in its asset registry
+fn price_lookup_assets(
+    price_map: &BTreeMap<String, AssetPrice>,
+    registry: &[WhitelistEntry],
+) -> Vec<PriceAsset> {
+    registry
+        .iter()
+        .filter(|entry| price_map.contains_key(&entry.denom))
+        .map(PriceAsset::from_whitelist_entry)
         .collect()
 }
 
+// CoinGecko ids the vault's registry leaves out come from the operator's asset registry
+async fn add_fallback_coingecko_ids(
+    assets: &mut [PriceAsset],
+    asset_registry: &AssetRegistry,
+    skip_client: &SkipAPIClient,
+) {
+    let missing: Vec<&str> = assets
+        .iter()
+        .filter(|asset| asset.source_id(SOURCE_COINGECKO).is_none())
+        .map(|asset| asset.denom.as_str())
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+
+    let known = asset_registry.resolve(&missing, skip_client).await;
+    for asset in assets.iter_mut() {
+        let Some(info) = known.get(&asset.denom) else {
+            continue;
+        };
+        // A decimals mismatch means the mapping describes a different asset
+        if info.decimals != asset.decimals {
+            host::log(
+                host::LogLevel::Warn,
+                &format!(
+                    "Ignoring asset registry entry for {}: {} decimals, vault expects {}",
+                    asset.denom, info.decimals, asset.decimals
+                ),
+            );
+            continue;
+        }
+        if let Some(id) = &info.coingecko_id {
+            host::log(
+                host::LogLevel::Info,
+                &format!(
+                    "Using CoinGecko id {id} for {} ({})",
+                    info.symbol, asset.denom
+                ),
+            );
+            asset
+                .source_ids
+                .insert(SOURCE_COINGECKO.to_string(), id.clone());
+        }
+    }
+}
+
+// Denoms the sources couldn't agree on are dropped so a stale price isn't resubmitted
 fn apply_fresh_prices(
     price_map: &mut BTreeMap<String, AssetPrice>,
-    fresh: BTreeMap<String, Decimal256>,
+    assets: &[PriceAsset],
+    mut fresh: BTreeMap<String, Decimal256>,
 ) {
-    for (denom, price) in fresh {
-        let decimals_override = get_neutron_asset(&denom).map(|(_, d)| d);
-        price_map
-            .entry(denom.clone())
-            .and_modify(|entry| {
-                entry.display_price = price;
-                if let Some(dec) = decimals_override {
-                    entry.decimals = dec;
-                }
-            })
-            .or_insert(AssetPrice {
-                display_price: price,
-                decimals: decimals_override.unwrap_or(0),
-            });
+    for asset in assets {
+        match fresh.remove(&asset.denom) {
+            Some(price) => {
+                price_map.insert(
+                    asset.denom.clone(),
+                    AssetPrice {
+                        display_price: price,
+                        decimals: asset.decimals,
+                    },
+                );
+            }
+            None => {
+                price_map.remove(&asset.denom);
+            }
+        }
     }
 }
 
@@ -568,19 +894,10 @@ mod tests {
     fn asset_price(denom: &str, display_price: &str) -> AssetPrice {
         AssetPrice {
             display_price: decimal(display_price),
-            decimals: get_neutron_asset(denom)
-                .map(|(_, decimals)| decimals)
-                .unwrap_or(0),
+            decimals: if denom == DENOM_DYDX { 18 } else { 6 },
         }
     }
 
-    #[test]
-    fn asset_price_for_high_precision_assets_has_precision() {
-        let price = asset_price(DENOM_DYDX, "0.310581");
-        assert!(price.display_price > Decimal256::zero());
-        assert_eq!(price.decimals, 18);
-    }
-
     #[test]
     fn analyze_positions_with_display_tvl_has_no_surplus() {
         let mut holdings = BTreeMap::new();
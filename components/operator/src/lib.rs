@@ -4,11 +4,15 @@ wit_bindgen::generate!({
 });
 
 mod ai;
+mod assets;
+mod astroport;
 mod coingecko;
 mod core;
+mod prices;
+mod pyth;
+mod routes;
 mod skip;
 
-use ai_portfolio_types::TradeStrategy;
 use layer_climb::{
     prelude::{AddrKind, Address, ChainConfig, ChainId, CosmosAddr},
     querier::QueryClient,
@@ -16,7 +20,11 @@ use layer_climb::{
 use wstd::runtime::block_on;
 
 use crate::{
+    ai::AdvisorConfig,
+    assets::AssetRegistry,
     core::generate_payload,
+    prices::PriceConfig,
+    routes::PinnedRoutes,
     wavs::{
         operator::input::TriggerData,
         types::{
@@ -78,6 +86,15 @@ impl Guest for Component {
             &format!("Vault address configured: {}", address),
         );
 
+        let price_config = PriceConfig::from_config_vars()
+            .map_err(|e| format!("Invalid price source config: {e}"))?;
+        let asset_registry = AssetRegistry::from_config_var()
+            .map_err(|e| format!("Invalid asset registry config: {e}"))?;
+        let advisor_config = AdvisorConfig::from_config_vars()
+            .map_err(|e| format!("Invalid AI advisor config: {e}"))?;
+        let pinned_routes = PinnedRoutes::from_config_var()
+            .map_err(|e| format!("Invalid consensus routes config: {e}"))?;
+
         let chain_config = host::get_cosmos_chain_config(&chain)
             .ok_or(format!("Could not get chain config for {chain}"))?;
 
@@ -99,20 +116,17 @@ impl Guest for Component {
             .await
             .map_err(|e| format!("Could not establish query client for {chain}: {e}"))?;
 
-            // Get the trade strategy
-            let trade_strategy: TradeStrategy = serde_json::from_str(
-                &host::config_var("trade_strategy").ok_or("Could not get the trade strategy")?,
-            )
-            .map_err(|e| format!("Error parsing the trade strategy: {e}"))?;
-
             host::log(host::LogLevel::Info, "Starting payload generation...");
 
             let result = generate_payload(
                 query_client,
                 Address::Cosmos(address),
-                trade_strategy,
                 trigger_time.nanos,
                 chain_config.chain_id,
+                &price_config,
+                &asset_registry,
+                &advisor_config,
+                &pinned_routes,
             )
             .await;
 
@@ -0,0 +1,344 @@
+use std::{collections::BTreeMap, future::Future, pin::Pin};
+
+use anyhow::{anyhow, bail, Result};
+use cosmwasm_std::Decimal256;
+use layer_climb::querier::QueryClient;
+use vault::WhitelistEntry;
+
+use crate::{
+    astroport::AstroportPriceSource, coingecko::CoinGeckoApiClient, host, pyth::PythHermesClient,
+    skip::SkipPriceSource,
+};
+
+pub const SOURCE_COINGECKO: &str = "coingecko";
+pub const SOURCE_PYTH: &str = "pyth";
+pub const SOURCE_SKIP: &str = "skip";
+pub const SOURCE_ASTROPORT: &str = "astroport";
+
+// Used when the component config leaves the price settings unset
+const DEFAULT_SOURCES: [&str; 4] = [SOURCE_COINGECKO, SOURCE_PYTH, SOURCE_SKIP, SOURCE_ASTROPORT];
+const DEFAULT_MIN_SOURCES: usize = 2;
+const DEFAULT_MAX_SPREAD_BPS: u64 = 500;
+// Neutron USDC, which pool-based sources quote against as one USD
+const DEFAULT_QUOTE_DENOM: &str =
+    "ibc/B559A80D62249C8AA07A380E2A2BEA6E5CA9A6F079C912C3A9E9B494105E4F81";
+
+/// An asset to price, with the ids each source knows it by.
+#[derive(Clone, Debug)]
+pub struct PriceAsset {
+    pub denom: String,
+    pub decimals: u8,
+    pub source_ids: BTreeMap<String, String>, // source name -> id, from the vault's asset registry
+}
+
+impl PriceAsset {
+    pub fn from_whitelist_entry(entry: &WhitelistEntry) -> Self {
+        Self {
+            denom: entry.denom.clone(),
+            decimals: entry.decimals,
+            source_ids: entry
+                .price_source_ids
+                .iter()
+                .map(|source_id| (source_id.source.clone(), source_id.id.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn source_id(&self, source: &str) -> Option<&str> {
+        self.source_ids.get(source).map(String::as_str)
+    }
+}
+
+pub type PriceFuture<'a> = Pin<Box<dyn Future<Output = Result<BTreeMap<String, Decimal256>>> + 'a>>;
+
+/// A provider of USD display prices. Sources return prices only for the assets
+/// they know; an asset missing from the result is simply not quoted.
+pub trait PriceSource {
+    fn name(&self) -> &'static str;
+
+    fn query_prices<'a>(&'a self, assets: &'a [PriceAsset]) -> PriceFuture<'a>;
+}
+
+/// How the operator combines price sources, read from the component config.
+#[derive(Clone, Debug)]
+pub struct PriceConfig {
+    pub sources: Vec<String>,
+    pub min_sources: usize, // Quotes that must agree before a denom is priced
+    pub max_spread: Decimal256, // Largest accepted distance from the median, as a fraction of it
+    pub quote_denom: String, // Denom pool-based sources price against
+}
+
+impl PriceConfig {
+    pub fn from_config_vars() -> Result<Self> {
+        let sources = match host::config_var("price_sources") {
+            Some(list) => list
+                .split(',')
+                .map(|source| source.trim().to_string())
+                .filter(|source| !source.is_empty())
+                .collect(),
+            None => DEFAULT_SOURCES
+                .iter()
+                .map(|source| source.to_string())
+                .collect(),
+        };
+
+        let min_sources = match host::config_var("price_min_sources") {
+            Some(value) => value
+                .parse()
+                .map_err(|e| anyhow!("invalid price_min_sources '{value}': {e}"))?,
+            None => DEFAULT_MIN_SOURCES,
+        };
+
+        let max_spread_bps: u64 = match host::config_var("price_max_spread_bps") {
+            Some(value) => value
+                .parse()
+                .map_err(|e| anyhow!("invalid price_max_spread_bps '{value}': {e}"))?,
+            None => DEFAULT_MAX_SPREAD_BPS,
+        };
+
+        let quote_denom = host::config_var("price_quote_denom")
+            .unwrap_or_else(|| DEFAULT_QUOTE_DENOM.to_string());
+
+        let config = Self {
+            sources,
+            min_sources,
+            max_spread: Decimal256::from_ratio(max_spread_bps, 10_000u128),
+            quote_denom,
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.sources.is_empty() {
+            bail!("at least one price source must be configured");
+        }
+        if self.min_sources == 0 {
+            bail!("price_min_sources must be at least 1");
+        }
+        if self.min_sources > self.sources.len() {
+            bail!(
+                "price_min_sources is {} but only {} price sources are configured",
+                self.min_sources,
+                self.sources.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Builds the configured sources. `registry` supplies the quote denom's decimals;
+/// pool-based sources are left out for vaults that don't list the quote denom.
+pub fn build_price_sources<'a>(
+    config: &PriceConfig,
+    query_client: &'a QueryClient,
+    chain_id: &str,
+    registry: &[WhitelistEntry],
+) -> Result<Vec<Box<dyn PriceSource + 'a>>> {
+    let quote_decimals = registry
+        .iter()
+        .find(|entry| entry.denom == config.quote_denom)
+        .map(|entry| entry.decimals);
+
+    let mut sources: Vec<Box<dyn PriceSource + 'a>> = Vec::new();
+    for name in &config.sources {
+        match name.as_str() {
+            SOURCE_COINGECKO => sources.push(Box::new(CoinGeckoApiClient::new(
+                std::env::var("WAVS_ENV_COINGECKO_API_KEY").ok(),
+            ))),
+            SOURCE_PYTH => sources.push(Box::new(PythHermesClient::new())),
+            SOURCE_SKIP | SOURCE_ASTROPORT => {
+                let Some(quote_decimals) = quote_decimals else {
+                    host::log(
+                        host::LogLevel::Warn,
+                        &format!(
+                            "Not using {name}: price quote denom {} is not in the vault's asset registry",
+                            config.quote_denom
+                        ),
+                    );
+                    continue;
+                };
+                if name == SOURCE_SKIP {
+                    sources.push(Box::new(SkipPriceSource::new(
+                        chain_id.to_string(),
+                        config.quote_denom.clone(),
+                        quote_decimals,
+                    )));
+                } else {
+                    sources.push(Box::new(AstroportPriceSource::new(
+                        query_client,
+                        config.quote_denom.clone(),
+                        quote_decimals,
+                    )));
+                }
+            }
+            other => bail!("unknown price source: {other}"),
+        }
+    }
+
+    if sources.len() < config.min_sources {
+        host::log(
+            host::LogLevel::Warn,
+            &format!(
+                "Only {} price sources are usable but {} must agree, no denom will be priced",
+                sources.len(),
+                config.min_sources
+            ),
+        );
+    }
+
+    Ok(sources)
+}
+
+/// Queries every source and aggregates the quotes per denom. A failing source
+/// is logged and left out rather than failing the whole payload.
+pub async fn query_aggregated_prices(
+    sources: &[Box<dyn PriceSource + '_>],
+    assets: &[PriceAsset],
+    config: &PriceConfig,
+) -> BTreeMap<String, Decimal256> {
+    let mut quotes: BTreeMap<String, Vec<(&'static str, Decimal256)>> = BTreeMap::new();
+
+    for source in sources {
+        match source.query_prices(assets).await {
+            Ok(prices) => {
+                host::log(
+                    host::LogLevel::Info,
+                    &format!("{} returned {} prices", source.name(), prices.len()),
+                );
+                for (denom, price) in prices {
+                    if price.is_zero() {
+                        continue;
+                    }
+                    quotes
+                        .entry(denom)
+                        .or_default()
+                        .push((source.name(), price));
+                }
+            }
+            Err(e) => host::log(
+                host::LogLevel::Warn,
+                &format!("Price source {} failed: {e:#}", source.name()),
+            ),
+        }
+    }
+
+    let mut prices = BTreeMap::new();
+    for asset in assets {
+        let denom_quotes = quotes.remove(&asset.denom).unwrap_or_default();
+        match aggregate_quotes(&denom_quotes, config.min_sources, config.max_spread) {
+            Some(price) => {
+                prices.insert(asset.denom.clone(), price);
+            }
+            None => host::log(
+                host::LogLevel::Warn,
+                &format!(
+                    "Skipping {}: fewer than {} price sources agree ({:?})",
+                    asset.denom, config.min_sources, denom_quotes
+                ),
+            ),
+        }
+    }
+
+    prices
+}
+
+/// Median of the quotes within `max_spread` of the overall median, or `None`
+/// when fewer than `min_sources` of them remain.
+fn aggregate_quotes(
+    quotes: &[(&'static str, Decimal256)],
+    min_sources: usize,
+    max_spread: Decimal256,
+) -> Option<Decimal256> {
+    let all: Vec<Decimal256> = quotes.iter().map(|(_, price)| *price).collect();
+    let center = median(all.clone())?;
+
+    let agreeing: Vec<Decimal256> = all
+        .into_iter()
+        .filter(|price| {
+            price
+                .abs_diff(center)
+                .checked_div(center)
+                .is_ok_and(|spread| spread <= max_spread)
+        })
+        .collect();
+
+    if agreeing.len() < min_sources {
+        return None;
+    }
+
+    median(agreeing)
+}
+
+fn median(mut values: Vec<Decimal256>) -> Option<Decimal256> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort();
+
+    let mid = values.len() / 2;
+    if values.len() % 2 == 1 {
+        return Some(values[mid]);
+    }
+
+    let sum = values[mid - 1].checked_add(values[mid]).ok()?;
+    sum.checked_div(Decimal256::from_ratio(2u128, 1u128)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn decimal(value: &str) -> Decimal256 {
+        Decimal256::from_str(value).expect("valid decimal string")
+    }
+
+    fn quotes(prices: &[&str]) -> Vec<(&'static str, Decimal256)> {
+        prices
+            .iter()
+            .map(|price| ("test", decimal(price)))
+            .collect()
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(vec![]), None);
+        assert_eq!(
+            median(vec![decimal("3"), decimal("1"), decimal("2")]),
+            Some(decimal("2"))
+        );
+        assert_eq!(
+            median(vec![decimal("4"), decimal("1"), decimal("2"), decimal("3")]),
+            Some(decimal("2.5"))
+        );
+    }
+
+    #[test]
+    fn aggregate_drops_outliers_past_the_spread() {
+        // 1.50 is ~48% away from the median of 1.015 and is ignored
+        let price = aggregate_quotes(
+            &quotes(&["1.00", "1.01", "1.02", "1.50"]),
+            3,
+            decimal("0.02"),
+        );
+        assert_eq!(price, Some(decimal("1.01")));
+    }
+
+    #[test]
+    fn aggregate_skips_denoms_without_enough_agreeing_sources() {
+        assert_eq!(
+            aggregate_quotes(&quotes(&["1.00"]), 2, decimal("0.02")),
+            None
+        );
+        assert_eq!(
+            aggregate_quotes(&quotes(&["1.00", "2.00", "3.00"]), 2, decimal("0.02")),
+            None
+        );
+        assert_eq!(
+            aggregate_quotes(&quotes(&["1.00", "1.01"]), 2, decimal("0.02")),
+            Some(decimal("1.005"))
+        );
+    }
+}
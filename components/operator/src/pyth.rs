@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Context, Result};
+use cosmwasm_std::{Decimal256, Uint256};
+use serde::Deserialize;
+use wavs_wasi_utils::http;
+
+use crate::{
+    host,
+    prices::{PriceAsset, PriceFuture, PriceSource, SOURCE_PYTH},
+};
+
+const PRICE_ENDPOINT: &str = "https://hermes.pyth.network/v2/updates/price";
+
+/// Reads the latest Pyth prices from Hermes. Assets are matched by the `pyth`
+/// feed id in the asset registry.
+#[derive(Default)]
+pub struct PythHermesClient;
+
+impl PythHermesClient {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub async fn query_prices(
+        &self,
+        assets: &[PriceAsset],
+    ) -> Result<BTreeMap<String, Decimal256>> {
+        self.fetch_prices(assets, "latest").await
+    }
+
+    /// Prices as published at `publish_time` (unix seconds), the same for
+    /// every caller no matter when they ask.
+    pub async fn query_prices_at(
+        &self,
+        assets: &[PriceAsset],
+        publish_time: u64,
+    ) -> Result<BTreeMap<String, Decimal256>> {
+        self.fetch_prices(assets, &publish_time.to_string()).await
+    }
+
+    async fn fetch_prices(
+        &self,
+        assets: &[PriceAsset],
+        update: &str,
+    ) -> Result<BTreeMap<String, Decimal256>> {
+        // Hermes returns feed ids lowercase and without the 0x prefix
+        let feeds: BTreeMap<String, &str> = assets
+            .iter()
+            .filter_map(|asset| {
+                let id = asset.source_id(SOURCE_PYTH)?;
+                Some((normalize_feed_id(id), asset.denom.as_str()))
+            })
+            .collect();
+        if feeds.is_empty() {
+            return Ok(BTreeMap::new());
+        }
+
+        let ids_param = feeds
+            .keys()
+            .map(|id| format!("ids[]={id}"))
+            .collect::<Vec<String>>()
+            .join("&");
+        let uri = format!("{PRICE_ENDPOINT}/{update}?{ids_param}&parsed=true");
+
+        host::log(
+            host::LogLevel::Info,
+            &format!("Making Pyth Hermes request to: {}", uri),
+        );
+
+        let response: PriceUpdateResponse = http::fetch_json(http::http_request_get(&uri)?)
+            .await
+            .context("failed to call Pyth Hermes API")?;
+
+        let mut prices = BTreeMap::new();
+        for update in response.parsed {
+            let Some(denom) = feeds.get(&normalize_feed_id(&update.id)) else {
+                continue;
+            };
+            if let Some(price) = update.price.to_decimal()? {
+                prices.insert(denom.to_string(), price);
+            }
+        }
+
+        Ok(prices)
+    }
+}
+
+impl PriceSource for PythHermesClient {
+    fn name(&self) -> &'static str {
+        SOURCE_PYTH
+    }
+
+    fn query_prices<'a>(&'a self, assets: &'a [PriceAsset]) -> PriceFuture<'a> {
+        Box::pin(PythHermesClient::query_prices(self, assets))
+    }
+}
+
+fn normalize_feed_id(id: &str) -> String {
+    id.trim_start_matches("0x").to_lowercase()
+}
+
+#[derive(Debug, Deserialize)]
+struct PriceUpdateResponse {
+    parsed: Vec<ParsedPriceUpdate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ParsedPriceUpdate {
+    id: String,
+    price: PythPrice,
+}
+
+#[derive(Debug, Deserialize)]
+struct PythPrice {
+    price: String, // Signed integer, scaled by 10^expo
+    expo: i32,
+}
+
+impl PythPrice {
+    // Non-positive prices carry no usable quote
+    fn to_decimal(&self) -> Result<Option<Decimal256>> {
+        let raw: i128 = self
+            .price
+            .parse()
+            .map_err(|e| anyhow!("failed to parse Pyth price '{}': {e}", self.price))?;
+        if raw <= 0 {
+            return Ok(None);
+        }
+        let raw = Uint256::from(raw as u128);
+
+        let price = if self.expo <= 0 {
+            Decimal256::from_atomics(raw, self.expo.unsigned_abs())
+                .map_err(|e| anyhow!("failed to convert Pyth price to decimal: {e}"))?
+        } else {
+            let scale = Uint256::from(10u128).checked_pow(self.expo.unsigned_abs())?;
+            Decimal256::from_ratio(raw.checked_mul(scale)?, Uint256::one())
+        };
+
+        Ok(Some(price))
+    }
+}
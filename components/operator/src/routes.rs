@@ -0,0 +1,113 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use vault::SwapOperation;
+
+use crate::host;
+
+/// A swap path fixed in config rather than asked of Skip.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct PinnedRoute {
+    pub venue: String,
+    pub operations: Vec<SwapOperation>,
+}
+
+/// Routes from the `consensus_routes` config var, a JSON list of pinned routes.
+/// Consensus operators only trade along these, so they all plan the same pools
+/// no matter what Skip's route API answers at the time.
+pub struct PinnedRoutes {
+    routes: Vec<PinnedRoute>,
+}
+
+impl PinnedRoutes {
+    pub fn from_config_var() -> Result<Self> {
+        match host::config_var("consensus_routes") {
+            Some(json) => Self::from_json(&json),
+            None => Ok(Self { routes: Vec::new() }),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let routes: Vec<PinnedRoute> = serde_json::from_str(json)
+            .map_err(|e| anyhow!("invalid consensus_routes config var: {e}"))?;
+        for route in &routes {
+            if route.operations.is_empty() {
+                return Err(anyhow!("pinned route on {} has no operations", route.venue));
+            }
+            // Each hop has to start where the previous one ended
+            for hops in route.operations.windows(2) {
+                if hops[0].denom_out != hops[1].denom_in {
+                    return Err(anyhow!(
+                        "pinned route on {} breaks between pools {} and {}",
+                        route.venue,
+                        hops[0].pool,
+                        hops[1].pool
+                    ));
+                }
+            }
+        }
+        Ok(Self { routes })
+    }
+
+    /// The first pinned route from `offer_denom` to `ask_denom`, if any.
+    pub fn find(&self, offer_denom: &str, ask_denom: &str) -> Option<&PinnedRoute> {
+        self.routes.iter().find(|route| {
+            route
+                .operations
+                .first()
+                .is_some_and(|op| op.denom_in == offer_denom)
+                && route
+                    .operations
+                    .last()
+                    .is_some_and(|op| op.denom_out == ask_denom)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinned_routes_match_by_first_and_last_denom() {
+        let routes = PinnedRoutes::from_json(
+            r#"[
+                {
+                    "venue": "neutron-astroport",
+                    "operations": [
+                        { "pool": "pool-1", "denom_in": "untrn", "denom_out": "uusdc" },
+                        { "pool": "pool-2", "denom_in": "uusdc", "denom_out": "uatom" }
+                    ]
+                }
+            ]"#,
+        )
+        .expect("valid routes config");
+
+        let route = routes.find("untrn", "uatom").expect("pinned route");
+        assert_eq!(route.venue, "neutron-astroport");
+        assert_eq!(route.operations.len(), 2);
+        assert!(routes.find("uatom", "untrn").is_none());
+        assert!(routes.find("untrn", "uusdc").is_none());
+    }
+
+    #[test]
+    fn pinned_routes_reject_broken_paths() {
+        let err = PinnedRoutes::from_json(
+            r#"[
+                {
+                    "venue": "neutron-astroport",
+                    "operations": [
+                        { "pool": "pool-1", "denom_in": "untrn", "denom_out": "uusdc" },
+                        { "pool": "pool-2", "denom_in": "uosmo", "denom_out": "uatom" }
+                    ]
+                }
+            ]"#,
+        )
+        .err()
+        .expect("broken path is rejected");
+        assert_eq!(
+            err.to_string(),
+            "pinned route on neutron-astroport breaks between pools pool-1 and pool-2"
+        );
+        assert!(PinnedRoutes::from_json(r#"[{ "venue": "v", "operations": [] }]"#).is_err());
+    }
+}
@@ -1,14 +1,20 @@
-use anyhow::{Context, Result};
-use cosmwasm_std::Uint128;
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+use cosmwasm_std::{Decimal256, Uint128};
 use serde::Serialize;
 use wavs_wasi_utils::http;
 
 mod types;
 
-use crate::host;
+use crate::{
+    host,
+    prices::{PriceAsset, PriceFuture, PriceSource, SOURCE_SKIP},
+};
 pub use types::*;
 
 pub const ROUTE: &str = "https://api.skip.build/v2/fungible/route";
+pub const ASSETS: &str = "https://api.skip.build/v2/fungible/assets";
 
 pub struct SkipAPIClient {
     chain_id: String, // source = dest
@@ -79,6 +85,99 @@ impl SkipAPIClient {
 
         Ok(route_plan)
     }
+
+    /// Every asset Skip lists on this chain, including IBC and CW20 denoms
+    pub async fn fungible_assets(&self) -> Result<Vec<FungibleAsset>> {
+        let uri = format!(
+            "{}?chain_ids={}&native_only=false&include_cw20_assets=true",
+            ASSETS, self.chain_id
+        );
+
+        host::log(
+            host::LogLevel::Info,
+            &format!("Making Skip API request to: {}", uri),
+        );
+
+        let mut response: AssetsResponse = http::fetch_json(http::http_request_get(&uri)?)
+            .await
+            .context("failed to fetch Skip assets response")?;
+
+        Ok(response
+            .chain_to_assets_map
+            .remove(&self.chain_id)
+            .map(|chain| chain.assets)
+            .unwrap_or_default())
+    }
+}
+
+/// Prices each asset by the Skip route quote for swapping one whole unit of it
+/// into the quote denom.
+pub struct SkipPriceSource {
+    client: SkipAPIClient,
+    quote_denom: String,
+    quote_decimals: u8,
+}
+
+impl SkipPriceSource {
+    pub fn new(chain_id: String, quote_denom: String, quote_decimals: u8) -> Self {
+        Self {
+            client: SkipAPIClient::new(chain_id),
+            quote_denom,
+            quote_decimals,
+        }
+    }
+
+    async fn quote(&self, asset: &PriceAsset) -> Result<Option<Decimal256>> {
+        let one_unit = 10u128
+            .checked_pow(u32::from(asset.decimals))
+            .ok_or_else(|| anyhow!("unsupported decimal precision: {}", asset.decimals))?;
+
+        let route_plan = self
+            .client
+            .plan_route(&asset.denom, &self.quote_denom, Uint128::new(one_unit))
+            .await?;
+        if !route_plan.does_swap {
+            return Ok(None);
+        }
+
+        let amount_out = Uint128::from_str(&route_plan.estimated_amount_out)
+            .map_err(|e| anyhow!("failed to parse estimated output amount: {e}"))?;
+        let price = Decimal256::from_atomics(amount_out, u32::from(self.quote_decimals))
+            .map_err(|e| anyhow!("failed to convert quote to decimal: {e}"))?;
+
+        Ok(Some(price))
+    }
+}
+
+impl PriceSource for SkipPriceSource {
+    fn name(&self) -> &'static str {
+        SOURCE_SKIP
+    }
+
+    fn query_prices<'a>(&'a self, assets: &'a [PriceAsset]) -> PriceFuture<'a> {
+        Box::pin(async move {
+            let mut prices = BTreeMap::new();
+            for asset in assets {
+                if asset.denom == self.quote_denom {
+                    continue;
+                }
+
+                // One unroutable asset shouldn't drop the quotes for the rest
+                match self.quote(asset).await {
+                    Ok(Some(price)) => {
+                        prices.insert(asset.denom.clone(), price);
+                    }
+                    Ok(None) => {}
+                    Err(e) => host::log(
+                        host::LogLevel::Warn,
+                        &format!("Skip could not quote {}: {e:#}", asset.denom),
+                    ),
+                }
+            }
+
+            Ok(prices)
+        })
+    }
 }
 
 #[derive(Serialize)]
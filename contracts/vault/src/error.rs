@@ -1,4 +1,7 @@
-use cosmwasm_std::{CheckedFromRatioError, Decimal256RangeExceeded, OverflowError, StdError};
+use cosmwasm_std::{
+    CheckedFromRatioError, Decimal256, Decimal256RangeExceeded, OverflowError, StdError, Timestamp,
+    Uint256,
+};
 use cw_ownable::OwnershipError;
 use cw_utils::PaymentError;
 use thiserror::Error;
@@ -32,12 +35,29 @@ pub enum ContractError {
     #[error("Insufficient shares")]
     InsufficientShares {},
 
+    #[error(
+        "Withdrawing {shares} shares requires attaching exactly that many share tokens, got {sent}"
+    )]
+    ShareFundsMismatch { shares: Uint256, sent: Uint256 },
+
+    #[error("Vault shares are already tokenized as {denom}")]
+    SharesAlreadyTokenized { denom: String },
+
     #[error("Price must be greater than zero for denom: {denom}")]
     ZeroPrice { denom: String },
 
     #[error("Deposit already completed: {deposit_id}")]
     DepositAlreadyCompleted { deposit_id: u64 },
 
+    #[error("Deposit already cancelled: {deposit_id}")]
+    DepositAlreadyCancelled { deposit_id: u64 },
+
+    #[error("Deposit {deposit_id} can only be reclaimed by its owner before {expires_at}")]
+    DepositNotExpired {
+        deposit_id: u64,
+        expires_at: Timestamp,
+    },
+
     #[error("Unknown reply id: {id}")]
     UnknownReplyId { id: u64 },
 
@@ -55,4 +75,169 @@ pub enum ContractError {
 
     #[error("Decimal precision {decimals} not supported for denom: {denom}")]
     UnsupportedDecimalPrecision { denom: String, decimals: u8 },
+
+    #[error("Price for {denom} uses {decimals} decimals but the asset registry has {expected}")]
+    DecimalsMismatch {
+        denom: String,
+        decimals: u8,
+        expected: u8,
+    },
+
+    #[error("Deposits of {denom} are disabled")]
+    DenomNotDepositable { denom: String },
+
+    #[error("Deposit of {denom} is below the minimum of {min_deposit}")]
+    DepositBelowMinimum { denom: String, min_deposit: Uint256 },
+
+    #[error("Trading {denom} is disabled")]
+    DenomNotTradable { denom: String },
+
+    #[error("Fee rate {rate} exceeds maximum of {max}")]
+    FeeRateTooHigh { rate: Decimal256, max: Decimal256 },
+
+    #[error("Fee recipient must be set when fees are enabled")]
+    FeeRecipientRequired {},
+
+    #[error("Fees can only be settled against prices updated within the last {max_age_seconds}s")]
+    FeePricesStale { max_age_seconds: u64 },
+
+    #[error("Envelope timestamp {timestamp} is not newer than last accepted {last_accepted}")]
+    StaleEnvelope {
+        timestamp: Timestamp,
+        last_accepted: Timestamp,
+    },
+
+    #[error("Envelope timestamp {timestamp} is older than the maximum age of {max_age_seconds}s")]
+    EnvelopeTooOld {
+        timestamp: Timestamp,
+        max_age_seconds: u64,
+    },
+
+    #[error(
+        "Envelope timestamp {timestamp} is more than {max_skew_seconds}s ahead of the block time"
+    )]
+    EnvelopeFromFuture {
+        timestamp: Timestamp,
+        max_skew_seconds: u64,
+    },
+
+    #[error("Maximum payload age must be greater than zero")]
+    ZeroMaxPayloadAge {},
+
+    #[error("Price for {denom} moved {deviation_bps} bps, exceeding the limit of {max_deviation_bps} bps")]
+    PriceDeviationExceeded {
+        denom: String,
+        deviation_bps: u64,
+        max_deviation_bps: u64,
+    },
+
+    #[error("Price guard for {denom} must allow a non-zero deviation")]
+    InvalidPriceGuard { denom: String },
+
+    #[error("Maximum slippage of {max_slippage_bps} bps exceeds 10000 bps")]
+    InvalidMaxSlippage { max_slippage_bps: u64 },
+
+    #[error("Max trade fraction {fraction} must not exceed 1")]
+    InvalidMaxTradeFraction { fraction: Decimal256 },
+
+    #[error("Address {address} is not allowed to deposit")]
+    DepositorNotAllowed { address: String },
+
+    #[error("Swap venue {venue} is not on the swap allowlist")]
+    SwapVenueNotAllowed { venue: String },
+
+    #[error("Pool {pool} on {venue} is not on the swap allowlist")]
+    SwapPoolNotAllowed { venue: String, pool: String },
+
+    #[error("Deposit would take the vault over its TVL cap of ${max_tvl_usd}")]
+    TvlCapExceeded { max_tvl_usd: Decimal256 },
+
+    #[error("Deposit would take {denom} over its cap of {cap}")]
+    DenomCapExceeded { denom: String, cap: Uint256 },
+
+    #[error("Deposit would take the position over its cap of ${max_position_usd}")]
+    PositionCapExceeded { max_position_usd: Decimal256 },
+
+    #[error("Duplicate deposit cap for denom: {denom}")]
+    DuplicateDenomCap { denom: String },
+
+    #[error("Target allocation weights sum to {total}, expected 1")]
+    AllocationNotNormalized { total: Decimal256 },
+
+    #[error("Duplicate target allocation for denom: {denom}")]
+    DuplicateAllocationDenom { denom: String },
+
+    #[error("Weight bounds for {denom} must satisfy min <= max <= 1")]
+    InvalidWeightBound { denom: String },
+
+    #[error("Minimum weights sum to {total_min}, more than 1")]
+    WeightBoundsInfeasible { total_min: Decimal256 },
+
+    #[error("Strategy change must set a target allocation or a timelock")]
+    EmptyStrategyChange {},
+
+    #[error("No strategy change is pending")]
+    NoPendingStrategyChange {},
+
+    #[error("Strategy change cannot be executed before {executable_at}")]
+    StrategyTimelockActive { executable_at: Timestamp },
+
+    #[error("Deposit expiry must be greater than zero")]
+    ZeroDepositExpiry {},
+
+    #[error("Deposits are paused")]
+    DepositsPaused {},
+
+    #[error("Price processing is paused")]
+    PriceProcessingPaused {},
+
+    #[error("Vault is in emergency mode, only withdrawals are allowed")]
+    EmergencyMode {},
+
+    #[error("Only the owner can unpause the vault")]
+    GuardianCannotUnpause {},
+
+    #[error("Trading is paused")]
+    TradingPaused {},
+
+    #[error("Withdrawal queue is disabled")]
+    WithdrawalQueueDisabled {},
+
+    #[error("Withdrawals are queued, use RequestWithdraw")]
+    WithdrawalsQueued {},
+
+    #[error("Withdrawal already completed: {withdraw_id}")]
+    WithdrawAlreadyCompleted { withdraw_id: u64 },
+
+    #[error("Withdrawal already cancelled: {withdraw_id}")]
+    WithdrawAlreadyCancelled { withdraw_id: u64 },
+
+    #[error("Bank balance {bank_balance} of {denom} does not cover the {pending} held for pending deposits")]
+    PendingDepositsUnbacked {
+        denom: String,
+        bank_balance: Uint256,
+        pending: Uint256,
+    },
+
+    #[error("Unexpected swap reply for withdrawal {withdrawal_id}")]
+    UnexpectedWithdrawSwapReply { withdrawal_id: u64 },
+
+    #[error("Swap for withdrawal {withdrawal_id} paid out {received} instead of {expected}")]
+    WithdrawSwapDenomMismatch {
+        withdrawal_id: u64,
+        expected: String,
+        received: String,
+    },
+
+    #[error("No withdrawal route swaps {denom} into {target}")]
+    MissingWithdrawRoute { denom: String, target: String },
+
+    #[error("No price available for {denom}")]
+    MissingPrice { denom: String },
+
+    #[error("Withdrawal output {amount_out} is below the minimum {min_out}")]
+    WithdrawOutputTooLow {
+        amount_out: Uint256,
+        min_out: Uint256,
+    },
 }
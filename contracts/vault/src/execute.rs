@@ -1,6 +1,7 @@
 use cosmwasm_std::{
-    ensure_eq, to_json_binary, BankMsg, Coin, CosmosMsg, Decimal256, DepsMut, Env, MessageInfo,
-    Response, SubMsg, Uint256, WasmMsg,
+    ensure_eq, from_json, to_json_binary, to_json_string, Addr, BankMsg, Coin, CosmosMsg,
+    Decimal256, DepsMut, Env, Event, MessageInfo, QuerierWrapper, Reply, Response, StdResult,
+    SubMsg, SubMsgResult, Timestamp, Uint128, Uint256, Uint64, WasmMsg,
 };
 use cw_ownable::assert_owner;
 use wavs_types::contracts::cosmwasm::service_handler::{WavsEnvelope, WavsSignatureData};
@@ -9,19 +10,71 @@ use wavs_types::contracts::cosmwasm::service_manager::{
 };
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, PriceInfo, VaultExecuteMsg};
+use crate::msg::{
+    AllowedPool, AssetDiscrepancy, DepositCaps, ExecuteMsg, NavSnapshot, PriceGuard,
+    PriceGuardAction, PriceInfo, StrategyChange, SurplusPolicy, TargetAllocation, TradingLimits,
+    VaultExecuteMsg, WeightBound, WhitelistEntry,
+};
 use crate::skip_entry::{
     Action as SkipAction, Asset as SkipAsset, ExecuteMsg as SkipExecuteMsg, Swap as SkipSwap,
-    SwapExactAssetIn, SwapRoute,
+    SwapExactAssetIn, SwapOperation, SwapRoute, WithdrawRoute,
 };
 use crate::state::{
-    self, StoredPriceInfo, TradeInfo, DEPOSIT_ID_COUNTER, DEPOSIT_REQUESTS, PRICES,
-    SKIP_ENTRY_POINT, TOTAL_PENDING_ASSETS, TOTAL_SHARES, TRADE_TRACKER, USER_SHARES, VAULT_ASSETS,
-    VAULT_VALUE_DEPOSITED, WHITELISTED_DENOMS,
+    self, PendingWithdrawal, StoredPriceInfo, TradeInfo, TurnoverEntry, DEPOSITOR_ALLOWLIST,
+    DEPOSITOR_ALLOWLIST_ENABLED, DEPOSIT_CAPS, DEPOSIT_ID_COUNTER, DEPOSIT_REQUESTS, FEE_CONFIG,
+    FEE_STATE, GUARDIAN, MAX_SLIPPAGE_BPS, NAV_SNAPSHOTS, NAV_SNAPSHOT_COUNT, PAUSE_STATE,
+    PENDING_STRATEGY_CHANGE, PENDING_TRADES, PRICES, PRICE_GUARDS, PRICE_GUARD_ACTION, SHARE_DENOM,
+    SKIP_ENTRY_POINT, STRATEGY_TIMELOCK_SECONDS, SURPLUS_POLICY, SWAP_ALLOWLIST_ENABLED,
+    SWAP_POOLS, SWAP_VENUES, TARGET_ALLOCATION, TOTAL_PENDING_ASSETS, TOTAL_SHARES,
+    TRADE_ID_COUNTER, TRADING_LIMITS, TURNOVER_LOG, USER_PENDING_ASSETS, USER_SHARES, VAULT_ASSETS,
+    VAULT_VALUE_DEPOSITED, WHITELISTED_DENOMS, WITHDRAWAL_QUEUE_ENABLED, WITHDRAW_QUEUE,
+    WITHDRAW_REQUESTS, WITHDRAW_REQUEST_ID_COUNTER, WITHDRAW_TO_ID_COUNTER, WITHDRAW_TO_PENDING,
+};
+use crate::token_factory;
+use crate::{
+    DepositRequest, DepositState, Payload, WithdrawRequest, WithdrawState, REPLY_TRACKER_ID,
+    WITHDRAW_SWAP_REPLY_ID,
 };
-use crate::{DepositRequest, DepositState, Payload, REPLY_TRACKER_ID};
 
-pub fn deposit(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+pub const SECONDS_PER_YEAR: u64 = 31_536_000;
+pub const BPS_DENOMINATOR: u64 = 10_000;
+pub const TURNOVER_WINDOW_SECONDS: u64 = 86_400;
+// Oldest NAV snapshots are dropped once the series reaches this length
+pub const MAX_NAV_SNAPSHOTS: u64 = 2_000;
+// Swaps for single-asset withdrawals execute in the same block, the timeout is only a backstop
+pub const WITHDRAW_SWAP_TIMEOUT_SECONDS: u64 = 300;
+
+// Virtual shares and assets counted in every share conversion (ERC-4626 style offset).
+// A donation that inflates the share price is mostly captured by the virtual shares:
+// a later deposit loses at most one share's worth to rounding, which is a thousandth
+// of what the donor gave up, so the attack costs far more than it takes from anyone.
+// The pair worth $0.001 keeps the initial price at 1e6 shares per USD, so the smallest
+// deposit that mints anything is one share, $0.000001 at that price. Smaller deposits
+// stay pending until the depositor cancels them.
+pub const VIRTUAL_SHARES: u128 = 1_000;
+pub const VIRTUAL_ASSETS_USD: Decimal256 = Decimal256::new(Uint256::new(1_000_000_000_000_000));
+
+// Upper bounds for owner-configured fee rates
+const MAX_MANAGEMENT_FEE_RATE: Decimal256 = Decimal256::percent(10);
+const MAX_PERFORMANCE_FEE_RATE: Decimal256 = Decimal256::percent(50);
+
+pub fn deposit(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let pause_state = PAUSE_STATE.load(deps.storage)?;
+    if pause_state.emergency_mode {
+        return Err(ContractError::EmergencyMode {});
+    }
+    if pause_state.deposits_paused {
+        return Err(ContractError::DepositsPaused {});
+    }
+
+    if DEPOSITOR_ALLOWLIST_ENABLED.load(deps.storage)?
+        && !DEPOSITOR_ALLOWLIST.has(deps.storage, &info.sender)
+    {
+        return Err(ContractError::DepositorNotAllowed {
+            address: info.sender.to_string(),
+        });
+    }
+
     // Validate that funds are provided
     if info.funds.is_empty() {
         return Err(ContractError::NoFunds {});
@@ -34,12 +87,23 @@ pub fn deposit(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response,
             continue;
         }
 
-        // Check if token is whitelisted
-        WHITELISTED_DENOMS
+        // Check if token is whitelisted and open for deposits
+        let entry = WHITELISTED_DENOMS
             .load(deps.storage, coin.denom.clone())
             .map_err(|_| ContractError::TokenNotWhitelisted {
                 token: coin.denom.clone(),
             })?;
+        if !entry.depositable {
+            return Err(ContractError::DenomNotDepositable {
+                denom: coin.denom.clone(),
+            });
+        }
+        if coin.amount < entry.min_deposit {
+            return Err(ContractError::DepositBelowMinimum {
+                denom: coin.denom.clone(),
+                min_deposit: entry.min_deposit,
+            });
+        }
 
         valid_coins.push(coin.clone());
     }
@@ -48,6 +112,8 @@ pub fn deposit(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response,
         return Err(ContractError::NoFunds {});
     }
 
+    check_deposit_caps(deps.storage, &deps.querier, &info.sender, &valid_coins)?;
+
     // Generate auto-incrementing deposit_id
     let deposit_id =
         DEPOSIT_ID_COUNTER.update::<_, ContractError>(deps.storage, |id| Ok(id + 1))?;
@@ -57,6 +123,7 @@ pub fn deposit(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response,
         user: info.sender.clone(),
         coins: valid_coins.clone(),
         state: DepositState::Pending,
+        created_at: env.block.time,
     };
 
     DEPOSIT_REQUESTS.save(deps.storage, deposit_id, &deposit_request)?;
@@ -73,6 +140,13 @@ pub fn deposit(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response,
                 Ok(updated)
             },
         )?;
+        USER_PENDING_ASSETS.update(
+            deps.storage,
+            (&info.sender, &coin.denom),
+            |balance| -> Result<_, ContractError> {
+                Ok(balance.unwrap_or_default().checked_add(amount)?)
+            },
+        )?;
     }
 
     // Create a single deposit event with summary info
@@ -85,130 +159,1246 @@ pub fn deposit(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response,
         deposit_event = deposit_event.add_attribute(&coin.denom, coin.amount.to_string());
     }
 
-    Ok(Response::new().add_event(deposit_event))
+    Ok(Response::new().add_event(deposit_event))
+}
+
+// The depositor can cancel at any time; anyone can once the configured expiry has passed
+pub fn cancel_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    deposit_id: u64,
+) -> Result<Response, ContractError> {
+    let mut deposit_request = DEPOSIT_REQUESTS.load(deps.storage, deposit_id)?;
+
+    match deposit_request.state {
+        DepositState::Pending => {}
+        DepositState::Completed { .. } => {
+            return Err(ContractError::DepositAlreadyCompleted { deposit_id })
+        }
+        DepositState::Cancelled => {
+            return Err(ContractError::DepositAlreadyCancelled { deposit_id })
+        }
+    }
+
+    if info.sender != deposit_request.user {
+        let Some(expiry_seconds) = state::DEPOSIT_EXPIRY_SECONDS.may_load(deps.storage)? else {
+            return Err(ContractError::Unauthorized {});
+        };
+        let expires_at = deposit_request.created_at.plus_seconds(expiry_seconds);
+        if env.block.time < expires_at {
+            return Err(ContractError::DepositNotExpired {
+                deposit_id,
+                expires_at,
+            });
+        }
+    }
+
+    for coin in &deposit_request.coins {
+        let new_pending_balance = TOTAL_PENDING_ASSETS.update(
+            deps.storage,
+            coin.denom.clone(),
+            |balance| -> Result<_, ContractError> {
+                let current_balance = balance.unwrap_or_default();
+                let updated_balance = current_balance.checked_sub(coin.amount)?;
+                Ok(updated_balance)
+            },
+        )?;
+        if new_pending_balance.is_zero() {
+            TOTAL_PENDING_ASSETS.remove(deps.storage, coin.denom.clone());
+        }
+    }
+    release_user_pending(deps.storage, &deposit_request)?;
+
+    deposit_request.state = DepositState::Cancelled;
+    DEPOSIT_REQUESTS.save(deps.storage, deposit_id, &deposit_request)?;
+
+    let mut cancel_event = Event::new("deposit_cancelled")
+        .add_attribute("deposit_id", deposit_id.to_string())
+        .add_attribute("user", &deposit_request.user)
+        .add_attribute("cancelled_by", &info.sender);
+    for coin in &deposit_request.coins {
+        cancel_event =
+            cancel_event.add_attribute(format!("refund_{}", coin.denom), coin.amount.to_string());
+    }
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: deposit_request.user.to_string(),
+            amount: deposit_request.coins,
+        })
+        .add_event(cancel_event))
+}
+
+// Rejects a deposit that would break one of the owner's caps. Pending amounts count
+// towards every cap; denoms that have never been priced add no USD value until
+// settlement checks the caps again.
+fn check_deposit_caps(
+    storage: &dyn cosmwasm_std::Storage,
+    querier: &QuerierWrapper,
+    user: &Addr,
+    coins: &[Coin],
+) -> Result<(), ContractError> {
+    let caps = DEPOSIT_CAPS.load(storage)?;
+
+    for cap in &caps.denom_caps {
+        let Some(coin) = coins.iter().find(|coin| coin.denom == cap.denom) else {
+            continue;
+        };
+        let total = VAULT_ASSETS
+            .may_load(storage, cap.denom.clone())?
+            .unwrap_or_default()
+            .checked_add(
+                TOTAL_PENDING_ASSETS
+                    .may_load(storage, cap.denom.clone())?
+                    .unwrap_or_default(),
+            )?
+            .checked_add(coin.amount)?;
+        if total > cap.amount {
+            return Err(ContractError::DenomCapExceeded {
+                denom: cap.denom.clone(),
+                cap: cap.amount,
+            });
+        }
+    }
+
+    if caps.max_tvl_usd.is_none() && caps.max_position_usd.is_none() {
+        return Ok(());
+    }
+    let deposit_value = priced_value(storage, coins)?;
+
+    if let Some(max_tvl_usd) = caps.max_tvl_usd {
+        let pending = TOTAL_PENDING_ASSETS
+            .range(storage, None, None, cosmwasm_std::Order::Ascending)
+            .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+            .collect::<StdResult<Vec<_>>>()?;
+        let tvl = VAULT_VALUE_DEPOSITED
+            .load(storage)?
+            .checked_add(priced_value(storage, &pending)?)?
+            .checked_add(deposit_value)?;
+        if tvl > max_tvl_usd {
+            return Err(ContractError::TvlCapExceeded { max_tvl_usd });
+        }
+    }
+
+    if let Some(max_position_usd) = caps.max_position_usd {
+        let pending = USER_PENDING_ASSETS
+            .prefix(user)
+            .range(storage, None, None, cosmwasm_std::Order::Ascending)
+            .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+            .collect::<StdResult<Vec<_>>>()?;
+        let position = held_position_value(storage, querier, user)?
+            .checked_add(priced_value(storage, &pending)?)?
+            .checked_add(deposit_value)?;
+        if position > max_position_usd {
+            return Err(ContractError::PositionCapExceeded { max_position_usd });
+        }
+    }
+
+    Ok(())
+}
+
+// Value of `coins` at the stored prices, counting denoms without a price as zero
+fn priced_value(
+    storage: &dyn cosmwasm_std::Storage,
+    coins: &[Coin],
+) -> Result<Decimal256, ContractError> {
+    coins.iter().try_fold(Decimal256::zero(), |acc, coin| {
+        if !PRICES.has(storage, coin.denom.clone()) {
+            return Ok(acc);
+        }
+        Ok(acc.checked_add(coin_value(storage, coin)?)?)
+    })
+}
+
+// Value of the shares `user` holds at the last settled vault value
+fn held_position_value(
+    storage: &dyn cosmwasm_std::Storage,
+    querier: &QuerierWrapper,
+    user: &Addr,
+) -> Result<Decimal256, ContractError> {
+    let shares = match SHARE_DENOM.may_load(storage)? {
+        Some(share_denom) => querier.query_balance(user, share_denom)?.amount,
+        None => USER_SHARES
+            .may_load(storage, user.to_string())?
+            .unwrap_or_default(),
+    };
+    if shares.is_zero() {
+        return Ok(Decimal256::zero());
+    }
+    value_for_shares(
+        shares,
+        VAULT_VALUE_DEPOSITED.load(storage)?,
+        TOTAL_SHARES.load(storage)?,
+    )
+}
+
+// Drop a deposit's coins from its depositor's pending totals
+fn release_user_pending(
+    storage: &mut dyn cosmwasm_std::Storage,
+    deposit_request: &DepositRequest,
+) -> Result<(), ContractError> {
+    for coin in &deposit_request.coins {
+        let key = (&deposit_request.user, coin.denom.as_str());
+        let remaining = USER_PENDING_ASSETS
+            .may_load(storage, key)?
+            .unwrap_or_default()
+            .saturating_sub(coin.amount);
+        if remaining.is_zero() {
+            USER_PENDING_ASSETS.remove(storage, key);
+        } else {
+            USER_PENDING_ASSETS.save(storage, key, &remaining)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn update_deposit_caps(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    caps: DepositCaps,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    for (i, cap) in caps.denom_caps.iter().enumerate() {
+        if caps.denom_caps[..i]
+            .iter()
+            .any(|other| other.denom == cap.denom)
+        {
+            return Err(ContractError::DuplicateDenomCap {
+                denom: cap.denom.clone(),
+            });
+        }
+    }
+    DEPOSIT_CAPS.save(deps.storage, &caps)?;
+
+    let display = |value: Option<Decimal256>| {
+        value.map_or_else(|| "none".to_string(), |value| value.to_string())
+    };
+    let mut event = Event::new("deposit_caps_updated")
+        .add_attribute("max_tvl_usd", display(caps.max_tvl_usd))
+        .add_attribute("max_position_usd", display(caps.max_position_usd));
+    for cap in &caps.denom_caps {
+        event = event.add_attribute(format!("cap_{}", cap.denom), cap.amount.to_string());
+    }
+
+    Ok(Response::new()
+        .add_event(event)
+        .add_attribute("method", "update_deposit_caps"))
+}
+
+// Every whitelisted or held denom whose bank balance differs from the deposits
+// waiting to be priced plus the tracked vault assets
+pub fn asset_discrepancies(
+    storage: &dyn cosmwasm_std::Storage,
+    querier: &QuerierWrapper,
+    contract: &Addr,
+) -> StdResult<Vec<AssetDiscrepancy>> {
+    let mut denoms = WHITELISTED_DENOMS
+        .keys(storage, None, None, cosmwasm_std::Order::Ascending)
+        .chain(VAULT_ASSETS.keys(storage, None, None, cosmwasm_std::Order::Ascending))
+        .collect::<StdResult<Vec<_>>>()?;
+    denoms.sort();
+    denoms.dedup();
+
+    let mut discrepancies = Vec::new();
+    for denom in denoms {
+        let bank_balance = querier.query_balance(contract, &denom)?.amount;
+        let pending = TOTAL_PENDING_ASSETS
+            .may_load(storage, denom.clone())?
+            .unwrap_or_default();
+        let tracked = VAULT_ASSETS
+            .may_load(storage, denom.clone())?
+            .unwrap_or_default();
+        let expected = pending.checked_add(tracked)?;
+        if bank_balance == expected {
+            continue;
+        }
+        discrepancies.push(AssetDiscrepancy {
+            denom,
+            bank_balance,
+            pending,
+            tracked,
+            surplus: bank_balance.saturating_sub(expected),
+            deficit: expected.saturating_sub(bank_balance),
+        });
+    }
+    Ok(discrepancies)
+}
+
+pub fn reconcile(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    let discrepancies = asset_discrepancies(deps.storage, &deps.querier, &env.contract.address)?;
+    let policy = SURPLUS_POLICY.load(deps.storage)?;
+
+    let mut event = Event::new("reconciled");
+    let mut sweep = Vec::new();
+    for discrepancy in discrepancies {
+        // Pending deposits are refunded in full on cancel, so a shortfall is written
+        // off the tracked vault assets. One the vault assets can't absorb leaves
+        // pending deposits unbacked, which needs the owner's attention instead.
+        let backing = discrepancy
+            .bank_balance
+            .checked_sub(discrepancy.pending)
+            .map_err(|_| ContractError::PendingDepositsUnbacked {
+                denom: discrepancy.denom.clone(),
+                bank_balance: discrepancy.bank_balance,
+                pending: discrepancy.pending,
+            })?;
+        let mut tracked = discrepancy.tracked.min(backing);
+        if !discrepancy.deficit.is_zero() {
+            event = event.add_attribute(
+                format!("deficit_{}", discrepancy.denom),
+                discrepancy.deficit.to_string(),
+            );
+        }
+
+        if !discrepancy.surplus.is_zero() {
+            event = event.add_attribute(
+                format!("surplus_{}", discrepancy.denom),
+                discrepancy.surplus.to_string(),
+            );
+            match policy {
+                SurplusPolicy::FoldIntoVault => {
+                    tracked = tracked.checked_add(discrepancy.surplus)?;
+                }
+                SurplusPolicy::SweepToTreasury { .. } => sweep.push(Coin {
+                    denom: discrepancy.denom.clone(),
+                    amount: discrepancy.surplus,
+                }),
+            }
+        }
+
+        if tracked.is_zero() {
+            VAULT_ASSETS.remove(deps.storage, discrepancy.denom);
+        } else {
+            VAULT_ASSETS.save(deps.storage, discrepancy.denom, &tracked)?;
+        }
+    }
+
+    // Re-value the corrected holdings at the stored prices
+    let vault_value = calculate_vault_usd_value(deps.storage)?;
+    VAULT_VALUE_DEPOSITED.save(deps.storage, &vault_value)?;
+
+    let mut response = Response::new();
+    if let SurplusPolicy::SweepToTreasury { treasury } = policy {
+        if !sweep.is_empty() {
+            event = event.add_attribute("swept_to", &treasury);
+            response = response.add_message(BankMsg::Send {
+                to_address: treasury.to_string(),
+                amount: sweep,
+            });
+        }
+    }
+
+    Ok(response
+        .add_event(event.add_attribute("vault_value_usd", vault_value.to_string()))
+        .add_attribute("method", "reconcile"))
+}
+
+pub fn update_surplus_policy(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    policy: SurplusPolicy,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    let response = Response::new().add_attribute("method", "update_surplus_policy");
+    let (policy, response) = match policy {
+        SurplusPolicy::FoldIntoVault => (
+            SurplusPolicy::FoldIntoVault,
+            response.add_attribute("policy", "fold_into_vault"),
+        ),
+        SurplusPolicy::SweepToTreasury { treasury } => {
+            let treasury = deps.api.addr_validate(treasury.as_str())?;
+            (
+                SurplusPolicy::SweepToTreasury {
+                    treasury: treasury.clone(),
+                },
+                response
+                    .add_attribute("policy", "sweep_to_treasury")
+                    .add_attribute("treasury", treasury),
+            )
+        }
+    };
+    SURPLUS_POLICY.save(deps.storage, &policy)?;
+
+    Ok(response)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_swap_allowlist(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    enabled: Option<bool>,
+    venues_to_add: Option<Vec<String>>,
+    venues_to_remove: Option<Vec<String>>,
+    pools_to_add: Option<Vec<AllowedPool>>,
+    pools_to_remove: Option<Vec<AllowedPool>>,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    if let Some(enabled) = enabled {
+        SWAP_ALLOWLIST_ENABLED.save(deps.storage, &enabled)?;
+    }
+
+    for venue in venues_to_add.unwrap_or_default() {
+        SWAP_VENUES.save(deps.storage, &venue, &())?;
+    }
+    for AllowedPool { venue, pool } in pools_to_add.unwrap_or_default() {
+        if !SWAP_VENUES.has(deps.storage, &venue) {
+            return Err(ContractError::SwapVenueNotAllowed { venue });
+        }
+        SWAP_POOLS.save(deps.storage, (&venue, &pool), &())?;
+    }
+    for AllowedPool { venue, pool } in pools_to_remove.unwrap_or_default() {
+        SWAP_POOLS.remove(deps.storage, (&venue, &pool));
+    }
+    for venue in venues_to_remove.unwrap_or_default() {
+        let pools = SWAP_POOLS
+            .prefix(&venue)
+            .keys(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for pool in pools {
+            SWAP_POOLS.remove(deps.storage, (&venue, &pool));
+        }
+        SWAP_VENUES.remove(deps.storage, &venue);
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "update_swap_allowlist")
+        .add_attribute(
+            "enabled",
+            SWAP_ALLOWLIST_ENABLED.load(deps.storage)?.to_string(),
+        ))
+}
+
+/// Rejects routes through venues or pools the owner has not allowlisted, once the
+/// allowlist is enabled.
+fn check_swap_allowlist(
+    storage: &dyn cosmwasm_std::Storage,
+    venue: &str,
+    operations: &[SwapOperation],
+) -> Result<(), ContractError> {
+    if !SWAP_ALLOWLIST_ENABLED.load(storage)? {
+        return Ok(());
+    }
+    if !SWAP_VENUES.has(storage, venue) {
+        return Err(ContractError::SwapVenueNotAllowed {
+            venue: venue.to_string(),
+        });
+    }
+    for operation in operations {
+        if !SWAP_POOLS.has(storage, (venue, &operation.pool)) {
+            return Err(ContractError::SwapPoolNotAllowed {
+                venue: venue.to_string(),
+                pool: operation.pool.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+pub fn validate_target_allocation(
+    storage: &dyn cosmwasm_std::Storage,
+    allocation: &TargetAllocation,
+) -> Result<(), ContractError> {
+    let weights = match allocation {
+        TargetAllocation::Ai { weight_bounds } => {
+            return validate_weight_bounds(storage, weight_bounds)
+        }
+        TargetAllocation::Fixed { weights } => weights,
+    };
+
+    let mut denoms = std::collections::BTreeSet::new();
+    let mut total = Decimal256::zero();
+    for weight in weights {
+        if !denoms.insert(weight.denom.as_str()) {
+            return Err(ContractError::DuplicateAllocationDenom {
+                denom: weight.denom.clone(),
+            });
+        }
+        let entry = WHITELISTED_DENOMS
+            .load(storage, weight.denom.clone())
+            .map_err(|_| ContractError::TokenNotWhitelisted {
+                token: weight.denom.clone(),
+            })?;
+        // The operators could never trade into it
+        if !entry.tradable && !weight.weight.is_zero() {
+            return Err(ContractError::DenomNotTradable {
+                denom: weight.denom.clone(),
+            });
+        }
+        total = total.checked_add(weight.weight)?;
+    }
+
+    if total != Decimal256::one() {
+        return Err(ContractError::AllocationNotNormalized { total });
+    }
+    Ok(())
+}
+
+fn validate_weight_bounds(
+    storage: &dyn cosmwasm_std::Storage,
+    weight_bounds: &[WeightBound],
+) -> Result<(), ContractError> {
+    let mut denoms = std::collections::BTreeSet::new();
+    let mut total_min = Decimal256::zero();
+    for bound in weight_bounds {
+        if !denoms.insert(bound.denom.as_str()) {
+            return Err(ContractError::DuplicateAllocationDenom {
+                denom: bound.denom.clone(),
+            });
+        }
+        let entry = WHITELISTED_DENOMS
+            .load(storage, bound.denom.clone())
+            .map_err(|_| ContractError::TokenNotWhitelisted {
+                token: bound.denom.clone(),
+            })?;
+        if bound.min_weight > bound.max_weight || bound.max_weight > Decimal256::one() {
+            return Err(ContractError::InvalidWeightBound {
+                denom: bound.denom.clone(),
+            });
+        }
+        if !entry.tradable && !bound.min_weight.is_zero() {
+            return Err(ContractError::DenomNotTradable {
+                denom: bound.denom.clone(),
+            });
+        }
+        total_min = total_min.checked_add(bound.min_weight)?;
+    }
+
+    // Otherwise no allocation could satisfy every minimum
+    if total_min > Decimal256::one() {
+        return Err(ContractError::WeightBoundsInfeasible { total_min });
+    }
+    Ok(())
+}
+
+pub fn propose_strategy_change(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    target_allocation: Option<TargetAllocation>,
+    timelock_seconds: Option<u64>,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    if target_allocation.is_none() && timelock_seconds.is_none() {
+        return Err(ContractError::EmptyStrategyChange {});
+    }
+    if let Some(allocation) = &target_allocation {
+        validate_target_allocation(deps.storage, allocation)?;
+    }
+
+    let timelock = STRATEGY_TIMELOCK_SECONDS.load(deps.storage)?;
+    let change = StrategyChange {
+        target_allocation,
+        timelock_seconds,
+        proposed_at: env.block.time,
+        executable_at: env.block.time.plus_seconds(timelock),
+    };
+    let replaced = PENDING_STRATEGY_CHANGE.exists(deps.storage);
+    PENDING_STRATEGY_CHANGE.save(deps.storage, &change)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "propose_strategy_change")
+        .add_attribute("replaced_pending", replaced.to_string())
+        .add_event(strategy_change_event("strategy_change_proposed", &change)?))
+}
+
+pub fn execute_strategy_change(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    let change = PENDING_STRATEGY_CHANGE
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoPendingStrategyChange {})?;
+    if env.block.time < change.executable_at {
+        return Err(ContractError::StrategyTimelockActive {
+            executable_at: change.executable_at,
+        });
+    }
+
+    if let Some(allocation) = &change.target_allocation {
+        // The whitelist may have changed while the proposal waited
+        validate_target_allocation(deps.storage, allocation)?;
+        TARGET_ALLOCATION.save(deps.storage, allocation)?;
+    }
+    if let Some(timelock_seconds) = change.timelock_seconds {
+        STRATEGY_TIMELOCK_SECONDS.save(deps.storage, &timelock_seconds)?;
+    }
+    PENDING_STRATEGY_CHANGE.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("method", "execute_strategy_change")
+        .add_event(strategy_change_event("strategy_change_executed", &change)?))
+}
+
+pub fn cancel_strategy_change(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    let change = PENDING_STRATEGY_CHANGE
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoPendingStrategyChange {})?;
+    PENDING_STRATEGY_CHANGE.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("method", "cancel_strategy_change")
+        .add_event(strategy_change_event("strategy_change_cancelled", &change)?))
+}
+
+fn strategy_change_event(ty: &str, change: &StrategyChange) -> StdResult<Event> {
+    let mut event = Event::new(ty)
+        .add_attribute("proposed_at", change.proposed_at.seconds().to_string())
+        .add_attribute("executable_at", change.executable_at.seconds().to_string());
+    if let Some(allocation) = &change.target_allocation {
+        event = event.add_attribute("target_allocation", to_json_string(allocation)?);
+    }
+    if let Some(timelock_seconds) = change.timelock_seconds {
+        event = event.add_attribute("timelock_seconds", timelock_seconds.to_string());
+    }
+    Ok(event)
+}
+
+pub fn update_depositor_allowlist(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    enabled: Option<bool>,
+    to_add: Option<Vec<String>>,
+    to_remove: Option<Vec<String>>,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    if let Some(enabled) = enabled {
+        DEPOSITOR_ALLOWLIST_ENABLED.save(deps.storage, &enabled)?;
+    }
+
+    let mut added_count: u32 = 0;
+    for address in to_add.unwrap_or_default() {
+        let address = deps.api.addr_validate(&address)?;
+        if !DEPOSITOR_ALLOWLIST.has(deps.storage, &address) {
+            DEPOSITOR_ALLOWLIST.save(deps.storage, &address, &())?;
+            added_count += 1;
+        }
+    }
+
+    let mut removed_count: u32 = 0;
+    for address in to_remove.unwrap_or_default() {
+        let address = deps.api.addr_validate(&address)?;
+        if DEPOSITOR_ALLOWLIST.has(deps.storage, &address) {
+            DEPOSITOR_ALLOWLIST.remove(deps.storage, &address);
+            removed_count += 1;
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "update_depositor_allowlist")
+        .add_attribute(
+            "enabled",
+            DEPOSITOR_ALLOWLIST_ENABLED.load(deps.storage)?.to_string(),
+        )
+        .add_attribute("addresses_added", added_count.to_string())
+        .add_attribute("addresses_removed", removed_count.to_string()))
+}
+
+pub fn update_deposit_expiry(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    expiry_seconds: Option<u64>,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    match expiry_seconds {
+        Some(0) => return Err(ContractError::ZeroDepositExpiry {}),
+        Some(seconds) => state::DEPOSIT_EXPIRY_SECONDS.save(deps.storage, &seconds)?,
+        None => state::DEPOSIT_EXPIRY_SECONDS.remove(deps.storage),
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "update_deposit_expiry")
+        .add_attribute(
+            "expiry_seconds",
+            expiry_seconds.map_or("none".to_string(), |seconds| seconds.to_string()),
+        ))
+}
+
+pub fn withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    shares: Uint256,
+) -> Result<Response, ContractError> {
+    let redemption = redeem_shares(deps.storage, &env, &info, shares)?;
+
+    let transfer_msgs = redemption.slices.into_iter().map(|slice| {
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![slice],
+        })
+    });
+
+    Ok(Response::new()
+        .add_messages(redemption.share_burn)
+        .add_messages(transfer_msgs)
+        .add_attribute("method", "withdraw")
+        .add_attribute("user", info.sender)
+        .add_attribute("shares", shares.to_string())
+        .add_attribute("value_usd", redemption.value_usd.to_string())
+        .add_attribute("new_vault_value_usd", redemption.vault_value.to_string()))
+}
+
+struct Redemption {
+    slices: Vec<Coin>, // Pro-rata share of every vault asset, already removed from VAULT_ASSETS
+    share_burn: Option<CosmosMsg>,
+    value_usd: Decimal256,
+    vault_value: Decimal256, // Vault value after the redemption
+}
+
+// Debit `shares` from the sender and carve their pro-rata slice out of the vault
+fn redeem_shares(
+    storage: &mut dyn cosmwasm_std::Storage,
+    env: &Env,
+    info: &MessageInfo,
+    shares: Uint256,
+) -> Result<Redemption, ContractError> {
+    // With the queue enabled, instant exits are only an emergency escape hatch
+    if WITHDRAWAL_QUEUE_ENABLED
+        .may_load(storage)?
+        .unwrap_or_default()
+        && !PAUSE_STATE.load(storage)?.emergency_mode
+    {
+        return Err(ContractError::WithdrawalsQueued {});
+    }
+
+    take_shares(storage, info, shares)?;
+    let share_burn = share_burn_msg(storage, env, shares)?;
+
+    let total_shares = TOTAL_SHARES.load(storage)?;
+    let vault_value = VAULT_VALUE_DEPOSITED.load(storage)?;
+    let value_usd = value_for_shares(shares, vault_value, total_shares)?;
+
+    let slices = carve_slices(storage, shares)?;
+
+    // Recalculate vault value from actual post-withdrawal asset balances
+    // This ensures the stored USD value matches the real asset value after truncation
+    let updated_vault_value = calculate_vault_usd_value(storage)?;
+    VAULT_VALUE_DEPOSITED.save(storage, &updated_vault_value)?;
+
+    Ok(Redemption {
+        slices,
+        share_burn,
+        value_usd,
+        vault_value: updated_vault_value,
+    })
+}
+
+// Take `shares` from the sender: tokenized shares must be attached as funds and are
+// then held by the vault, internal shares are debited from USER_SHARES
+fn take_shares(
+    storage: &mut dyn cosmwasm_std::Storage,
+    info: &MessageInfo,
+    shares: Uint256,
+) -> Result<(), ContractError> {
+    if shares.is_zero() {
+        return Err(ContractError::ZeroWithdrawal {});
+    }
+
+    if let Some(share_denom) = SHARE_DENOM.may_load(storage)? {
+        let sent = cw_utils::must_pay(info, &share_denom)?;
+        if sent != shares {
+            return Err(ContractError::ShareFundsMismatch { shares, sent });
+        }
+        return Ok(());
+    }
+
+    let user_shares = USER_SHARES
+        .may_load(storage, info.sender.to_string())?
+        .unwrap_or_default();
+    if user_shares < shares {
+        return Err(ContractError::InsufficientShares {});
+    }
+
+    // Remove user from shares map if they have no shares left
+    let updated_user_shares = user_shares.checked_sub(shares)?;
+    if updated_user_shares.is_zero() {
+        USER_SHARES.remove(storage, info.sender.to_string());
+    } else {
+        USER_SHARES.save(storage, info.sender.to_string(), &updated_user_shares)?;
+    }
+    Ok(())
+}
+
+fn share_burn_msg(
+    storage: &dyn cosmwasm_std::Storage,
+    env: &Env,
+    shares: Uint256,
+) -> Result<Option<CosmosMsg>, ContractError> {
+    let contract = env.contract.address.as_str();
+    Ok(SHARE_DENOM.may_load(storage)?.map(|share_denom| {
+        token_factory::burn_msg(contract, &Coin::new(shares, share_denom), contract)
+    }))
+}
+
+// Remove `shares` from the supply and their pro-rata slice from VAULT_ASSETS
+fn carve_slices(
+    storage: &mut dyn cosmwasm_std::Storage,
+    shares: Uint256,
+) -> Result<Vec<Coin>, ContractError> {
+    let total_shares = TOTAL_SHARES.load(storage)?;
+    if total_shares < shares {
+        return Err(ContractError::InsufficientShares {});
+    }
+
+    // Slices round down and the virtual shares keep their part, which is equivalent
+    // to rounding the shares burned up
+    let virtual_total_shares = total_shares.checked_add(VIRTUAL_SHARES.into())?;
+    let slices = VAULT_ASSETS
+        .range(storage, None, None, cosmwasm_std::Order::Ascending)
+        .map(|item| {
+            let (denom, balance) = item?;
+            let proportion = shares.multiply_ratio(balance, virtual_total_shares);
+            Ok::<Coin, ContractError>(Coin {
+                denom,
+                amount: proportion,
+            })
+        })
+        .collect::<Result<Vec<_>, ContractError>>()?;
+
+    TOTAL_SHARES.save(storage, &total_shares.checked_sub(shares)?)?;
+
+    for slice in &slices {
+        VAULT_ASSETS.update(
+            storage,
+            slice.denom.clone(),
+            |current_balance| -> Result<_, ContractError> {
+                let new_balance = current_balance
+                    .unwrap_or_default()
+                    .checked_sub(slice.amount)?;
+                Ok(new_balance)
+            },
+        )?;
+    }
+
+    Ok(slices)
+}
+
+pub fn request_withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    shares: Uint256,
+) -> Result<Response, ContractError> {
+    if !WITHDRAWAL_QUEUE_ENABLED
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+    {
+        return Err(ContractError::WithdrawalQueueDisabled {});
+    }
+    // Queued withdrawals settle at price updates, which emergency mode refuses
+    if PAUSE_STATE.load(deps.storage)?.emergency_mode {
+        return Err(ContractError::EmergencyMode {});
+    }
+
+    take_shares(deps.storage, &info, shares)?;
+
+    let withdraw_id =
+        WITHDRAW_REQUEST_ID_COUNTER.update::<_, ContractError>(deps.storage, |id| Ok(id + 1))?;
+    WITHDRAW_REQUESTS.save(
+        deps.storage,
+        withdraw_id,
+        &WithdrawRequest {
+            id: withdraw_id,
+            user: info.sender.clone(),
+            shares,
+            state: WithdrawState::Pending,
+            created_at: env.block.time,
+        },
+    )?;
+    WITHDRAW_QUEUE.save(deps.storage, withdraw_id, &())?;
+
+    Ok(Response::new().add_event(
+        Event::new("withdrawal_requested")
+            .add_attribute("withdraw_id", withdraw_id.to_string())
+            .add_attribute("user", info.sender)
+            .add_attribute("shares", shares.to_string()),
+    ))
+}
+
+// Only the requester can cancel. Nothing is paused for this, so shares queued before
+// emergency mode can be taken back out through the emergency withdrawal.
+pub fn cancel_withdraw(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    withdraw_id: u64,
+) -> Result<Response, ContractError> {
+    let mut request = WITHDRAW_REQUESTS.load(deps.storage, withdraw_id)?;
+
+    match request.state {
+        WithdrawState::Pending => {}
+        WithdrawState::Completed { .. } => {
+            return Err(ContractError::WithdrawAlreadyCompleted { withdraw_id })
+        }
+        WithdrawState::Cancelled => {
+            return Err(ContractError::WithdrawAlreadyCancelled { withdraw_id })
+        }
+    }
+
+    if info.sender != request.user {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    WITHDRAW_QUEUE.remove(deps.storage, withdraw_id);
+    request.state = WithdrawState::Cancelled;
+    WITHDRAW_REQUESTS.save(deps.storage, withdraw_id, &request)?;
+
+    // Tokenized shares were held by the vault, internal ones go back to USER_SHARES
+    let mut response = Response::new();
+    if let Some(share_denom) = SHARE_DENOM.may_load(deps.storage)? {
+        response = response.add_message(BankMsg::Send {
+            to_address: request.user.to_string(),
+            amount: vec![Coin::new(request.shares, share_denom)],
+        });
+    } else {
+        USER_SHARES.update(
+            deps.storage,
+            request.user.to_string(),
+            |shares| -> Result<_, ContractError> {
+                Ok(shares.unwrap_or_default().checked_add(request.shares)?)
+            },
+        )?;
+    }
+
+    Ok(response.add_event(
+        Event::new("withdrawal_cancelled")
+            .add_attribute("withdraw_id", withdraw_id.to_string())
+            .add_attribute("user", &request.user)
+            .add_attribute("shares", request.shares.to_string()),
+    ))
+}
+
+// Pay out every queued withdrawal in-kind at `vault_value`, which must reflect the
+// prices just stored. Returns the transfers and the vault value left afterwards.
+fn settle_queued_withdrawals(
+    storage: &mut dyn cosmwasm_std::Storage,
+    env: &Env,
+    mut vault_value: Decimal256,
+    events: &mut Vec<Event>,
+) -> Result<(Vec<CosmosMsg>, Decimal256), ContractError> {
+    let queued = WITHDRAW_QUEUE
+        .keys(storage, None, None, cosmwasm_std::Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    if queued.is_empty() {
+        return Ok((Vec::new(), vault_value));
+    }
+
+    let mut msgs = Vec::new();
+    for withdraw_id in queued {
+        WITHDRAW_QUEUE.remove(storage, withdraw_id);
+        let mut request = WITHDRAW_REQUESTS.load(storage, withdraw_id)?;
+
+        // Each settlement shrinks both the supply and the vault
+        let total_shares = TOTAL_SHARES.load(storage)?;
+        let value_usd = value_for_shares(request.shares, vault_value, total_shares)?;
+        let slices = carve_slices(storage, request.shares)?;
+        vault_value = calculate_vault_usd_value(storage)?;
+        let assets: Vec<Coin> = slices
+            .into_iter()
+            .filter(|slice| !slice.amount.is_zero())
+            .collect();
+
+        msgs.extend(share_burn_msg(storage, env, request.shares)?);
+        if !assets.is_empty() {
+            msgs.push(CosmosMsg::Bank(BankMsg::Send {
+                to_address: request.user.to_string(),
+                amount: assets.clone(),
+            }));
+        }
+
+        events.push(
+            Event::new("withdrawal_settled")
+                .add_attribute("withdraw_id", withdraw_id.to_string())
+                .add_attribute("user", &request.user)
+                .add_attribute("shares", request.shares.to_string())
+                .add_attribute("value_usd", value_usd.to_string()),
+        );
+
+        request.state = WithdrawState::Completed { value_usd, assets };
+        WITHDRAW_REQUESTS.save(storage, withdraw_id, &request)?;
+    }
+
+    Ok((msgs, vault_value))
 }
 
-pub fn withdraw(
+pub fn update_withdrawal_queue(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    WITHDRAWAL_QUEUE_ENABLED.save(deps.storage, &enabled)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "update_withdrawal_queue")
+        .add_attribute("enabled", enabled.to_string()))
+}
+
+// Redeem shares into `denom`: the slice already in `denom` is paid out as is and every
+// other slice is swapped through Skip back to the vault. Once all swaps reply, the user
+// receives the combined output, or the output plus in-kind refunds if any swap failed.
+pub fn withdraw_to(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
     shares: Uint256,
+    denom: String,
+    min_out: Uint256,
+    routes: Vec<WithdrawRoute>,
 ) -> Result<Response, ContractError> {
-    if shares.is_zero() {
-        return Err(ContractError::ZeroWithdrawal {});
+    let pause_state = PAUSE_STATE.load(deps.storage)?;
+    if pause_state.emergency_mode {
+        return Err(ContractError::EmergencyMode {});
+    }
+    if pause_state.trading_paused {
+        return Err(ContractError::TradingPaused {});
+    }
+    if !WHITELISTED_DENOMS.has(deps.storage, denom.clone()) {
+        return Err(ContractError::TokenNotWhitelisted { token: denom });
     }
 
-    let mut total_shares = TOTAL_SHARES.load(deps.storage)?;
-    let vault_value = VAULT_VALUE_DEPOSITED.load(deps.storage)?;
-
-    // Check if user has sufficient shares
-    let user_shares = USER_SHARES
-        .may_load(deps.storage, info.sender.to_string())?
-        .unwrap_or_default();
-    if user_shares < shares {
-        return Err(ContractError::InsufficientShares {});
+    let redemption = redeem_shares(deps.storage, &env, &info, shares)?;
+    let storage = deps.storage;
+
+    let mut amount_out = Uint256::zero();
+    let mut swaps = Vec::new();
+    for slice in redemption.slices {
+        if slice.denom == denom {
+            amount_out = slice.amount;
+        } else if !slice.amount.is_zero() {
+            let route = routes
+                .iter()
+                .find(|route| route.offer_denom == slice.denom)
+                .filter(|route| {
+                    route
+                        .operations
+                        .last()
+                        .is_some_and(|operation| operation.denom_out == denom)
+                })
+                .ok_or_else(|| ContractError::MissingWithdrawRoute {
+                    denom: slice.denom.clone(),
+                    target: denom.clone(),
+                })?;
+            check_swap_allowlist(storage, &route.swap_venue_name, &route.operations)?;
+            swaps.push((slice, route));
+        }
     }
 
-    if total_shares < shares {
-        return Err(ContractError::InsufficientShares {});
+    let mut response = Response::new()
+        .add_messages(redemption.share_burn)
+        .add_attribute("method", "withdraw_to")
+        .add_attribute("user", &info.sender)
+        .add_attribute("shares", shares.to_string())
+        .add_attribute("denom", &denom)
+        .add_attribute("value_usd", redemption.value_usd.to_string())
+        .add_attribute("new_vault_value_usd", redemption.vault_value.to_string());
+
+    if swaps.is_empty() {
+        if amount_out < min_out {
+            return Err(ContractError::WithdrawOutputTooLow {
+                amount_out,
+                min_out,
+            });
+        }
+        return Ok(response.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![Coin::new(amount_out, denom)],
+        }));
     }
 
-    let user_value_usd = Decimal256::from_ratio(shares, total_shares).checked_mul(vault_value)?;
+    // Each swap must cover its value-weighted part of whatever the direct slice leaves
+    // to reach `min_out`, so the combined output meets it whenever every swap succeeds
+    let shortfall = min_out.saturating_sub(amount_out);
+    let swap_values = swaps
+        .iter()
+        .map(|(slice, _)| coin_value(storage, slice))
+        .collect::<Result<Vec<_>, _>>()?;
+    let total_swap_value = swap_values
+        .iter()
+        .try_fold(Decimal256::zero(), |acc, value| acc.checked_add(*value))?;
+
+    let withdrawal_id =
+        WITHDRAW_TO_ID_COUNTER.update::<_, ContractError>(storage, |id| Ok(id + 1))?;
+    WITHDRAW_TO_PENDING.save(
+        storage,
+        withdrawal_id,
+        &PendingWithdrawal {
+            user: info.sender.clone(),
+            denom: denom.clone(),
+            min_out,
+            amount_out,
+            refunds: vec![],
+            outstanding_swaps: swaps.len() as u32,
+        },
+    )?;
+
+    let entry_point = SKIP_ENTRY_POINT.load(storage)?;
+    let timeout = env.block.time.plus_seconds(WITHDRAW_SWAP_TIMEOUT_SECONDS);
+    for ((slice, route), value) in swaps.into_iter().zip(swap_values) {
+        let swap_min_out = if shortfall.is_zero() || total_swap_value.is_zero() {
+            Uint256::zero()
+        } else {
+            Decimal256::from_atomics(shortfall, 0)?
+                .checked_mul(value)?
+                .checked_div(total_swap_value)?
+                .to_uint_ceil()
+        };
 
-    // Store the old total shares for calculation (before subtraction)
-    let old_total_shares = total_shares;
+        let swap_msg = SkipExecuteMsg::SwapAndAction {
+            sent_asset: None,
+            user_swap: SkipSwap::SwapExactAssetIn(SwapExactAssetIn {
+                swap_venue_name: route.swap_venue_name.clone(),
+                operations: route.operations.clone(),
+            }),
+            min_asset: SkipAsset::Native(Coin::new(swap_min_out, denom.clone())),
+            timeout_timestamp: timeout.nanos(),
+            post_swap_action: SkipAction::Transfer {
+                to_address: env.contract.address.to_string(),
+            },
+            affiliates: vec![],
+        };
 
-    // First, collect all vault assets and calculate proportions
-    let assets_to_withdraw = VAULT_ASSETS
-        .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
-        .map(|item| {
-            let (denom, balance) = item?;
-            let proportion = shares.multiply_ratio(balance, old_total_shares);
-            Ok::<(String, Uint256, Uint256), ContractError>((denom, balance, proportion))
-        })
-        .collect::<Result<Vec<_>, ContractError>>()?;
+        response = response.add_submessage(
+            SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: entry_point.to_string(),
+                    msg: to_json_binary(&swap_msg)?,
+                    funds: vec![slice.clone()],
+                },
+                WITHDRAW_SWAP_REPLY_ID,
+            )
+            .with_payload(to_json_binary(&(withdrawal_id, slice))?),
+        );
+    }
 
-    // Update the state and vault assets
-    total_shares = total_shares.checked_sub(shares)?;
+    Ok(response.add_attribute("withdrawal_id", withdrawal_id.to_string()))
+}
 
-    // Update user's shares
-    let updated_user_shares = user_shares.checked_sub(shares)?;
+// Reply for one swap of a single-asset withdrawal; pays out once the last swap settles
+pub fn settle_withdraw_swap(deps: DepsMut, msg: Reply) -> Result<Response, ContractError> {
+    let (withdrawal_id, slice): (u64, Coin) = from_json(&msg.payload)?;
+    let Some(mut withdrawal) = WITHDRAW_TO_PENDING.may_load(deps.storage, withdrawal_id)? else {
+        return Ok(Response::new());
+    };
 
-    // Remove user from shares map if they have no shares left
-    if updated_user_shares.is_zero() {
-        USER_SHARES.remove(deps.storage, info.sender.to_string());
-    } else {
-        USER_SHARES.save(deps.storage, info.sender.to_string(), &updated_user_shares)?;
+    match msg.result {
+        SubMsgResult::Ok(msg_response) => {
+            let (swap_out_denom, swap_out_amount) = crate::parse_swap_output(msg_response.events)?;
+            // Paying out a different denom would spend whatever the vault holds of the target
+            if swap_out_denom != withdrawal.denom {
+                return Err(ContractError::WithdrawSwapDenomMismatch {
+                    withdrawal_id,
+                    expected: withdrawal.denom,
+                    received: swap_out_denom,
+                });
+            }
+            withdrawal.amount_out = withdrawal.amount_out.checked_add(swap_out_amount)?;
+        }
+        // The swap was reverted, so the slice is still held by the vault
+        SubMsgResult::Err(_) => withdrawal.refunds.push(slice),
     }
+    withdrawal.outstanding_swaps = withdrawal
+        .outstanding_swaps
+        .checked_sub(1)
+        .ok_or(ContractError::UnexpectedWithdrawSwapReply { withdrawal_id })?;
+
+    if withdrawal.outstanding_swaps > 0 {
+        WITHDRAW_TO_PENDING.save(deps.storage, withdrawal_id, &withdrawal)?;
+        return Ok(Response::new());
+    }
+    WITHDRAW_TO_PENDING.remove(deps.storage, withdrawal_id);
 
-    TOTAL_SHARES.save(deps.storage, &total_shares)?;
-
-    // Create transfer messages and update vault asset balances
-    let transfer_msgs = assets_to_withdraw
-        .into_iter()
-        .map(|(denom, _balance, proportion)| {
-            // Update the vault asset balance
-            VAULT_ASSETS.update(
-                deps.storage,
-                denom.clone(),
-                |current_balance| -> Result<_, ContractError> {
-                    let new_balance = current_balance
-                        .unwrap_or_default()
-                        .checked_sub(proportion)?;
-                    Ok(new_balance)
-                },
-            )?;
-
-            let balance = vec![Coin {
-                denom,
-                amount: proportion,
-            }];
-            Ok::<CosmosMsg, ContractError>(CosmosMsg::Bank(BankMsg::Send {
-                to_address: info.sender.to_string(),
-                amount: balance,
-            }))
-        })
-        .collect::<Result<Vec<_>, ContractError>>()?;
+    let refunded = !withdrawal.refunds.is_empty() || withdrawal.amount_out < withdrawal.min_out;
+    let mut payout = withdrawal.refunds;
+    if !withdrawal.amount_out.is_zero() {
+        payout.push(Coin::new(withdrawal.amount_out, withdrawal.denom.clone()));
+    }
+    payout.sort_by(|a, b| a.denom.cmp(&b.denom));
 
-    // Recalculate vault value from actual post-withdrawal asset balances
-    // This ensures the stored USD value matches the real asset value after truncation
-    let updated_vault_value = calculate_vault_usd_value(deps.storage)?;
-    VAULT_VALUE_DEPOSITED.save(deps.storage, &updated_vault_value)?;
+    let event = Event::new(if refunded {
+        "withdraw_to_refunded"
+    } else {
+        "withdraw_to_completed"
+    })
+    .add_attribute("withdrawal_id", withdrawal_id.to_string())
+    .add_attribute("user", &withdrawal.user)
+    .add_attribute("denom", &withdrawal.denom)
+    .add_attribute("amount_out", withdrawal.amount_out.to_string())
+    .add_attribute("min_out", withdrawal.min_out.to_string());
+
+    let mut response = Response::new().add_event(event);
+    if !payout.is_empty() {
+        response = response.add_message(BankMsg::Send {
+            to_address: withdrawal.user.to_string(),
+            amount: payout,
+        });
+    }
+    Ok(response)
+}
 
-    Ok(Response::new()
-        .add_messages(transfer_msgs)
-        .add_attribute("method", "withdraw")
-        .add_attribute("user", info.sender)
-        .add_attribute("shares", shares.to_string())
-        .add_attribute("value_usd", user_value_usd.to_string())
-        .add_attribute("new_vault_value_usd", updated_vault_value.to_string()))
+fn coin_value(
+    storage: &dyn cosmwasm_std::Storage,
+    coin: &Coin,
+) -> Result<Decimal256, ContractError> {
+    let price = PRICES
+        .may_load(storage, coin.denom.clone())?
+        .ok_or_else(|| ContractError::MissingPrice {
+            denom: coin.denom.clone(),
+        })?;
+    let amount = Decimal256::from_atomics(coin.amount, u32::from(price.decimals))?;
+    Ok(price.price_usd.checked_mul(amount)?)
 }
 
 pub fn update_whitelist(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    to_add: Option<Vec<String>>,
+    to_add: Option<Vec<WhitelistEntry>>,
     to_remove: Option<Vec<String>>,
 ) -> Result<Response, ContractError> {
     // Check if sender is the owner using cw_ownable
     cw_ownable::assert_owner(deps.storage, &info.sender)?;
 
     let mut added_count: u32 = 0;
+    let mut updated_count: u32 = 0;
     let mut removed_count: u32 = 0;
 
-    // Add tokens to whitelist
-    if let Some(tokens_to_add) = to_add {
-        for token in tokens_to_add {
-            // Only save if not already present
-            if WHITELISTED_DENOMS
-                .may_load(deps.storage, token.clone())?
-                .is_none()
-            {
-                WHITELISTED_DENOMS.save(deps.storage, token, &())?;
+    // Add tokens to whitelist, replacing the metadata of listed ones
+    if let Some(entries_to_add) = to_add {
+        for entry in entries_to_add {
+            validate_whitelist_entry(&entry)?;
+            if WHITELISTED_DENOMS.has(deps.storage, entry.denom.clone()) {
+                updated_count = updated_count.checked_add(1).unwrap_or(updated_count);
+            } else {
                 added_count = added_count.checked_add(1).unwrap_or(added_count);
                 // If overflow, keep original count
             }
+            WHITELISTED_DENOMS.save(deps.storage, entry.denom.clone(), &entry)?;
         }
     }
 
@@ -231,9 +1421,20 @@ pub fn update_whitelist(
         .add_attribute("method", "update_whitelist")
         .add_attribute("updated_by", info.sender)
         .add_attribute("tokens_added", added_count.to_string())
+        .add_attribute("tokens_updated", updated_count.to_string())
         .add_attribute("tokens_removed", removed_count.to_string()))
 }
 
+pub fn validate_whitelist_entry(entry: &WhitelistEntry) -> Result<(), ContractError> {
+    if entry.decimals > 18 {
+        return Err(ContractError::UnsupportedDecimalPrecision {
+            denom: entry.denom.clone(),
+            decimals: entry.decimals,
+        });
+    }
+    Ok(())
+}
+
 pub fn update_prices(
     deps: DepsMut,
     env: Env,
@@ -247,13 +1448,24 @@ pub fn update_prices(
         ContractError::Unauthorized {}
     );
 
+    let pause_state = PAUSE_STATE.load(deps.storage)?;
+    if pause_state.emergency_mode {
+        return Err(ContractError::EmergencyMode {});
+    }
+    if pause_state.price_processing_paused {
+        return Err(ContractError::PriceProcessingPaused {});
+    }
+
     let mut events = Vec::new();
     let mut msgs = Vec::new();
+    let mut prices_rejected = false;
+
+    let guard_action = PRICE_GUARD_ACTION.load(deps.storage)?;
 
     // Update all provided prices
     for price_update in prices {
         // Validate that the denom is whitelisted
-        WHITELISTED_DENOMS
+        let entry = WHITELISTED_DENOMS
             .load(deps.storage, price_update.denom.clone())
             .map_err(|_| ContractError::TokenNotWhitelisted {
                 token: price_update.denom.clone(),
@@ -265,13 +1477,42 @@ pub fn update_prices(
             });
         }
 
-        if price_update.decimals > 18 {
-            return Err(ContractError::UnsupportedDecimalPrecision {
+        if price_update.decimals != entry.decimals {
+            return Err(ContractError::DecimalsMismatch {
                 denom: price_update.denom.clone(),
                 decimals: price_update.decimals,
+                expected: entry.decimals,
             });
         }
 
+        // Circuit breaker: compare against the last stored price for this denom
+        if let Some(rejection) = check_price_deviation(deps.storage, &price_update)? {
+            match guard_action {
+                PriceGuardAction::Reject => {
+                    return Err(ContractError::PriceDeviationExceeded {
+                        denom: price_update.denom,
+                        deviation_bps: rejection.deviation_bps,
+                        max_deviation_bps: rejection.max_deviation_bps,
+                    });
+                }
+                PriceGuardAction::HoldDeposits => {
+                    prices_rejected = true;
+                    events.push(
+                        Event::new("price_rejected")
+                            .add_attribute("denom", &price_update.denom)
+                            .add_attribute("price_usd", price_update.price_usd.to_string())
+                            .add_attribute("stored_price_usd", rejection.stored_price.to_string())
+                            .add_attribute("deviation_bps", rejection.deviation_bps.to_string())
+                            .add_attribute(
+                                "max_deviation_bps",
+                                rejection.max_deviation_bps.to_string(),
+                            ),
+                    );
+                    continue;
+                }
+            }
+        }
+
         // Store the new price
         PRICES.save(
             deps.storage,
@@ -293,56 +1534,163 @@ pub fn update_prices(
     // Calculate new total vault value based on current prices
     let new_vault_value = calculate_vault_usd_value(deps.storage)?;
 
-    // Process all pending deposits
-    let (processed_deposits, final_vault_value) =
-        process_pending_deposits(deps.storage, new_vault_value)?;
+    // Share token mints for fees and deposits, empty unless the vault is tokenized
+    let mut share_mints = Vec::new();
+
+    // Charge fees to existing holders before new deposits are priced in. A rejected
+    // price leaves a stale one in the vault value, so fees wait for an accepted update.
+    if !prices_rejected {
+        if let Some(fee_event) = accrue_fees(deps.storage, &env, new_vault_value, &mut share_mints)?
+        {
+            events.push(fee_event);
+        }
+    }
+
+    // Settle queued withdrawals and then pending deposits, unless a price was rejected
+    // by the circuit breaker. Withdrawals leave first so deposits are priced without them.
+    let mut withdrawal_msgs = Vec::new();
+    let mut deposit_refunds = Vec::new();
+    let (processed_deposits, final_vault_value) = if prices_rejected {
+        (Vec::new(), new_vault_value)
+    } else {
+        let (msgs, vault_value) =
+            settle_queued_withdrawals(deps.storage, &env, new_vault_value, &mut events)?;
+        withdrawal_msgs = msgs;
+        process_pending_deposits(
+            deps.storage,
+            &deps.querier,
+            &env,
+            vault_value,
+            &mut share_mints,
+            &mut deposit_refunds,
+            &mut events,
+        )?
+    };
 
     // Update the stored vault value to include all processed deposits
     VAULT_VALUE_DEPOSITED.save(deps.storage, &final_vault_value)?;
+    if !prices_rejected {
+        state::PRICES_UPDATED_AT.save(deps.storage, &env.block.time)?;
+    }
+
+    // The first deposits set the initial high-water mark
+    if !prices_rejected {
+        initialize_high_water_mark(deps.storage, final_vault_value)?;
+    }
+
+    record_nav_snapshot(deps.storage, env.block.time)?;
 
     // Add events for processed deposits
     let processed_count = processed_deposits.len();
     for deposit_info in &processed_deposits {
-        events.push(
-            cosmwasm_std::Event::new("deposit_processed")
-                .add_attribute("deposit_id", deposit_info.deposit_id.to_string())
-                .add_attribute("user", &deposit_info.user)
-                .add_attribute("value_usd", deposit_info.value_usd.to_string())
-                .add_attribute("shares_issued", deposit_info.shares_issued.to_string()),
-        );
+        let mut processed_event = cosmwasm_std::Event::new("deposit_processed")
+            .add_attribute("deposit_id", deposit_info.deposit_id.to_string())
+            .add_attribute("user", &deposit_info.user)
+            .add_attribute("value_usd", deposit_info.value_usd.to_string())
+            .add_attribute("shares_issued", deposit_info.shares_issued.to_string());
+        for coin in &deposit_info.refund {
+            processed_event = processed_event
+                .add_attribute(format!("refund_{}", coin.denom), coin.amount.to_string());
+        }
+        events.push(processed_event);
     }
 
+    // Routes planned from rejected prices are not trusted, and trading may be paused
+    let skip_reason = if prices_rejected {
+        Some("price_rejected")
+    } else if pause_state.trading_paused {
+        Some("trading_paused")
+    } else {
+        None
+    };
+    let swap_routes = match (swap_routes, skip_reason) {
+        (Some(routes), Some(reason)) if !routes.is_empty() => {
+            events.push(
+                Event::new("rebalancing_skipped")
+                    .add_attribute("reason", reason)
+                    .add_attribute("swap_count", routes.len().to_string()),
+            );
+            None
+        }
+        (routes, _) => routes,
+    };
+
     // Execute rebalancing at the end
     if let Some(swap_routes) = swap_routes {
         if !swap_routes.is_empty() {
-            let entry_point = SKIP_ENTRY_POINT.load(deps.storage)?;
-
             events.push(
                 cosmwasm_std::Event::new("rebalancing_started")
                     .add_attribute("swap_count", swap_routes.len().to_string()),
             );
-            for route in swap_routes {
-                if env.block.time >= route.timeout {
-                    return Err(ContractError::SwapRouteExpired {});
-                }
-
-                if route.amount_in.is_zero() {
-                    return Err(ContractError::SwapRouteZeroAmount {
-                        denom: route.offer_denom,
-                    });
-                }
+            let max_slippage_bps = MAX_SLIPPAGE_BPS.load(deps.storage)?;
+            let max_slippage = Decimal256::from_ratio(max_slippage_bps, BPS_DENOMINATOR);
+
+            let mut accepted_routes = Vec::new();
+            for (route_index, route) in swap_routes.into_iter().enumerate() {
+                let min_amount_out = route
+                    .minimum_amount_out
+                    .unwrap_or(route.estimated_amount_out);
+
+                // A bad route is dropped on its own so prices and deposits still settle
+                let rejection = if let Some((reason, error)) =
+                    screen_route(deps.storage, env.block.time, &route)?
+                {
+                    Event::new("swap_route_rejected")
+                        .add_attribute("reason", reason)
+                        .add_attribute("error", error.to_string())
+                } else {
+                    // Independent check of the operator's minimum output against stored prices
+                    match value_route(deps.storage, &route, min_amount_out)? {
+                        None => Event::new("swap_route_rejected")
+                            .add_attribute("reason", "missing_price"),
+                        Some(valuation) if valuation.slippage > max_slippage => {
+                            Event::new("swap_route_rejected")
+                                .add_attribute("reason", "slippage_exceeded")
+                                .add_attribute(
+                                    "slippage_bps",
+                                    to_bps(valuation.slippage)?.to_string(),
+                                )
+                                .add_attribute("max_slippage_bps", max_slippage_bps.to_string())
+                        }
+                        Some(valuation) => {
+                            accepted_routes.push(AcceptedRoute {
+                                route,
+                                min_amount_out,
+                                value_usd: valuation.offer_value_usd,
+                            });
+                            continue;
+                        }
+                    }
+                };
+                events.push(
+                    rejection
+                        .add_attribute("route_index", route_index.to_string())
+                        .add_attribute("offer_denom", &route.offer_denom)
+                        .add_attribute("offer_amount", route.amount_in.to_string())
+                        .add_attribute("ask_denom", &route.ask_denom)
+                        .add_attribute("min_amount_out", min_amount_out.to_string()),
+                );
+            }
 
-                WHITELISTED_DENOMS
-                    .load(deps.storage, route.offer_denom.clone())
-                    .map_err(|_| ContractError::TokenNotWhitelisted {
-                        token: route.offer_denom.clone(),
-                    })?;
-                WHITELISTED_DENOMS
-                    .load(deps.storage, route.ask_denom.clone())
-                    .map_err(|_| ContractError::TokenNotWhitelisted {
-                        token: route.ask_denom.clone(),
-                    })?;
+            // Limits apply to the plan as a whole so a partial rebalance is never executed
+            if let Some(limit_event) = check_trading_limits(
+                deps.storage,
+                env.block.time,
+                final_vault_value,
+                &accepted_routes,
+            )? {
+                events.push(limit_event);
+                accepted_routes.clear();
+            }
 
+            let entry_point = SKIP_ENTRY_POINT.load(deps.storage)?;
+            let envelope_timestamp = state::LAST_ENVELOPE_TIMESTAMP.may_load(deps.storage)?;
+            for AcceptedRoute {
+                route,
+                min_amount_out,
+                value_usd,
+            } in accepted_routes
+            {
                 let swap_coin = Coin {
                     denom: route.offer_denom.clone(),
                     amount: route.amount_in.into(),
@@ -350,12 +1698,19 @@ pub fn update_prices(
 
                 let target_denom = route.ask_denom.clone();
 
-                TRADE_TRACKER.push_back(
+                let trade_id =
+                    TRADE_ID_COUNTER.update::<_, ContractError>(deps.storage, |id| Ok(id + 1))?;
+                PENDING_TRADES.save(
                     deps.storage,
+                    trade_id,
                     &TradeInfo {
+                        id: trade_id,
                         in_coin: swap_coin.clone(),
                         out_denom: target_denom.clone(),
                         timeout_timestamp: route.timeout.nanos(),
+                        swap_venue: route.swap_venue_name.clone(),
+                        envelope_timestamp,
+                        value_usd,
                     },
                 )?;
 
@@ -367,10 +1722,7 @@ pub fn update_prices(
                     }),
                     min_asset: SkipAsset::Native(Coin {
                         denom: target_denom.clone(),
-                        amount: route
-                            .minimum_amount_out
-                            .unwrap_or(route.estimated_amount_out)
-                            .into(),
+                        amount: min_amount_out.into(),
                     }),
                     timeout_timestamp: route.timeout.nanos(),
                     post_swap_action: SkipAction::Transfer {
@@ -382,70 +1734,569 @@ pub fn update_prices(
                 // Add trade event for frontend visibility
                 events.push(
                     cosmwasm_std::Event::new("trade_initiated")
+                        .add_attribute("trade_id", trade_id.to_string())
                         .add_attribute("offer_denom", &route.offer_denom)
                         .add_attribute("offer_amount", route.amount_in.to_string())
                         .add_attribute("ask_denom", &route.ask_denom)
-                        .add_attribute(
-                            "min_amount_out",
-                            route
-                                .minimum_amount_out
-                                .unwrap_or(route.estimated_amount_out)
-                                .to_string(),
-                        )
+                        .add_attribute("min_amount_out", min_amount_out.to_string())
                         .add_attribute("swap_venue", &route.swap_venue_name)
                         .add_attribute("timeout", route.timeout.nanos().to_string()),
                 );
 
-                msgs.push(SubMsg::reply_always(
-                    WasmMsg::Execute {
-                        contract_addr: entry_point.to_string(),
-                        msg: to_json_binary(&swap_msg)?,
-                        funds: vec![swap_coin],
-                    },
-                    REPLY_TRACKER_ID,
-                ))
-            }
-        }
+                msgs.push(
+                    SubMsg::reply_always(
+                        WasmMsg::Execute {
+                            contract_addr: entry_point.to_string(),
+                            msg: to_json_binary(&swap_msg)?,
+                            funds: vec![swap_coin],
+                        },
+                        REPLY_TRACKER_ID,
+                    )
+                    .with_payload(to_json_binary(&trade_id)?),
+                )
+            }
+        }
+    }
+
+    Ok(Response::new()
+        .add_messages(withdrawal_msgs)
+        .add_messages(share_mints)
+        .add_messages(deposit_refunds)
+        .add_submessages(msgs)
+        .add_events(events)
+        .add_attribute("method", "update_prices")
+        .add_attribute("updated_by", info.sender)
+        .add_attribute("final_vault_value_usd", final_vault_value.to_string())
+        .add_attribute("processed_deposits", processed_count.to_string()))
+}
+
+struct AcceptedRoute {
+    route: SwapRoute,
+    min_amount_out: Uint128,
+    value_usd: Decimal256,
+}
+
+struct RouteValuation {
+    offer_value_usd: Decimal256,
+    // Fraction by which the minimum output falls short of the output implied by stored prices
+    slippage: Decimal256,
+}
+
+// Why a route can't be executed at all, as an event reason and the matching error
+fn screen_route(
+    storage: &dyn cosmwasm_std::Storage,
+    now: Timestamp,
+    route: &SwapRoute,
+) -> Result<Option<(&'static str, ContractError)>, ContractError> {
+    if now >= route.timeout {
+        return Ok(Some(("expired", ContractError::SwapRouteExpired {})));
+    }
+    if route.amount_in.is_zero() {
+        return Ok(Some((
+            "zero_amount",
+            ContractError::SwapRouteZeroAmount {
+                denom: route.offer_denom.clone(),
+            },
+        )));
+    }
+    for denom in [&route.offer_denom, &route.ask_denom] {
+        match WHITELISTED_DENOMS.may_load(storage, denom.clone())? {
+            None => {
+                return Ok(Some((
+                    "not_whitelisted",
+                    ContractError::TokenNotWhitelisted {
+                        token: denom.clone(),
+                    },
+                )))
+            }
+            Some(entry) if !entry.tradable => {
+                return Ok(Some((
+                    "not_tradable",
+                    ContractError::DenomNotTradable {
+                        denom: denom.clone(),
+                    },
+                )))
+            }
+            Some(_) => {}
+        }
+    }
+    match check_swap_allowlist(storage, &route.swap_venue_name, &route.operations) {
+        Ok(()) => {}
+        Err(
+            error @ (ContractError::SwapVenueNotAllowed { .. }
+            | ContractError::SwapPoolNotAllowed { .. }),
+        ) => return Ok(Some(("not_allowlisted", error))),
+        Err(error) => return Err(error),
+    }
+    Ok(None)
+}
+
+// Values a route at stored prices. Returns None when either denom has no usable price.
+fn value_route(
+    storage: &dyn cosmwasm_std::Storage,
+    route: &SwapRoute,
+    min_amount_out: Uint128,
+) -> Result<Option<RouteValuation>, ContractError> {
+    let (Some(offer_price), Some(ask_price)) = (
+        PRICES.may_load(storage, route.offer_denom.clone())?,
+        PRICES.may_load(storage, route.ask_denom.clone())?,
+    ) else {
+        return Ok(None);
+    };
+    if ask_price.price_usd.is_zero() {
+        return Ok(None);
+    }
+
+    let offer_amount = Decimal256::from_atomics(
+        Uint256::from(route.amount_in),
+        u32::from(offer_price.decimals),
+    )?;
+    let offer_value_usd = offer_price.price_usd.checked_mul(offer_amount)?;
+    let fair_amount_out = offer_value_usd.checked_div(ask_price.price_usd)?;
+    if fair_amount_out.is_zero() {
+        return Ok(None);
+    }
+
+    let min_amount_out =
+        Decimal256::from_atomics(Uint256::from(min_amount_out), u32::from(ask_price.decimals))?;
+    let slippage = if min_amount_out >= fair_amount_out {
+        Decimal256::zero()
+    } else {
+        fair_amount_out
+            .checked_sub(min_amount_out)?
+            .checked_div(fair_amount_out)?
+    };
+
+    Ok(Some(RouteValuation {
+        offer_value_usd,
+        slippage,
+    }))
+}
+
+// Returns a `rebalancing_skipped` event when the plan breaks one of the owner's trading limits
+fn check_trading_limits(
+    storage: &dyn cosmwasm_std::Storage,
+    now: Timestamp,
+    vault_value: Decimal256,
+    routes: &[AcceptedRoute],
+) -> Result<Option<Event>, ContractError> {
+    let limits = TRADING_LIMITS.load(storage)?;
+    let trade_value = routes
+        .iter()
+        .try_fold(Decimal256::zero(), |acc, accepted| {
+            acc.checked_add(accepted.value_usd)
+        })?;
+    let skipped = |reason: &str| {
+        Event::new("rebalancing_skipped")
+            .add_attribute("reason", reason)
+            .add_attribute("swap_count", routes.len().to_string())
+            .add_attribute("trade_value_usd", trade_value.to_string())
+    };
+
+    if let Some(max_routes) = limits.max_routes {
+        if routes.len() > max_routes as usize {
+            return Ok(Some(
+                skipped("max_routes").add_attribute("max_routes", max_routes.to_string()),
+            ));
+        }
+    }
+
+    if let Some(max_trade_fraction) = limits.max_trade_fraction {
+        let max_trade_value = vault_value.checked_mul(max_trade_fraction)?;
+        if trade_value > max_trade_value {
+            return Ok(Some(skipped("max_trade_fraction").add_attribute(
+                "max_trade_value_usd",
+                max_trade_value.to_string(),
+            )));
+        }
+    }
+
+    if let Some(max_daily_turnover) = limits.max_daily_turnover_usd {
+        let remaining = max_daily_turnover.saturating_sub(rolling_turnover(storage, now)?);
+        if trade_value > remaining {
+            return Ok(Some(
+                skipped("daily_turnover")
+                    .add_attribute("remaining_turnover_usd", remaining.to_string()),
+            ));
+        }
+    }
+
+    Ok(None)
+}
+
+// USD value traded within the last 24 hours
+pub fn rolling_turnover(
+    storage: &dyn cosmwasm_std::Storage,
+    now: Timestamp,
+) -> StdResult<Decimal256> {
+    let window_start = now.minus_seconds(TURNOVER_WINDOW_SECONDS);
+    TURNOVER_LOG
+        .iter(storage)?
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .map_or(true, |entry| entry.timestamp > window_start)
+        })
+        .try_fold(Decimal256::zero(), |acc, entry| {
+            Ok(acc.checked_add(entry?.value_usd)?)
+        })
+}
+
+pub fn record_turnover(
+    storage: &mut dyn cosmwasm_std::Storage,
+    now: Timestamp,
+    value_usd: Decimal256,
+) -> StdResult<()> {
+    // Drop entries that have left the window so the log stays bounded
+    let window_start = now.minus_seconds(TURNOVER_WINDOW_SECONDS);
+    while let Some(entry) = TURNOVER_LOG.front(storage)? {
+        if entry.timestamp > window_start {
+            break;
+        }
+        TURNOVER_LOG.pop_front(storage)?;
+    }
+
+    TURNOVER_LOG.push_back(
+        storage,
+        &TurnoverEntry {
+            timestamp: now,
+            value_usd,
+        },
+    )
+}
+
+fn to_bps(ratio: Decimal256) -> Result<u64, ContractError> {
+    let bps = ratio
+        .checked_mul(Decimal256::from_ratio(BPS_DENOMINATOR, 1u64))?
+        .to_uint_floor();
+    Ok(Uint64::try_from(bps).map_or(u64::MAX, |bps| bps.u64()))
+}
+
+struct PriceDeviation {
+    stored_price: Decimal256,
+    deviation_bps: u64,
+    max_deviation_bps: u64,
+}
+
+// Returns the deviation when a guarded denom moves further from its stored price than allowed
+fn check_price_deviation(
+    storage: &dyn cosmwasm_std::Storage,
+    price_update: &PriceInfo,
+) -> Result<Option<PriceDeviation>, ContractError> {
+    let Some(max_deviation_bps) = PRICE_GUARDS.may_load(storage, price_update.denom.clone())?
+    else {
+        return Ok(None);
+    };
+
+    let Some(stored) = PRICES.may_load(storage, price_update.denom.clone())? else {
+        return Ok(None);
+    };
+    if stored.price_usd.is_zero() {
+        return Ok(None);
+    }
+
+    let difference = price_update.price_usd.abs_diff(stored.price_usd);
+    let deviation = difference.checked_div(stored.price_usd)?;
+    let max_deviation = Decimal256::from_ratio(max_deviation_bps, BPS_DENOMINATOR);
+    if deviation <= max_deviation {
+        return Ok(None);
+    }
+
+    Ok(Some(PriceDeviation {
+        stored_price: stored.price_usd,
+        deviation_bps: to_bps(deviation)?,
+        max_deviation_bps,
+    }))
+}
+
+// Calculate the total USD value of all vault assets based on current prices
+pub fn calculate_vault_usd_value(
+    storage: &mut dyn cosmwasm_std::Storage,
+) -> Result<Decimal256, ContractError> {
+    let mut total_value = Decimal256::zero();
+
+    // Iterate through all vault assets
+    for item in VAULT_ASSETS.range(storage, None, None, cosmwasm_std::Order::Ascending) {
+        let (denom, balance) = item?;
+
+        // Get the current price for this denom
+        if let Some(price_info) = PRICES.may_load(storage, denom.clone())? {
+            let decimals = u32::from(price_info.decimals);
+            let balance_decimal = Decimal256::from_atomics(balance, decimals)?;
+            let usd_value = price_info.price_usd.checked_mul(balance_decimal)?;
+            total_value = total_value.checked_add(usd_value)?;
+        }
+        // If no price is available, we assume the asset has no USD value
+        // This could be enhanced to handle missing prices differently
+    }
+
+    Ok(total_value)
+}
+
+// Mint fee shares to the fee recipient for management fees accrued since the last
+// accrual and performance fees on any share price gain above the high-water mark.
+// `vault_value` must reflect current prices but exclude pending deposits.
+pub fn accrue_fees(
+    storage: &mut dyn cosmwasm_std::Storage,
+    env: &Env,
+    vault_value: Decimal256,
+    share_mints: &mut Vec<CosmosMsg>,
+) -> Result<Option<Event>, ContractError> {
+    let now = env.block.time;
+    let fee_config = FEE_CONFIG.load(storage)?;
+    let mut fee_state = FEE_STATE.load(storage)?;
+    let total_shares = TOTAL_SHARES.load(storage)?;
+
+    let elapsed = now
+        .seconds()
+        .saturating_sub(fee_state.last_accrual.seconds());
+    fee_state.last_accrual = now;
+
+    // Nothing to charge against; the mark is re-established by the next deposit
+    if total_shares.is_zero() || vault_value.is_zero() {
+        fee_state.high_water_mark = Decimal256::zero();
+        FEE_STATE.save(storage, &fee_state)?;
+        return Ok(None);
+    }
+
+    let Some(fee_recipient) = fee_config.fee_recipient else {
+        FEE_STATE.save(storage, &fee_state)?;
+        return Ok(None);
+    };
+
+    // Management fee: annual rate pro-rated per second against the vault value
+    let management_fee_usd =
+        management_fee_for_period(vault_value, fee_config.management_fee_rate, elapsed)?;
+    let management_fee_shares =
+        fee_shares_for_value(management_fee_usd, vault_value, total_shares)?;
+    let shares_after_management = total_shares.checked_add(management_fee_shares)?;
+
+    // Performance fee: charged only on the per-share gain above the high-water mark
+    let price_per_share = share_price(vault_value, shares_after_management)?;
+    let mut performance_fee_usd = Decimal256::zero();
+    let mut performance_fee_shares = Uint256::zero();
+    if fee_state.high_water_mark.is_zero() {
+        fee_state.high_water_mark = price_per_share;
+    } else if price_per_share > fee_state.high_water_mark {
+        let gain = price_per_share
+            .checked_sub(fee_state.high_water_mark)?
+            .checked_mul(Decimal256::from_atomics(shares_after_management, 0)?)?;
+        performance_fee_usd = gain.checked_mul(fee_config.performance_fee_rate)?;
+        performance_fee_shares =
+            fee_shares_for_value(performance_fee_usd, vault_value, shares_after_management)?;
+        fee_state.high_water_mark = share_price(
+            vault_value,
+            shares_after_management.checked_add(performance_fee_shares)?,
+        )?;
+    }
+
+    let minted = management_fee_shares.checked_add(performance_fee_shares)?;
+    if !minted.is_zero() {
+        share_mints.extend(issue_shares(storage, env, fee_recipient.as_str(), minted)?);
+        TOTAL_SHARES.save(storage, &total_shares.checked_add(minted)?)?;
+    }
+
+    fee_state.total_management_fee_shares = fee_state
+        .total_management_fee_shares
+        .checked_add(management_fee_shares)?;
+    fee_state.total_performance_fee_shares = fee_state
+        .total_performance_fee_shares
+        .checked_add(performance_fee_shares)?;
+    FEE_STATE.save(storage, &fee_state)?;
+
+    if minted.is_zero() {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        Event::new("fees_accrued")
+            .add_attribute("fee_recipient", fee_recipient)
+            .add_attribute("period_seconds", elapsed.to_string())
+            .add_attribute("management_fee_usd", management_fee_usd.to_string())
+            .add_attribute("management_fee_shares", management_fee_shares.to_string())
+            .add_attribute("performance_fee_usd", performance_fee_usd.to_string())
+            .add_attribute("performance_fee_shares", performance_fee_shares.to_string())
+            .add_attribute("high_water_mark", fee_state.high_water_mark.to_string())
+            .add_attribute("vault_value_usd", vault_value.to_string()),
+    ))
+}
+
+pub fn management_fee_for_period(
+    vault_value: Decimal256,
+    annual_rate: Decimal256,
+    elapsed_seconds: u64,
+) -> StdResult<Decimal256> {
+    let year_fraction = Decimal256::from_ratio(elapsed_seconds, SECONDS_PER_YEAR);
+    Ok(vault_value
+        .checked_mul(annual_rate)?
+        .checked_mul(year_fraction)?)
+}
+
+// Shares that, once minted, are worth `fee_usd` at `share_price`:
+// shares = (total_shares + VIRTUAL_SHARES) * fee / (vault_value + VIRTUAL_ASSETS_USD - fee),
+// rounded down in favor of holders
+fn fee_shares_for_value(
+    fee_usd: Decimal256,
+    vault_value: Decimal256,
+    total_shares: Uint256,
+) -> Result<Uint256, ContractError> {
+    if fee_usd.is_zero() || fee_usd >= vault_value {
+        return Ok(Uint256::zero());
     }
 
-    Ok(Response::new()
-        .add_submessages(msgs)
-        .add_events(events)
-        .add_attribute("method", "update_prices")
-        .add_attribute("updated_by", info.sender)
-        .add_attribute("final_vault_value_usd", final_vault_value.to_string())
-        .add_attribute("processed_deposits", processed_count.to_string()))
+    let remaining_value = vault_value
+        .checked_add(VIRTUAL_ASSETS_USD)?
+        .checked_sub(fee_usd)?;
+    Ok(
+        Decimal256::from_atomics(total_shares.checked_add(VIRTUAL_SHARES.into())?, 0)?
+            .checked_mul(fee_usd)?
+            .checked_div(remaining_value)?
+            .to_uint_floor(),
+    )
 }
 
-// Calculate the total USD value of all vault assets based on current prices
-pub fn calculate_vault_usd_value(
-    storage: &mut dyn cosmwasm_std::Storage,
+// USD per share at the rate `shares_for_value` mints at, virtual offsets included. The
+// high-water mark, fee dilution and the reported share price all go through this.
+pub fn share_price(
+    vault_value: Decimal256,
+    total_shares: Uint256,
 ) -> Result<Decimal256, ContractError> {
-    let mut total_value = Decimal256::zero();
+    let virtual_total_shares =
+        Decimal256::from_atomics(total_shares.checked_add(VIRTUAL_SHARES.into())?, 0)?;
+    Ok(vault_value
+        .checked_add(VIRTUAL_ASSETS_USD)?
+        .checked_div(virtual_total_shares)?)
+}
 
-    // Iterate through all vault assets
-    for item in VAULT_ASSETS.range(storage, None, None, cosmwasm_std::Order::Ascending) {
-        let (denom, balance) = item?;
+// Snapshots share a key within a block, so the last state of the block wins
+pub fn record_nav_snapshot(
+    storage: &mut dyn cosmwasm_std::Storage,
+    now: Timestamp,
+) -> Result<(), ContractError> {
+    let tvl = VAULT_VALUE_DEPOSITED.load(storage)?;
+    let total_shares = TOTAL_SHARES.load(storage)?;
+    let share_price = if total_shares.is_zero() {
+        Decimal256::zero()
+    } else {
+        share_price(tvl, total_shares)?
+    };
+    let balances = VAULT_ASSETS
+        .range(storage, None, None, cosmwasm_std::Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect::<StdResult<Vec<_>>>()?;
 
-        // Get the current price for this denom
-        if let Some(price_info) = PRICES.may_load(storage, denom.clone())? {
-            let decimals = u32::from(price_info.decimals);
-            let balance_decimal = Decimal256::from_atomics(balance, decimals)?;
-            let usd_value = price_info.price_usd.checked_mul(balance_decimal)?;
-            total_value = total_value.checked_add(usd_value)?;
+    let key = now.nanos();
+    let mut count = NAV_SNAPSHOT_COUNT.may_load(storage)?.unwrap_or_default();
+    if !NAV_SNAPSHOTS.has(storage, key) {
+        count += 1;
+    }
+    NAV_SNAPSHOTS.save(
+        storage,
+        key,
+        &NavSnapshot {
+            timestamp: now,
+            tvl,
+            total_shares,
+            share_price,
+            balances,
+        },
+    )?;
+
+    // At most one snapshot is added per call, so dropping the oldest keeps the bound
+    if count > MAX_NAV_SNAPSHOTS {
+        let oldest = NAV_SNAPSHOTS
+            .keys(storage, None, None, cosmwasm_std::Order::Ascending)
+            .next()
+            .transpose()?;
+        if let Some(oldest) = oldest {
+            NAV_SNAPSHOTS.remove(storage, oldest);
+            count -= 1;
         }
-        // If no price is available, we assume the asset has no USD value
-        // This could be enhanced to handle missing prices differently
     }
+    NAV_SNAPSHOT_COUNT.save(storage, &count)?;
 
-    Ok(total_value)
+    Ok(())
+}
+
+fn initialize_high_water_mark(
+    storage: &mut dyn cosmwasm_std::Storage,
+    vault_value: Decimal256,
+) -> Result<(), ContractError> {
+    let mut fee_state = FEE_STATE.load(storage)?;
+    let total_shares = TOTAL_SHARES.load(storage)?;
+    if fee_state.high_water_mark.is_zero() && !total_shares.is_zero() && !vault_value.is_zero() {
+        fee_state.high_water_mark = share_price(vault_value, total_shares)?;
+        FEE_STATE.save(storage, &fee_state)?;
+    }
+    Ok(())
+}
+
+// Credit newly issued shares to `recipient`. Tokenized vaults mint the share denom,
+// otherwise the shares are booked in USER_SHARES. Callers maintain TOTAL_SHARES.
+fn issue_shares(
+    storage: &mut dyn cosmwasm_std::Storage,
+    env: &Env,
+    recipient: &str,
+    shares: Uint256,
+) -> Result<Option<CosmosMsg>, ContractError> {
+    if let Some(share_denom) = SHARE_DENOM.may_load(storage)? {
+        let contract = env.contract.address.as_str();
+        return Ok(Some(token_factory::mint_msg(
+            contract,
+            &Coin::new(shares, share_denom),
+            recipient,
+        )));
+    }
+
+    USER_SHARES.update(
+        storage,
+        recipient.to_string(),
+        |user_shares| -> Result<_, ContractError> {
+            Ok(user_shares.unwrap_or_default().checked_add(shares)?)
+        },
+    )?;
+    Ok(None)
+}
+
+// Shares minted for `value_usd` of deposits, rounded down in favor of the vault:
+// new_shares = value_usd * (total_shares + VIRTUAL_SHARES) / (vault_value + VIRTUAL_ASSETS_USD)
+pub fn shares_for_value(
+    value_usd: Decimal256,
+    vault_value: Decimal256,
+    total_shares: Uint256,
+) -> Result<Uint256, ContractError> {
+    let virtual_total_shares =
+        Decimal256::from_atomics(total_shares.checked_add(VIRTUAL_SHARES.into())?, 0)?;
+    Ok(value_usd
+        .checked_mul(virtual_total_shares)?
+        .checked_div(vault_value.checked_add(VIRTUAL_ASSETS_USD)?)?
+        .to_uint_floor())
+}
+
+// USD value redeemed by burning `shares`, counting the virtual shares so redemptions
+// never pay out more than minting charged
+pub fn value_for_shares(
+    shares: Uint256,
+    vault_value: Decimal256,
+    total_shares: Uint256,
+) -> Result<Decimal256, ContractError> {
+    Ok(
+        Decimal256::from_ratio(shares, total_shares.checked_add(VIRTUAL_SHARES.into())?)
+            .checked_mul(vault_value)?,
+    )
 }
 
 // Process all pending deposits using batch calculation for fair allocation
-// Returns processed deposits and the final vault value including all deposits
+// Returns processed deposits and the final vault value including all deposits.
+// Deposits are trimmed to the owner's caps, with the excess refunded.
 fn process_pending_deposits(
     storage: &mut dyn cosmwasm_std::Storage,
+    querier: &QuerierWrapper,
+    env: &Env,
     vault_value: Decimal256,
+    share_mints: &mut Vec<CosmosMsg>,
+    refunds: &mut Vec<CosmosMsg>,
+    events: &mut Vec<Event>,
 ) -> Result<(Vec<ProcessedDepositInfo>, Decimal256), ContractError> {
     let mut processed_deposits = Vec::new();
     let total_shares = TOTAL_SHARES.load(storage)?;
@@ -481,7 +2332,7 @@ fn process_pending_deposits(
                         None
                     }
                 }
-                DepositState::Completed { .. } => None,
+                DepositState::Completed { .. } | DepositState::Cancelled => None,
             }
         })
         .collect();
@@ -490,96 +2341,155 @@ fn process_pending_deposits(
         return Ok((processed_deposits, vault_value));
     }
 
-    // Calculate total value of all pending deposits
-    let total_deposit_value: Decimal256 = pending_deposits
-        .iter()
-        .map(|(_, value)| *value)
-        .try_fold(Decimal256::zero(), |acc, val| acc.checked_add(val))?;
-
-    // Calculate final vault value after all deposits
-    let final_vault_value = vault_value.checked_add(total_deposit_value)?;
-
-    // Calculate all share allocations using the standard formula: new_shares = total_shares * value_usd / current_vault_value
-    let mut new_shares_for_deposits = Vec::new();
-    for (_, value_usd) in &pending_deposits {
-        let new_shares = if total_shares.is_zero() {
-            // Share precision - 1e6 shares per USD
-            let share_precision = Decimal256::from_ratio(1000000u128, 1u128);
-            // First deposit: shares = value_usd * PRECISION
-            value_usd.checked_mul(share_precision)?.to_uint_ceil()
-        } else {
-            // Standard formula: new_shares = total_shares * value_usd / current_vault_value
-            // This ensures proportional share issuance based on current vault value
-            let total_shares_decimal = Decimal256::from_atomics(total_shares, 0)?;
-            let new_shares_decimal = total_shares_decimal
-                .checked_mul(*value_usd)?
-                .checked_div(vault_value)?;
-            new_shares_decimal.to_uint_ceil()
-        };
-        new_shares_for_deposits.push(new_shares);
-    }
+    let caps = DEPOSIT_CAPS.load(storage)?;
+    // Position of each depositor seen in this batch, starting from the shares they held
+    let mut positions: Vec<(Addr, Decimal256)> = Vec::new();
 
-    // Process each deposit with pre-calculated shares
+    // Every deposit is priced against the same pre-deposit vault value, while caps
+    // apply to the running totals so earlier deposits fill them first
+    let mut final_vault_value = vault_value;
     let mut new_total_shares = total_shares;
-    for (i, (deposit_id, value_usd)) in pending_deposits.into_iter().enumerate() {
+    for (deposit_id, value_usd) in pending_deposits {
         let mut deposit_request = DEPOSIT_REQUESTS.load(storage, deposit_id)?;
-        let new_shares = new_shares_for_deposits[i];
 
-        // Only process if still pending
-        if let DepositState::Pending = deposit_request.state {
-            // Update deposit state to completed
-            deposit_request.state = DepositState::Completed { value_usd };
-            DEPOSIT_REQUESTS.save(storage, deposit_id, &deposit_request)?;
+        let position = match positions
+            .iter()
+            .position(|(user, _)| *user == deposit_request.user)
+        {
+            Some(index) => index,
+            None => {
+                let held = held_position_value(storage, querier, &deposit_request.user)?;
+                positions.push((deposit_request.user.clone(), held));
+                positions.len() - 1
+            }
+        };
+
+        let accepted = fit_deposit_to_caps(
+            storage,
+            &caps,
+            &deposit_request.coins,
+            value_usd,
+            final_vault_value,
+            positions[position].1,
+        )?;
+        let trimmed = accepted != deposit_request.coins;
+        let accepted_value = if trimmed {
+            priced_value(storage, &accepted)?
+        } else {
+            value_usd
+        };
+        let new_shares = shares_for_value(accepted_value, vault_value, total_shares)?;
+
+        // Deposits too small to mint a whole share stay pending so the depositor can
+        // cancel them, unless the caps trimmed them in which case they are refunded
+        if new_shares.is_zero() && !trimmed {
+            continue;
+        }
 
-            // Update user's shares
-            USER_SHARES.update(
+        for coin in &deposit_request.coins {
+            let new_pending_balance = TOTAL_PENDING_ASSETS.update(
                 storage,
-                deposit_request.user.to_string(),
-                |user_shares| -> Result<_, ContractError> {
-                    let current_shares = user_shares.unwrap_or_default();
-                    let updated_shares = current_shares.checked_add(new_shares)?;
-                    Ok(updated_shares)
+                coin.denom.clone(),
+                |balance| -> Result<_, ContractError> {
+                    let current_balance = balance.unwrap_or_default();
+                    let updated_balance = current_balance.checked_sub(coin.amount)?;
+                    Ok(updated_balance)
                 },
             )?;
+            if new_pending_balance.is_zero() {
+                TOTAL_PENDING_ASSETS.remove(storage, coin.denom.clone());
+            }
+        }
+        release_user_pending(storage, &deposit_request)?;
 
-            // Add all deposited coins to vault assets
-            for coin in &deposit_request.coins {
-                VAULT_ASSETS.update(
-                    storage,
-                    coin.denom.clone(),
-                    |balance| -> Result<_, ContractError> {
-                        let current_balance = balance.unwrap_or_default();
-                        let updated_balance = current_balance.checked_add(coin.amount)?;
-                        Ok(updated_balance)
-                    },
-                )?;
-
-                let pending_amount: Uint256 = coin.amount;
-                let new_pending_balance = TOTAL_PENDING_ASSETS.update(
-                    storage,
-                    coin.denom.clone(),
-                    |balance| -> Result<_, ContractError> {
-                        let current_balance = balance.unwrap_or_default();
-                        let updated_balance = current_balance.checked_sub(pending_amount)?;
-                        Ok(updated_balance)
-                    },
-                )?;
-                if new_pending_balance.is_zero() {
-                    TOTAL_PENDING_ASSETS.remove(storage, coin.denom.clone());
+        let accepted: Vec<Coin> = if new_shares.is_zero() {
+            Vec::new()
+        } else {
+            accepted
+        };
+        let refund: Vec<Coin> = deposit_request
+            .coins
+            .iter()
+            .map(|coin| {
+                let kept = accepted
+                    .iter()
+                    .find(|accepted| accepted.denom == coin.denom)
+                    .map_or_else(Uint256::zero, |accepted| accepted.amount);
+                Ok(Coin {
+                    denom: coin.denom.clone(),
+                    amount: coin.amount.checked_sub(kept)?,
+                })
+            })
+            .filter(|coin: &Result<Coin, ContractError>| {
+                coin.as_ref().map_or(true, |coin| !coin.amount.is_zero())
+            })
+            .collect::<Result<_, _>>()?;
+        if !refund.is_empty() {
+            refunds.push(
+                BankMsg::Send {
+                    to_address: deposit_request.user.to_string(),
+                    amount: refund.clone(),
                 }
-            }
+                .into(),
+            );
+        }
 
-            // Update total shares for next calculation
-            new_total_shares = new_total_shares.checked_add(new_shares)?;
+        if new_shares.is_zero() {
+            deposit_request.state = DepositState::Cancelled;
+            DEPOSIT_REQUESTS.save(storage, deposit_id, &deposit_request)?;
 
-            // Record the processed deposit info for events
-            processed_deposits.push(ProcessedDepositInfo {
-                deposit_id,
-                user: deposit_request.user.to_string(),
-                value_usd,
-                shares_issued: new_shares,
-            });
+            let mut cancel_event = Event::new("deposit_cancelled")
+                .add_attribute("deposit_id", deposit_id.to_string())
+                .add_attribute("user", &deposit_request.user)
+                .add_attribute("reason", "deposit_cap");
+            for coin in &refund {
+                cancel_event = cancel_event
+                    .add_attribute(format!("refund_{}", coin.denom), coin.amount.to_string());
+            }
+            events.push(cancel_event);
+            continue;
+        }
+
+        // The deposit record keeps only what entered the vault
+        deposit_request.coins = accepted;
+        deposit_request.state = DepositState::Completed {
+            value_usd: accepted_value,
+        };
+        DEPOSIT_REQUESTS.save(storage, deposit_id, &deposit_request)?;
+
+        // Update user's shares
+        share_mints.extend(issue_shares(
+            storage,
+            env,
+            deposit_request.user.as_str(),
+            new_shares,
+        )?);
+
+        // Add all accepted coins to vault assets
+        for coin in &deposit_request.coins {
+            VAULT_ASSETS.update(
+                storage,
+                coin.denom.clone(),
+                |balance| -> Result<_, ContractError> {
+                    let current_balance = balance.unwrap_or_default();
+                    let updated_balance = current_balance.checked_add(coin.amount)?;
+                    Ok(updated_balance)
+                },
+            )?;
         }
+
+        final_vault_value = final_vault_value.checked_add(accepted_value)?;
+        positions[position].1 = positions[position].1.checked_add(accepted_value)?;
+        new_total_shares = new_total_shares.checked_add(new_shares)?;
+
+        // Record the processed deposit info for events
+        processed_deposits.push(ProcessedDepositInfo {
+            deposit_id,
+            user: deposit_request.user.to_string(),
+            value_usd: accepted_value,
+            shares_issued: new_shares,
+            refund,
+        });
     }
 
     // Save the final total shares
@@ -588,12 +2498,62 @@ fn process_pending_deposits(
     Ok((processed_deposits, final_vault_value))
 }
 
+// Largest part of a pending deposit that fits the caps. Denom caps trim each coin to
+// the room left in that denom, then USD caps scale the whole deposit down.
+fn fit_deposit_to_caps(
+    storage: &dyn cosmwasm_std::Storage,
+    caps: &DepositCaps,
+    coins: &[Coin],
+    value_usd: Decimal256,
+    vault_value: Decimal256,
+    position_value: Decimal256,
+) -> Result<Vec<Coin>, ContractError> {
+    let mut accepted = coins.to_vec();
+    let mut accepted_value = value_usd;
+
+    for coin in &mut accepted {
+        let Some(cap) = caps.denom_caps.iter().find(|cap| cap.denom == coin.denom) else {
+            continue;
+        };
+        let held = VAULT_ASSETS
+            .may_load(storage, coin.denom.clone())?
+            .unwrap_or_default();
+        coin.amount = coin.amount.min(cap.amount.saturating_sub(held));
+    }
+    if accepted != coins {
+        accepted_value = priced_value(storage, &accepted)?;
+    }
+
+    let room = [
+        caps.max_tvl_usd.map(|cap| cap.saturating_sub(vault_value)),
+        caps.max_position_usd
+            .map(|cap| cap.saturating_sub(position_value)),
+    ]
+    .into_iter()
+    .flatten()
+    .min();
+    if let Some(room) = room {
+        if accepted_value > room {
+            let ratio = room.checked_div(accepted_value)?;
+            for coin in &mut accepted {
+                coin.amount = coin.amount.mul_floor(ratio);
+            }
+        }
+    }
+
+    Ok(accepted)
+}
+
 pub fn handle_signed_envelope(
     deps: DepsMut,
     env: Env,
     envelope: WavsEnvelope,
     signature_data: WavsSignatureData,
 ) -> Result<Response, ContractError> {
+    if PAUSE_STATE.load(deps.storage)?.emergency_mode {
+        return Err(ContractError::EmergencyMode {});
+    }
+
     let contract_addr = state::SERVICE_MANAGER.load(deps.storage)?;
 
     deps.querier
@@ -606,11 +2566,15 @@ pub fn handle_signed_envelope(
         )?
         .into_std()?;
 
+    let payload = state::decode_envelope(envelope)?;
+    validate_envelope_timestamp(deps.storage, &env, payload.timestamp)?;
+    state::save_envelope(deps.storage, &payload, signature_data)?;
+
     let Payload {
         prices,
         swap_routes,
         ..
-    } = state::save_envelope(deps.storage, envelope, signature_data)?;
+    } = payload;
 
     let msg = CosmosMsg::Wasm(WasmMsg::Execute {
         contract_addr: env.contract.address.to_string(),
@@ -624,12 +2588,48 @@ pub fn handle_signed_envelope(
     Ok(Response::new().add_message(msg))
 }
 
+// Reject replayed or delayed envelopes so old prices and swap routes cannot be re-applied,
+// and envelopes from the future, which would block every envelope until that time
+pub fn validate_envelope_timestamp(
+    storage: &dyn cosmwasm_std::Storage,
+    env: &Env,
+    timestamp: Timestamp,
+) -> Result<(), ContractError> {
+    if let Some(last_accepted) = state::LAST_ENVELOPE_TIMESTAMP.may_load(storage)? {
+        if timestamp <= last_accepted {
+            return Err(ContractError::StaleEnvelope {
+                timestamp,
+                last_accepted,
+            });
+        }
+    }
+
+    let max_skew_seconds = state::MAX_CLOCK_SKEW_SECONDS.load(storage)?;
+    if timestamp > env.block.time.plus_seconds(max_skew_seconds) {
+        return Err(ContractError::EnvelopeFromFuture {
+            timestamp,
+            max_skew_seconds,
+        });
+    }
+
+    let max_age_seconds = state::MAX_PAYLOAD_AGE_SECONDS.load(storage)?;
+    if timestamp.plus_seconds(max_age_seconds) < env.block.time {
+        return Err(ContractError::EnvelopeTooOld {
+            timestamp,
+            max_age_seconds,
+        });
+    }
+
+    Ok(())
+}
+
 // Helper struct to track processed deposit information for events
 struct ProcessedDepositInfo {
     deposit_id: u64,
     user: String,
     value_usd: Decimal256,
     shares_issued: Uint256,
+    refund: Vec<Coin>, // Part of the deposit over the caps
 }
 
 pub fn manual_trigger(
@@ -639,6 +2639,10 @@ pub fn manual_trigger(
 ) -> Result<Response, ContractError> {
     assert_owner(deps.storage, &info.sender)?;
 
+    if PAUSE_STATE.load(deps.storage)?.emergency_mode {
+        return Err(ContractError::EmergencyMode {});
+    }
+
     Ok(Response::new().add_event(
         cosmwasm_std::Event::new("manual_trigger")
             .add_attribute("trigger_time", env.block.time.nanos().to_string()),
@@ -660,3 +2664,296 @@ pub fn update_service_manager(
         .add_attribute("method", "update_service_manager")
         .add_attribute("new_service_manager", new_service_manager.to_string()))
 }
+
+pub fn update_fee_config(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    management_fee_rate: Option<Decimal256>,
+    performance_fee_rate: Option<Decimal256>,
+    fee_recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    // Settle fees owed under the current configuration before changing it. The stored
+    // vault value is only as current as the last price update, so that has to be recent.
+    if !TOTAL_SHARES.load(deps.storage)?.is_zero() {
+        let max_age_seconds = state::MAX_PAYLOAD_AGE_SECONDS.load(deps.storage)?;
+        let fresh = state::PRICES_UPDATED_AT
+            .may_load(deps.storage)?
+            .is_some_and(|updated_at| {
+                env.block
+                    .time
+                    .seconds()
+                    .saturating_sub(updated_at.seconds())
+                    <= max_age_seconds
+            });
+        if !fresh {
+            return Err(ContractError::FeePricesStale { max_age_seconds });
+        }
+    }
+    let vault_value = VAULT_VALUE_DEPOSITED.load(deps.storage)?;
+    let mut share_mints = Vec::new();
+    let fee_event = accrue_fees(deps.storage, &env, vault_value, &mut share_mints)?;
+
+    let mut fee_config = FEE_CONFIG.load(deps.storage)?;
+
+    if let Some(rate) = management_fee_rate {
+        if rate > MAX_MANAGEMENT_FEE_RATE {
+            return Err(ContractError::FeeRateTooHigh {
+                rate,
+                max: MAX_MANAGEMENT_FEE_RATE,
+            });
+        }
+        fee_config.management_fee_rate = rate;
+    }
+
+    if let Some(rate) = performance_fee_rate {
+        if rate > MAX_PERFORMANCE_FEE_RATE {
+            return Err(ContractError::FeeRateTooHigh {
+                rate,
+                max: MAX_PERFORMANCE_FEE_RATE,
+            });
+        }
+        fee_config.performance_fee_rate = rate;
+    }
+
+    if let Some(recipient) = fee_recipient {
+        fee_config.fee_recipient = Some(deps.api.addr_validate(&recipient)?);
+    }
+
+    let fees_enabled =
+        !fee_config.management_fee_rate.is_zero() || !fee_config.performance_fee_rate.is_zero();
+    if fees_enabled && fee_config.fee_recipient.is_none() {
+        return Err(ContractError::FeeRecipientRequired {});
+    }
+
+    FEE_CONFIG.save(deps.storage, &fee_config)?;
+
+    Ok(Response::new()
+        .add_messages(share_mints)
+        .add_events(fee_event)
+        .add_event(
+            Event::new("fee_config_updated")
+                .add_attribute(
+                    "management_fee_rate",
+                    fee_config.management_fee_rate.to_string(),
+                )
+                .add_attribute(
+                    "performance_fee_rate",
+                    fee_config.performance_fee_rate.to_string(),
+                )
+                .add_attribute(
+                    "fee_recipient",
+                    fee_config
+                        .fee_recipient
+                        .map(|addr| addr.to_string())
+                        .unwrap_or_default(),
+                ),
+        )
+        .add_attribute("method", "update_fee_config")
+        .add_attribute("updated_by", info.sender))
+}
+
+pub fn update_max_payload_age(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    max_age_seconds: u64,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    if max_age_seconds == 0 {
+        return Err(ContractError::ZeroMaxPayloadAge {});
+    }
+
+    state::MAX_PAYLOAD_AGE_SECONDS.save(deps.storage, &max_age_seconds)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "update_max_payload_age")
+        .add_attribute("max_age_seconds", max_age_seconds.to_string()))
+}
+
+pub fn update_max_clock_skew(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    max_skew_seconds: u64,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    state::MAX_CLOCK_SKEW_SECONDS.save(deps.storage, &max_skew_seconds)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "update_max_clock_skew")
+        .add_attribute("max_skew_seconds", max_skew_seconds.to_string()))
+}
+
+pub fn update_price_guards(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    to_set: Option<Vec<PriceGuard>>,
+    to_remove: Option<Vec<String>>,
+    action: Option<PriceGuardAction>,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    let mut set_count: u32 = 0;
+    let mut removed_count: u32 = 0;
+
+    if let Some(guards) = to_set {
+        for guard in guards {
+            if guard.max_deviation_bps == 0 {
+                return Err(ContractError::InvalidPriceGuard { denom: guard.denom });
+            }
+            PRICE_GUARDS.save(deps.storage, guard.denom, &guard.max_deviation_bps)?;
+            set_count = set_count.saturating_add(1);
+        }
+    }
+
+    if let Some(denoms) = to_remove {
+        for denom in denoms {
+            if PRICE_GUARDS.has(deps.storage, denom.clone()) {
+                PRICE_GUARDS.remove(deps.storage, denom);
+                removed_count = removed_count.saturating_add(1);
+            }
+        }
+    }
+
+    if let Some(action) = action {
+        PRICE_GUARD_ACTION.save(deps.storage, &action)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "update_price_guards")
+        .add_attribute("updated_by", info.sender)
+        .add_attribute("guards_set", set_count.to_string())
+        .add_attribute("guards_removed", removed_count.to_string()))
+}
+
+pub fn update_guardian(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    guardian: Option<String>,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    let guardian = match guardian {
+        Some(addr) => {
+            let guardian = deps.api.addr_validate(&addr)?;
+            GUARDIAN.save(deps.storage, &guardian)?;
+            guardian.to_string()
+        }
+        None => {
+            GUARDIAN.remove(deps.storage);
+            "none".to_string()
+        }
+    };
+
+    Ok(Response::new()
+        .add_attribute("method", "update_guardian")
+        .add_attribute("guardian", guardian))
+}
+
+// The owner may change any flag; the guardian may only pause or enter emergency mode
+pub fn update_pause_state(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    deposits: Option<bool>,
+    price_processing: Option<bool>,
+    trading: Option<bool>,
+    emergency_mode: Option<bool>,
+) -> Result<Response, ContractError> {
+    let is_owner = assert_owner(deps.storage, &info.sender).is_ok();
+    if !is_owner {
+        let guardian = GUARDIAN.may_load(deps.storage)?;
+        if guardian.as_ref() != Some(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        if [deposits, price_processing, trading, emergency_mode].contains(&Some(false)) {
+            return Err(ContractError::GuardianCannotUnpause {});
+        }
+    }
+
+    let mut pause_state = PAUSE_STATE.load(deps.storage)?;
+    if let Some(paused) = deposits {
+        pause_state.deposits_paused = paused;
+    }
+    if let Some(paused) = price_processing {
+        pause_state.price_processing_paused = paused;
+    }
+    if let Some(paused) = trading {
+        pause_state.trading_paused = paused;
+    }
+    if let Some(enabled) = emergency_mode {
+        pause_state.emergency_mode = enabled;
+    }
+    PAUSE_STATE.save(deps.storage, &pause_state)?;
+
+    Ok(Response::new().add_event(
+        Event::new("pause_state_updated")
+            .add_attribute("updated_by", info.sender)
+            .add_attribute("deposits_paused", pause_state.deposits_paused.to_string())
+            .add_attribute(
+                "price_processing_paused",
+                pause_state.price_processing_paused.to_string(),
+            )
+            .add_attribute("trading_paused", pause_state.trading_paused.to_string())
+            .add_attribute("emergency_mode", pause_state.emergency_mode.to_string()),
+    ))
+}
+
+pub fn update_max_slippage(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    max_slippage_bps: u64,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    if max_slippage_bps > BPS_DENOMINATOR {
+        return Err(ContractError::InvalidMaxSlippage { max_slippage_bps });
+    }
+    MAX_SLIPPAGE_BPS.save(deps.storage, &max_slippage_bps)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "update_max_slippage")
+        .add_attribute("max_slippage_bps", max_slippage_bps.to_string()))
+}
+
+pub fn update_trading_limits(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    limits: TradingLimits,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.storage, &info.sender)?;
+
+    if let Some(fraction) = limits.max_trade_fraction {
+        if fraction > Decimal256::one() {
+            return Err(ContractError::InvalidMaxTradeFraction { fraction });
+        }
+    }
+    TRADING_LIMITS.save(deps.storage, &limits)?;
+
+    let display = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+    Ok(Response::new().add_event(
+        Event::new("trading_limits_updated")
+            .add_attribute(
+                "max_trade_fraction",
+                display(limits.max_trade_fraction.map(|v| v.to_string())),
+            )
+            .add_attribute(
+                "max_routes",
+                display(limits.max_routes.map(|v| v.to_string())),
+            )
+            .add_attribute(
+                "max_daily_turnover_usd",
+                display(limits.max_daily_turnover_usd.map(|v| v.to_string())),
+            ),
+    ))
+}
@@ -1,8 +1,8 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdError, StdResult,
-    Uint256,
+    from_json, to_json_binary, Binary, Coin, Deps, DepsMut, Env, MessageInfo, Reply, Response,
+    StdError, StdResult, SubMsgResult, Uint256,
 };
 use cw2::set_contract_version;
 use wavs_types::contracts::cosmwasm::service_handler::{
@@ -12,8 +12,8 @@ use wavs_types::contracts::cosmwasm::service_handler::{
 use crate::error::ContractError;
 use crate::execute::calculate_vault_usd_value;
 use crate::state::{
-    DEPOSIT_ID_COUNTER, SKIP_ENTRY_POINT, TOTAL_SHARES, TRADE_TRACKER, VAULT_ASSETS,
-    VAULT_VALUE_DEPOSITED, WHITELISTED_DENOMS,
+    FeeState, DEPOSIT_ID_COUNTER, FEE_CONFIG, FEE_STATE, PENDING_TRADES, SKIP_ENTRY_POINT,
+    TOTAL_SHARES, TRADE_ID_COUNTER, VAULT_ASSETS, VAULT_VALUE_DEPOSITED, WHITELISTED_DENOMS,
 };
 
 mod error;
@@ -22,9 +22,10 @@ pub mod msg;
 mod query;
 mod skip_entry;
 mod state;
+mod token_factory;
 
 pub use msg::*;
-pub use skip_entry::{SwapOperation, SwapRoute};
+pub use skip_entry::{SwapOperation, SwapRoute, WithdrawRoute};
 
 #[cfg(test)]
 mod tests;
@@ -33,11 +34,24 @@ const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 const REPLY_TRACKER_ID: u64 = 1u64;
+const WITHDRAW_SWAP_REPLY_ID: u64 = 2u64;
+
+// Swap routes whose minimum output is further than this below the price-implied output are skipped
+const DEFAULT_MAX_SLIPPAGE_BPS: u64 = 300;
+
+// Envelopes older than this are rejected unless the owner configures otherwise
+const DEFAULT_MAX_PAYLOAD_AGE_SECONDS: u64 = 3600;
+
+// Envelopes stamped further ahead of the block time than this are rejected
+const DEFAULT_MAX_CLOCK_SKEW_SECONDS: u64 = 60;
+
+// Strategy changes wait this long before they can be executed
+const DEFAULT_STRATEGY_TIMELOCK_SECONDS: u64 = 86_400;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
@@ -54,20 +68,57 @@ pub fn instantiate(
     VAULT_VALUE_DEPOSITED.save(deps.storage, &cosmwasm_std::Decimal256::zero())?;
 
     // Initialize whitelisted denoms
-    for denom in msg.initial_whitelisted_denoms {
-        WHITELISTED_DENOMS.save(deps.storage, denom.clone(), &())?;
+    for entry in msg.initial_whitelist {
+        execute::validate_whitelist_entry(&entry)?;
+        WHITELISTED_DENOMS.save(deps.storage, entry.denom.clone(), &entry)?;
+    }
+
+    // The initial strategy needs no timelock, nobody has deposited yet
+    if let Some(allocation) = msg.target_allocation {
+        execute::validate_target_allocation(deps.storage, &allocation)?;
+        state::TARGET_ALLOCATION.save(deps.storage, &allocation)?;
     }
+    state::STRATEGY_TIMELOCK_SECONDS.save(deps.storage, &DEFAULT_STRATEGY_TIMELOCK_SECONDS)?;
 
     // Initialize deposit_id counter to 0
     DEPOSIT_ID_COUNTER.save(deps.storage, &0u64)?;
+    TRADE_ID_COUNTER.save(deps.storage, &0u64)?;
+    state::WITHDRAW_TO_ID_COUNTER.save(deps.storage, &0u64)?;
+    state::WITHDRAW_REQUEST_ID_COUNTER.save(deps.storage, &0u64)?;
+    state::WITHDRAWAL_QUEUE_ENABLED.save(deps.storage, &false)?;
+    state::MAX_SLIPPAGE_BPS.save(deps.storage, &DEFAULT_MAX_SLIPPAGE_BPS)?;
+    state::TRADING_LIMITS.save(deps.storage, &TradingLimits::default())?;
+    state::DEPOSIT_CAPS.save(deps.storage, &DepositCaps::default())?;
+    state::DEPOSITOR_ALLOWLIST_ENABLED.save(deps.storage, &false)?;
+    state::SWAP_ALLOWLIST_ENABLED.save(deps.storage, &false)?;
+    state::SURPLUS_POLICY.save(deps.storage, &SurplusPolicy::default())?;
 
     let service_manager = deps.api.addr_validate(&msg.service_manager)?;
     state::SERVICE_MANAGER.save(deps.storage, &service_manager)?;
+    state::MAX_PAYLOAD_AGE_SECONDS.save(deps.storage, &DEFAULT_MAX_PAYLOAD_AGE_SECONDS)?;
+    state::MAX_CLOCK_SKEW_SECONDS.save(deps.storage, &DEFAULT_MAX_CLOCK_SKEW_SECONDS)?;
+    state::PRICE_GUARD_ACTION.save(deps.storage, &PriceGuardAction::default())?;
+    state::PAUSE_STATE.save(deps.storage, &PauseState::default())?;
 
     let skip_entry_point = deps.api.addr_validate(&msg.skip_entry_point)?;
     SKIP_ENTRY_POINT.save(deps.storage, &skip_entry_point)?;
 
+    // Fees start disabled until the owner configures them
+    initialize_fees(deps.storage, &env)?;
+
+    // Tokenized vaults create their share denom up front
+    let mut msgs = Vec::new();
+    if let Some(subdenom) = msg.share_subdenom {
+        let contract = env.contract.address.as_str();
+        state::SHARE_DENOM.save(
+            deps.storage,
+            &token_factory::full_denom(contract, &subdenom),
+        )?;
+        msgs.push(token_factory::create_denom_msg(contract, &subdenom));
+    }
+
     Ok(Response::new()
+        .add_messages(msgs)
         .add_attribute("method", "instantiate")
         .add_attribute("skip_entry_point", skip_entry_point)
         .add_attributes(ownership.into_attributes()))
@@ -84,6 +135,12 @@ pub fn execute(
         ExecuteMsg::Vault(msg) => match msg {
             VaultExecuteMsg::Deposit {} => execute::deposit(deps, env, info),
             VaultExecuteMsg::Withdraw { shares } => execute::withdraw(deps, env, info, shares),
+            VaultExecuteMsg::WithdrawTo {
+                shares,
+                denom,
+                min_out,
+                routes,
+            } => execute::withdraw_to(deps, env, info, shares, denom, min_out, routes),
             VaultExecuteMsg::UpdateWhitelist { to_add, to_remove } => {
                 execute::update_whitelist(deps, env, info, to_add, to_remove)
             }
@@ -101,6 +158,111 @@ pub fn execute(
                 execute::update_service_manager(deps, env, info, addr)
             }
             VaultExecuteMsg::ManualTrigger {} => execute::manual_trigger(deps, env, info),
+            VaultExecuteMsg::UpdateMaxPayloadAge { max_age_seconds } => {
+                execute::update_max_payload_age(deps, env, info, max_age_seconds)
+            }
+            VaultExecuteMsg::UpdateMaxClockSkew { max_skew_seconds } => {
+                execute::update_max_clock_skew(deps, env, info, max_skew_seconds)
+            }
+            VaultExecuteMsg::UpdatePriceGuards {
+                to_set,
+                to_remove,
+                action,
+            } => execute::update_price_guards(deps, env, info, to_set, to_remove, action),
+            VaultExecuteMsg::UpdateMaxSlippage { max_slippage_bps } => {
+                execute::update_max_slippage(deps, env, info, max_slippage_bps)
+            }
+            VaultExecuteMsg::UpdateTradingLimits { limits } => {
+                execute::update_trading_limits(deps, env, info, limits)
+            }
+            VaultExecuteMsg::UpdateDepositCaps { caps } => {
+                execute::update_deposit_caps(deps, env, info, caps)
+            }
+            VaultExecuteMsg::Reconcile {} => execute::reconcile(deps, env, info),
+            VaultExecuteMsg::ProposeStrategyChange {
+                target_allocation,
+                timelock_seconds,
+            } => execute::propose_strategy_change(
+                deps,
+                env,
+                info,
+                target_allocation,
+                timelock_seconds,
+            ),
+            VaultExecuteMsg::ExecuteStrategyChange {} => {
+                execute::execute_strategy_change(deps, env, info)
+            }
+            VaultExecuteMsg::CancelStrategyChange {} => {
+                execute::cancel_strategy_change(deps, env, info)
+            }
+            VaultExecuteMsg::UpdateSurplusPolicy { policy } => {
+                execute::update_surplus_policy(deps, env, info, policy)
+            }
+            VaultExecuteMsg::UpdateDepositorAllowlist {
+                enabled,
+                to_add,
+                to_remove,
+            } => execute::update_depositor_allowlist(deps, env, info, enabled, to_add, to_remove),
+            VaultExecuteMsg::UpdateSwapAllowlist {
+                enabled,
+                venues_to_add,
+                venues_to_remove,
+                pools_to_add,
+                pools_to_remove,
+            } => execute::update_swap_allowlist(
+                deps,
+                env,
+                info,
+                enabled,
+                venues_to_add,
+                venues_to_remove,
+                pools_to_add,
+                pools_to_remove,
+            ),
+            VaultExecuteMsg::CancelDeposit { deposit_id } => {
+                execute::cancel_deposit(deps, env, info, deposit_id)
+            }
+            VaultExecuteMsg::UpdateDepositExpiry { expiry_seconds } => {
+                execute::update_deposit_expiry(deps, env, info, expiry_seconds)
+            }
+            VaultExecuteMsg::RequestWithdraw { shares } => {
+                execute::request_withdraw(deps, env, info, shares)
+            }
+            VaultExecuteMsg::CancelWithdraw { withdraw_id } => {
+                execute::cancel_withdraw(deps, env, info, withdraw_id)
+            }
+            VaultExecuteMsg::UpdateWithdrawalQueue { enabled } => {
+                execute::update_withdrawal_queue(deps, env, info, enabled)
+            }
+            VaultExecuteMsg::UpdateGuardian { guardian } => {
+                execute::update_guardian(deps, env, info, guardian)
+            }
+            VaultExecuteMsg::UpdatePauseState {
+                deposits,
+                price_processing,
+                trading,
+                emergency_mode,
+            } => execute::update_pause_state(
+                deps,
+                env,
+                info,
+                deposits,
+                price_processing,
+                trading,
+                emergency_mode,
+            ),
+            VaultExecuteMsg::UpdateFeeConfig {
+                management_fee_rate,
+                performance_fee_rate,
+                fee_recipient,
+            } => execute::update_fee_config(
+                deps,
+                env,
+                info,
+                management_fee_rate,
+                performance_fee_rate,
+                fee_recipient,
+            ),
         },
         ExecuteMsg::Wavs(msg) => match msg {
             ServiceHandlerExecuteMessages::WavsHandleSignedEnvelope {
@@ -112,115 +274,351 @@ pub fn execute(
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
     match msg.id {
         REPLY_TRACKER_ID => {
-            let msg_response = msg.result.into_result().map_err(StdError::msg)?;
-
-            if let Some(trade_info) = TRADE_TRACKER.pop_front(deps.storage)? {
-                // Extract swap output information from reply attributes
-                let mut swap_out_amount: Option<Uint256> = None;
-                let mut swap_out_denom: Option<String> = None;
-
-                for event in msg_response.events {
-                    for attribute in event.attributes {
-                        match attribute.key.as_str() {
-                            "post_swap_action_amount_out" => {
-                                swap_out_amount = Some(
-                                    attribute
-                                        .value
-                                        .parse::<u128>()
-                                        .map_err(|_| {
-                                            ContractError::Std(StdError::msg(format!(
-                                                "Failed to parse amount: {}",
-                                                attribute.value
-                                            )))
-                                        })?
-                                        .into(),
-                                );
-                            }
-                            "post_swap_action_denom_out" => {
-                                swap_out_denom = Some(attribute.value);
-                            }
-                            _ => {}
-                        }
-                    }
-                }
+            let trade_id: u64 = from_json(&msg.payload)?;
+            let Some(trade_info) = PENDING_TRADES.may_load(deps.storage, trade_id)? else {
+                return Ok(Response::new());
+            };
+            PENDING_TRADES.remove(deps.storage, trade_id);
+
+            let (mut response, status, out_coin) = match msg.result {
+                SubMsgResult::Ok(msg_response) => {
+                    // Extract swap output information from reply attributes
+                    let (swap_out_denom, swap_out_amount) = parse_swap_output(msg_response.events)?;
+
+                    // Update vault assets using only the swap deltas
+                    // Incoming asset: add only the amount received from the swap (from reply attributes)
+                    VAULT_ASSETS.update::<_, ContractError>(
+                        deps.storage,
+                        swap_out_denom.clone(),
+                        |current_balance| -> Result<_, ContractError> {
+                            let balance = current_balance.unwrap_or_default();
+                            let new_balance = balance.checked_add(swap_out_amount)?;
+                            Ok(new_balance)
+                        },
+                    )?;
 
-                // Update vault assets using only the swap deltas
-                // Incoming asset: add only the amount received from the swap (from reply attributes)
-                if let (Some(denom), Some(amount)) = (swap_out_denom.clone(), swap_out_amount) {
+                    // Outgoing asset: subtract the amount we sent in the swap
                     VAULT_ASSETS.update::<_, ContractError>(
                         deps.storage,
-                        denom,
+                        trade_info.in_coin.denom.clone(),
                         |current_balance| -> Result<_, ContractError> {
                             let balance = current_balance.unwrap_or_default();
-                            let new_balance = balance.checked_add(amount)?;
+                            let new_balance = balance.checked_sub(trade_info.in_coin.amount)?;
                             Ok(new_balance)
                         },
                     )?;
-                } else {
-                    return Err(ContractError::Std(StdError::msg(
-                        "Could not get post swap action attributes",
-                    )));
+
+                    // Only swaps that went through count against the daily turnover
+                    if !trade_info.value_usd.is_zero() {
+                        execute::record_turnover(
+                            deps.storage,
+                            env.block.time,
+                            trade_info.value_usd,
+                        )?;
+                    }
+
+                    // Add trade completion event with actual swap amounts
+                    let response = Response::new().add_event(
+                        cosmwasm_std::Event::new("trade_completed")
+                            .add_attribute("trade_id", trade_id.to_string())
+                            .add_attribute("in_denom", &trade_info.in_coin.denom)
+                            .add_attribute("in_amount", trade_info.in_coin.amount.to_string())
+                            .add_attribute("out_denom", &swap_out_denom)
+                            .add_attribute("out_amount", swap_out_amount.to_string()),
+                    );
+                    let out_coin = Coin {
+                        denom: swap_out_denom,
+                        amount: swap_out_amount,
+                    };
+                    (response, TradeStatus::Completed, Some(out_coin))
+                }
+                // The swap's state changes were reverted, so the offered coins never left the vault
+                SubMsgResult::Err(error) => {
+                    let response = Response::new().add_event(
+                        cosmwasm_std::Event::new("trade_failed")
+                            .add_attribute("trade_id", trade_id.to_string())
+                            .add_attribute("in_denom", &trade_info.in_coin.denom)
+                            .add_attribute("in_amount", trade_info.in_coin.amount.to_string())
+                            .add_attribute("out_denom", &trade_info.out_denom)
+                            .add_attribute("error", &error),
+                    );
+                    (response, TradeStatus::Failed { error }, None)
                 }
+            };
 
-                // Outgoing asset: subtract the amount we sent in the swap
-                VAULT_ASSETS.update::<_, ContractError>(
-                    deps.storage,
-                    trade_info.in_coin.denom.clone(),
-                    |current_balance| -> Result<_, ContractError> {
-                        let balance = current_balance.unwrap_or_default();
-                        let new_balance = balance.checked_sub(trade_info.in_coin.amount)?;
-                        Ok(new_balance)
-                    },
-                )?;
+            let price_of = |denom: &str| -> StdResult<Option<cosmwasm_std::Decimal256>> {
+                Ok(state::PRICES
+                    .may_load(deps.storage, denom.to_string())?
+                    .map(|price| price.price_usd))
+            };
+            let record = TradeRecord {
+                id: trade_id,
+                status,
+                in_price_usd: price_of(&trade_info.in_coin.denom)?,
+                out_price_usd: price_of(&trade_info.out_denom)?,
+                in_coin: trade_info.in_coin,
+                out_denom: trade_info.out_denom,
+                out_coin,
+                swap_venue: trade_info.swap_venue,
+                executed_at: env.block.time,
+                envelope_timestamp: trade_info.envelope_timestamp,
+            };
+            state::TRADE_HISTORY.save(deps.storage, trade_id, &record)?;
+
+            // Once all operations are completed, then calculate vault value again
+            if PENDING_TRADES.is_empty(deps.storage) {
+                let updated_vault_value = calculate_vault_usd_value(deps.storage)?;
+                VAULT_VALUE_DEPOSITED.save(deps.storage, &updated_vault_value)?;
+                execute::record_nav_snapshot(deps.storage, env.block.time)?;
 
-                // Add trade completion event with actual swap amounts
-                let mut response = Response::new().add_event(
-                    cosmwasm_std::Event::new("trade_completed")
-                        .add_attribute("in_denom", &trade_info.in_coin.denom)
-                        .add_attribute("in_amount", trade_info.in_coin.amount.to_string())
-                        .add_attribute(
-                            "out_denom",
-                            swap_out_denom.unwrap_or_else(|| trade_info.out_denom.clone()),
-                        )
-                        .add_attribute(
-                            "out_amount",
-                            swap_out_amount
-                                .map(|a| a.to_string())
-                                .unwrap_or_else(|| "unknown".to_string()),
-                        ),
+                response = response.add_event(
+                    cosmwasm_std::Event::new("trade_finalized")
+                        .add_attribute("vault_value_usd", updated_vault_value.to_string()),
                 );
+            }
+
+            Ok(response)
+        }
+        WITHDRAW_SWAP_REPLY_ID => execute::settle_withdraw_swap(deps, msg),
+        _ => Err(ContractError::UnknownReplyId { id: msg.id }),
+    }
+}
 
-                // Once all operations are completed, then calculate vault value again
-                if TRADE_TRACKER.is_empty(deps.storage)? {
-                    let updated_vault_value = calculate_vault_usd_value(deps.storage)?;
-                    VAULT_VALUE_DEPOSITED.save(deps.storage, &updated_vault_value)?;
+// Reads the amount and denom the Skip entry point transferred back to the vault
+pub(crate) fn parse_swap_output(
+    events: Vec<cosmwasm_std::Event>,
+) -> Result<(String, Uint256), ContractError> {
+    let mut swap_out_amount: Option<Uint256> = None;
+    let mut swap_out_denom: Option<String> = None;
 
-                    response = response.add_event(
-                        cosmwasm_std::Event::new("trade_finalized")
-                            .add_attribute("vault_value_usd", updated_vault_value.to_string()),
+    for event in events {
+        for attribute in event.attributes {
+            match attribute.key.as_str() {
+                "post_swap_action_amount_out" => {
+                    swap_out_amount = Some(
+                        attribute
+                            .value
+                            .parse::<u128>()
+                            .map_err(|_| {
+                                ContractError::Std(StdError::msg(format!(
+                                    "Failed to parse amount: {}",
+                                    attribute.value
+                                )))
+                            })?
+                            .into(),
                     );
                 }
-
-                Ok(response)
-            } else {
-                Ok(Response::new())
+                "post_swap_action_denom_out" => {
+                    swap_out_denom = Some(attribute.value);
+                }
+                _ => {}
             }
         }
-        _ => Err(ContractError::UnknownReplyId { id: msg.id }),
+    }
+
+    match (swap_out_denom, swap_out_amount) {
+        (Some(denom), Some(amount)) => Ok((denom, amount)),
+        _ => Err(ContractError::Std(StdError::msg(
+            "Could not get post swap action attributes",
+        ))),
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    Ok(Response::default())
+
+    // Vaults deployed before fees existed start with fees disabled
+    if !FEE_CONFIG.exists(deps.storage) {
+        initialize_fees(deps.storage, &env)?;
+    }
+
+    // Trades are tracked by id instead of reply order
+    if !TRADE_ID_COUNTER.exists(deps.storage) {
+        TRADE_ID_COUNTER.save(deps.storage, &0u64)?;
+    }
+
+    if !state::WITHDRAW_TO_ID_COUNTER.exists(deps.storage) {
+        state::WITHDRAW_TO_ID_COUNTER.save(deps.storage, &0u64)?;
+    }
+
+    if !state::WITHDRAW_REQUEST_ID_COUNTER.exists(deps.storage) {
+        state::WITHDRAW_REQUEST_ID_COUNTER.save(deps.storage, &0u64)?;
+        state::WITHDRAWAL_QUEUE_ENABLED.save(deps.storage, &false)?;
+    }
+
+    if !state::MAX_SLIPPAGE_BPS.exists(deps.storage) {
+        state::MAX_SLIPPAGE_BPS.save(deps.storage, &DEFAULT_MAX_SLIPPAGE_BPS)?;
+    }
+
+    if !state::TRADING_LIMITS.exists(deps.storage) {
+        state::TRADING_LIMITS.save(deps.storage, &TradingLimits::default())?;
+    }
+
+    if !state::DEPOSITOR_ALLOWLIST_ENABLED.exists(deps.storage) {
+        state::DEPOSITOR_ALLOWLIST_ENABLED.save(deps.storage, &false)?;
+    }
+
+    if !state::SWAP_ALLOWLIST_ENABLED.exists(deps.storage) {
+        state::SWAP_ALLOWLIST_ENABLED.save(deps.storage, &false)?;
+    }
+
+    if !state::SURPLUS_POLICY.exists(deps.storage) {
+        state::SURPLUS_POLICY.save(deps.storage, &SurplusPolicy::default())?;
+    }
+
+    // Existing vaults start without an on-chain strategy
+    if !state::STRATEGY_TIMELOCK_SECONDS.exists(deps.storage) {
+        state::STRATEGY_TIMELOCK_SECONDS.save(deps.storage, &DEFAULT_STRATEGY_TIMELOCK_SECONDS)?;
+    }
+
+    // Caps start unlimited; per-depositor pending totals are rebuilt from open deposits
+    if !state::DEPOSIT_CAPS.exists(deps.storage) {
+        state::DEPOSIT_CAPS.save(deps.storage, &DepositCaps::default())?;
+        let pending = state::DEPOSIT_REQUESTS
+            .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+            .filter(|item| {
+                item.as_ref()
+                    .map_or(true, |(_, deposit)| deposit.state == DepositState::Pending)
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+        for (_, deposit) in pending {
+            for coin in deposit.coins {
+                state::USER_PENDING_ASSETS.update(
+                    deps.storage,
+                    (&deposit.user, &coin.denom),
+                    |balance| -> StdResult<_> {
+                        Ok(balance.unwrap_or_default().checked_add(coin.amount)?)
+                    },
+                )?;
+            }
+        }
+    }
+
+    if !state::PRICE_GUARD_ACTION.exists(deps.storage) {
+        state::PRICE_GUARD_ACTION.save(deps.storage, &PriceGuardAction::default())?;
+    }
+
+    // Bare whitelisted denoms become registry entries; decimals follow the last stored
+    // price so the running operator keeps passing the decimals check
+    let legacy_denoms = state::LEGACY_WHITELISTED_DENOMS
+        .keys(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for denom in legacy_denoms {
+        let decimals = state::PRICES
+            .may_load(deps.storage, denom.clone())?
+            .map_or(0, |price| price.decimals);
+        let entry = WhitelistEntry {
+            denom: denom.clone(),
+            symbol: denom.clone(),
+            decimals,
+            price_source_ids: vec![],
+            min_deposit: Uint256::zero(),
+            depositable: true,
+            tradable: true,
+        };
+        WHITELISTED_DENOMS.save(deps.storage, denom.clone(), &entry)?;
+        state::LEGACY_WHITELISTED_DENOMS.remove(deps.storage, denom);
+    }
+
+    if !state::PAUSE_STATE.exists(deps.storage) {
+        state::PAUSE_STATE.save(deps.storage, &PauseState::default())?;
+    }
+
+    // Replay protection starts from the newest envelope already stored
+    if !state::MAX_PAYLOAD_AGE_SECONDS.exists(deps.storage) {
+        state::MAX_PAYLOAD_AGE_SECONDS.save(deps.storage, &DEFAULT_MAX_PAYLOAD_AGE_SECONDS)?;
+    }
+    if !state::MAX_CLOCK_SKEW_SECONDS.exists(deps.storage) {
+        state::MAX_CLOCK_SKEW_SECONDS.save(deps.storage, &DEFAULT_MAX_CLOCK_SKEW_SECONDS)?;
+    }
+    if !state::LAST_ENVELOPE_TIMESTAMP.exists(deps.storage) {
+        let newest = state::TRIGGER_MESSAGE
+            .keys(deps.storage, None, None, cosmwasm_std::Order::Descending)
+            .next()
+            .transpose()?;
+        if let Some(nanos) = newest {
+            state::LAST_ENVELOPE_TIMESTAMP.save(
+                deps.storage,
+                &cosmwasm_std::Timestamp::from_nanos(nanos.u64()),
+            )?;
+        }
+    }
+
+    let mut response = Response::default();
+    if let Some(subdenom) = msg.share_subdenom {
+        response = tokenize_shares(deps, &env, &subdenom)?;
+    }
+
+    Ok(response)
+}
+
+// Create the share denom and mint every internal share balance to its holder. Shares
+// locked in queued withdrawals are minted to the vault, which burns them at settlement.
+fn tokenize_shares(deps: DepsMut, env: &Env, subdenom: &str) -> Result<Response, ContractError> {
+    if let Some(denom) = state::SHARE_DENOM.may_load(deps.storage)? {
+        return Err(ContractError::SharesAlreadyTokenized { denom });
+    }
+
+    let contract = env.contract.address.as_str();
+    let share_denom = token_factory::full_denom(contract, subdenom);
+    state::SHARE_DENOM.save(deps.storage, &share_denom)?;
+
+    let balances = state::USER_SHARES
+        .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut msgs = vec![token_factory::create_denom_msg(contract, subdenom)];
+    for (holder, shares) in &balances {
+        state::USER_SHARES.remove(deps.storage, holder.clone());
+        msgs.push(token_factory::mint_msg(
+            contract,
+            &Coin::new(*shares, share_denom.clone()),
+            holder,
+        ));
+    }
+
+    let mut queued_shares = Uint256::zero();
+    for withdraw_id in
+        state::WITHDRAW_QUEUE.keys(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+    {
+        let request = state::WITHDRAW_REQUESTS.load(deps.storage, withdraw_id?)?;
+        queued_shares = queued_shares.checked_add(request.shares)?;
+    }
+    if !queued_shares.is_zero() {
+        msgs.push(token_factory::mint_msg(
+            contract,
+            &Coin::new(queued_shares, share_denom.clone()),
+            contract,
+        ));
+    }
+
+    Ok(Response::new().add_messages(msgs).add_event(
+        cosmwasm_std::Event::new("shares_tokenized")
+            .add_attribute("share_denom", share_denom)
+            .add_attribute("holders", balances.len().to_string())
+            .add_attribute("queued_shares", queued_shares.to_string()),
+    ))
+}
+
+fn initialize_fees(storage: &mut dyn cosmwasm_std::Storage, env: &Env) -> StdResult<()> {
+    FEE_CONFIG.save(storage, &FeeConfig::default())?;
+    FEE_STATE.save(
+        storage,
+        &FeeState {
+            last_accrual: env.block.time,
+            high_water_mark: cosmwasm_std::Decimal256::zero(),
+            total_management_fee_shares: Uint256::zero(),
+            total_performance_fee_shares: Uint256::zero(),
+        },
+    )
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Vault(msg) => match msg {
             VaultQueryMsg::GetTotalShares {} => to_json_binary(&query::total_shares(deps)?),
@@ -228,12 +626,26 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             VaultQueryMsg::GetWhitelistedDenoms {} => {
                 to_json_binary(&query::whitelisted_denoms(deps)?)
             }
+            VaultQueryMsg::GetAssetRegistry { start_after, limit } => {
+                to_json_binary(&query::asset_registry(deps, start_after, limit)?)
+            }
             VaultQueryMsg::GetDepositRequest { deposit_id } => {
                 to_json_binary(&query::deposit_request(deps, deposit_id)?)
             }
             VaultQueryMsg::ListDepositRequests { start_after, limit } => {
                 to_json_binary(&query::deposit_requests(deps, start_after, limit)?)
             }
+            VaultQueryMsg::GetWithdrawRequest { withdraw_id } => {
+                to_json_binary(&query::withdraw_request(deps, withdraw_id)?)
+            }
+            VaultQueryMsg::ListWithdrawRequests {
+                user,
+                start_after,
+                limit,
+            } => to_json_binary(&query::withdraw_requests(deps, user, start_after, limit)?),
+            VaultQueryMsg::GetWithdrawalQueueEnabled {} => {
+                to_json_binary(&query::withdrawal_queue_enabled(deps)?)
+            }
             VaultQueryMsg::GetVaultAssets {} => to_json_binary(&query::vault_assets(deps)?),
             VaultQueryMsg::GetVaultAssetBalance { denom } => {
                 to_json_binary(&query::vault_asset_balance(deps, denom)?)
@@ -253,6 +665,42 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             VaultQueryMsg::GetUserShares { user } => {
                 to_json_binary(&query::user_shares(deps, user)?)
             }
+            VaultQueryMsg::GetFeeConfig {} => to_json_binary(&query::fee_config(deps)?),
+            VaultQueryMsg::GetAccruedFees {} => to_json_binary(&query::accrued_fees(deps, env)?),
+            VaultQueryMsg::GetEnvelopeStatus {} => to_json_binary(&query::envelope_status(deps)?),
+            VaultQueryMsg::GetPriceGuards {} => to_json_binary(&query::price_guards(deps)?),
+            VaultQueryMsg::GetTrade { id } => to_json_binary(&query::trade(deps, id)?),
+            VaultQueryMsg::ListTrades {
+                start_after,
+                limit,
+                denom,
+            } => to_json_binary(&query::trades(deps, start_after, limit, denom)?),
+            VaultQueryMsg::GetSharePrice {} => to_json_binary(&query::share_price(deps)?),
+            VaultQueryMsg::GetShareDenom {} => to_json_binary(&query::share_denom(deps)?),
+            VaultQueryMsg::GetNavHistory { start, end, limit } => {
+                to_json_binary(&query::nav_history(deps, start, end, limit)?)
+            }
+            VaultQueryMsg::GetLatestNavSnapshots { count } => {
+                to_json_binary(&query::latest_nav_snapshots(deps, count)?)
+            }
+            VaultQueryMsg::GetMaxSlippage {} => to_json_binary(&query::max_slippage(deps)?),
+            VaultQueryMsg::GetTradingLimits {} => {
+                to_json_binary(&query::trading_limits(deps, env)?)
+            }
+            VaultQueryMsg::GetDepositCaps {} => to_json_binary(&query::deposit_caps(deps)?),
+            VaultQueryMsg::GetReconciliation {} => {
+                to_json_binary(&query::reconciliation(deps, env)?)
+            }
+            VaultQueryMsg::GetStrategy {} => to_json_binary(&query::strategy(deps)?),
+            VaultQueryMsg::GetDepositorAllowlist { start_after, limit } => {
+                to_json_binary(&query::depositor_allowlist(deps, start_after, limit)?)
+            }
+            VaultQueryMsg::GetSwapAllowlist { start_after, limit } => {
+                to_json_binary(&query::swap_allowlist(deps, start_after, limit)?)
+            }
+            VaultQueryMsg::GetPauseState {} => to_json_binary(&query::pause_state(deps)?),
+            VaultQueryMsg::GetDepositExpiry {} => to_json_binary(&query::deposit_expiry(deps)?),
+            VaultQueryMsg::GetGuardian {} => to_json_binary(&query::guardian(deps)?),
         },
         QueryMsg::Wavs(msg) => match msg {
             ServiceHandlerQueryMessages::WavsServiceManager {} => {
@@ -1,4 +1,4 @@
-use crate::skip_entry::SwapRoute;
+use crate::skip_entry::{SwapRoute, WithdrawRoute};
 use bincode::error::{DecodeError, EncodeError};
 use cosmwasm_schema::{cw_serde, QueryResponses};
 use cosmwasm_std::{Addr, Coin, Decimal256, Timestamp, Uint256};
@@ -11,19 +11,35 @@ use wavs_types::contracts::cosmwasm::service_handler::{
 #[cw_serde]
 pub struct InstantiateMsg {
     pub service_manager: String,
-    pub initial_whitelisted_denoms: Vec<String>,
+    pub initial_whitelist: Vec<WhitelistEntry>,
     pub skip_entry_point: String,
+    #[serde(default)]
+    pub share_subdenom: Option<String>, // Opt-in: issue shares as the Token Factory denom factory/{vault}/{subdenom}
+    #[serde(default)]
+    pub target_allocation: Option<TargetAllocation>, // Active immediately; later changes are timelocked
 }
 
 #[cw_ownable_execute]
 #[cw_serde]
 pub enum VaultExecuteMsg {
+    // Held until the next price update. A deposit worth less than one share ($0.000001 at
+    // the initial share price) mints nothing and stays pending until cancelled.
     Deposit {},
     Withdraw {
         shares: Uint256,
     },
+    // Redeem shares into a single denom, swapping every other slice through Skip. Each
+    // slice settles on its own: if one swap fails, that slice is returned in-kind while
+    // the others are still paid out in `denom`, which can then fall short of `min_out`.
+    // A swap that pays out any other denom reverts the whole withdrawal.
+    WithdrawTo {
+        shares: Uint256,
+        denom: String,
+        min_out: Uint256,
+        routes: Vec<WithdrawRoute>, // One route per other denom held by the vault
+    },
     UpdateWhitelist {
-        to_add: Option<Vec<String>>,
+        to_add: Option<Vec<WhitelistEntry>>, // Also replaces the metadata of denoms already listed
         to_remove: Option<Vec<String>>,
     },
     UpdatePrices {
@@ -34,6 +50,89 @@ pub enum VaultExecuteMsg {
     UpdateServiceManager {
         addr: String,
     },
+    // Settles fees under the old rates first, so once shares exist the last price update
+    // must be younger than the max payload age
+    UpdateFeeConfig {
+        management_fee_rate: Option<Decimal256>,
+        performance_fee_rate: Option<Decimal256>,
+        fee_recipient: Option<String>,
+    },
+    UpdateMaxPayloadAge {
+        max_age_seconds: u64,
+    },
+    // How far past the block time an envelope timestamp may be, for operator clock drift
+    UpdateMaxClockSkew {
+        max_skew_seconds: u64,
+    },
+    UpdatePriceGuards {
+        to_set: Option<Vec<PriceGuard>>,
+        to_remove: Option<Vec<String>>,
+        action: Option<PriceGuardAction>,
+    },
+    UpdateGuardian {
+        guardian: Option<String>,
+    },
+    UpdateMaxSlippage {
+        max_slippage_bps: u64,
+    },
+    UpdateTradingLimits {
+        limits: TradingLimits,
+    },
+    UpdateDepositCaps {
+        caps: DepositCaps,
+    },
+    // Compare bank balances with the tracked assets, writing down deficits and
+    // handling surpluses per the surplus policy. Fails if a balance no longer covers
+    // the deposits still pending
+    Reconcile {},
+    UpdateSurplusPolicy {
+        policy: SurplusPolicy,
+    },
+    // Removing an address only stops new deposits, its shares stay withdrawable
+    UpdateDepositorAllowlist {
+        enabled: Option<bool>, // When enabled, only listed addresses may deposit
+        to_add: Option<Vec<String>>,
+        to_remove: Option<Vec<String>>,
+    },
+    // Removing a venue also drops its pools
+    UpdateSwapAllowlist {
+        enabled: Option<bool>, // When enabled, swap routes may only use listed venues and pools
+        venues_to_add: Option<Vec<String>>,
+        venues_to_remove: Option<Vec<String>>,
+        pools_to_add: Option<Vec<AllowedPool>>, // The venue must already be listed
+        pools_to_remove: Option<Vec<AllowedPool>>,
+    },
+    // Queue a strategy change that can be executed once the timelock has passed, so
+    // depositors can exit first. A new proposal replaces the pending one
+    ProposeStrategyChange {
+        target_allocation: Option<TargetAllocation>,
+        timelock_seconds: Option<u64>, // Applies to proposals made after this one executes
+    },
+    ExecuteStrategyChange {},
+    CancelStrategyChange {},
+    CancelDeposit {
+        deposit_id: u64,
+    },
+    UpdateDepositExpiry {
+        expiry_seconds: Option<u64>,
+    },
+    // Lock shares until the next price update redeems them at the fresh NAV
+    RequestWithdraw {
+        shares: Uint256,
+    },
+    // Return a queued withdrawal's shares to the requester, also allowed in emergency mode
+    CancelWithdraw {
+        withdraw_id: u64,
+    },
+    UpdateWithdrawalQueue {
+        enabled: bool, // When enabled, instant withdrawals are only allowed in emergency mode
+    },
+    UpdatePauseState {
+        deposits: Option<bool>,
+        price_processing: Option<bool>,
+        trading: Option<bool>,
+        emergency_mode: Option<bool>,
+    },
 }
 
 #[cw_serde]
@@ -53,6 +152,11 @@ pub enum VaultQueryMsg {
     GetVaultValue {},
     #[returns(Vec<String>)]
     GetWhitelistedDenoms {},
+    #[returns(Vec<WhitelistEntry>)]
+    GetAssetRegistry {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
     #[returns(DepositRequest)]
     GetDepositRequest { deposit_id: u64 },
     #[returns(Vec<DepositRequest>)]
@@ -60,6 +164,16 @@ pub enum VaultQueryMsg {
         start_after: Option<u64>,
         limit: Option<u32>,
     },
+    #[returns(WithdrawRequest)]
+    GetWithdrawRequest { withdraw_id: u64 },
+    #[returns(Vec<WithdrawRequest>)]
+    ListWithdrawRequests {
+        user: Option<String>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    #[returns(bool)]
+    GetWithdrawalQueueEnabled {},
     #[returns(Vec<Coin>)]
     GetVaultAssets {},
     #[returns(Uint256)]
@@ -76,6 +190,60 @@ pub enum VaultQueryMsg {
     GetVaultState {},
     #[returns(Uint256)]
     GetUserShares { user: String },
+    #[returns(FeeConfig)]
+    GetFeeConfig {},
+    #[returns(AccruedFees)]
+    GetAccruedFees {},
+    #[returns(EnvelopeStatus)]
+    GetEnvelopeStatus {},
+    #[returns(PriceGuardConfig)]
+    GetPriceGuards {},
+    #[returns(TradeRecord)]
+    GetTrade { id: u64 },
+    #[returns(Vec<TradeRecord>)]
+    ListTrades {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        denom: Option<String>, // Only trades offering or receiving this denom
+    },
+    #[returns(Decimal256)]
+    GetSharePrice {},
+    #[returns(Option<String>)]
+    GetShareDenom {},
+    #[returns(Vec<NavSnapshot>)]
+    GetNavHistory {
+        start: Option<Timestamp>, // Inclusive
+        end: Option<Timestamp>,   // Inclusive
+        limit: Option<u32>,
+    },
+    #[returns(Vec<NavSnapshot>)]
+    GetLatestNavSnapshots { count: u32 },
+    #[returns(u64)]
+    GetMaxSlippage {},
+    #[returns(TradingLimitsResponse)]
+    GetTradingLimits {},
+    #[returns(DepositCaps)]
+    GetDepositCaps {},
+    #[returns(ReconciliationReport)]
+    GetReconciliation {},
+    #[returns(StrategyResponse)]
+    GetStrategy {},
+    #[returns(DepositorAllowlistResponse)]
+    GetDepositorAllowlist {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    #[returns(SwapAllowlistResponse)]
+    GetSwapAllowlist {
+        start_after: Option<String>, // Venue name
+        limit: Option<u32>,
+    },
+    #[returns(PauseState)]
+    GetPauseState {},
+    #[returns(Option<u64>)]
+    GetDepositExpiry {},
+    #[returns(Option<cosmwasm_std::Addr>)]
+    GetGuardian {},
 }
 
 #[cw_serde]
@@ -88,7 +256,10 @@ pub enum QueryMsg {
 }
 
 #[cw_serde]
-pub struct MigrateMsg {}
+pub struct MigrateMsg {
+    #[serde(default)]
+    pub share_subdenom: Option<String>, // Convert internal share balances into this Token Factory subdenom
+}
 
 #[cw_serde]
 pub struct VaultState {
@@ -96,6 +267,8 @@ pub struct VaultState {
     pub total_pending_assets: Vec<Coin>,
     pub prices: Vec<PriceInfo>,
     pub tvl: Decimal256,
+    #[serde(default)]
+    pub target_allocation: Option<TargetAllocation>, // None until the owner sets one
 }
 
 #[cw_serde]
@@ -111,12 +284,264 @@ pub struct DepositRequest {
     pub user: Addr,
     pub coins: Vec<Coin>,
     pub state: DepositState,
+    // Requests stored before this field existed read as the epoch, so a configured expiry applies immediately
+    #[serde(default)]
+    pub created_at: Timestamp,
 }
 
 #[cw_serde]
 pub enum DepositState {
     Pending,
     Completed { value_usd: Decimal256 },
+    Cancelled,
+}
+
+#[cw_serde]
+pub struct WithdrawRequest {
+    pub id: u64,
+    pub user: Addr,
+    pub shares: Uint256,
+    pub state: WithdrawState,
+    pub created_at: Timestamp,
+}
+
+#[cw_serde]
+pub enum WithdrawState {
+    Pending,
+    Completed {
+        value_usd: Decimal256,
+        assets: Vec<Coin>,
+    },
+    Cancelled,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct FeeConfig {
+    pub management_fee_rate: Decimal256, // Annual rate charged on vault value (e.g., 0.02 = 2%)
+    pub performance_fee_rate: Decimal256, // Share of gains above the high-water mark
+    pub fee_recipient: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct AccruedFees {
+    pub fee_recipient: Option<Addr>,
+    pub high_water_mark: Decimal256, // USD value per share
+    pub last_accrual: Timestamp,
+    pub total_management_fee_shares: Uint256,
+    pub total_performance_fee_shares: Uint256,
+    pub pending_management_fee_usd: Decimal256,
+}
+
+#[cw_serde]
+pub struct NavSnapshot {
+    pub timestamp: Timestamp,
+    pub tvl: Decimal256,
+    pub total_shares: Uint256,
+    pub share_price: Decimal256, // USD per share, zero while no shares exist
+    pub balances: Vec<Coin>,
+}
+
+#[cw_serde]
+pub enum TradeStatus {
+    Completed,
+    Failed { error: String },
+}
+
+#[cw_serde]
+pub struct TradeRecord {
+    pub id: u64,
+    pub status: TradeStatus,
+    pub in_coin: Coin,
+    pub out_denom: String,
+    pub out_coin: Option<Coin>, // None when the swap failed
+    pub swap_venue: String,
+    pub in_price_usd: Option<Decimal256>, // Stored prices when the swap settled
+    pub out_price_usd: Option<Decimal256>,
+    pub executed_at: Timestamp,
+    pub envelope_timestamp: Option<Timestamp>, // Envelope whose payload planned the swap
+}
+
+/// Whitelisted denom with the metadata operators and frontends need to price and
+/// display it.
+#[cw_serde]
+pub struct WhitelistEntry {
+    pub denom: String,
+    pub symbol: String,
+    pub decimals: u8, // Every PriceInfo for the denom must use these decimals
+    pub price_source_ids: Vec<PriceSourceId>,
+    pub min_deposit: Uint256, // Smallest accepted deposit in base units
+    pub depositable: bool,
+    pub tradable: bool, // Whether rebalancing swaps may use the denom
+}
+
+#[cw_serde]
+pub struct PriceSourceId {
+    pub source: String, // e.g. "coingecko", "pyth"
+    pub id: String,
+}
+
+/// Limits on how much a single payload and the last 24 hours may trade.
+/// `None` leaves that dimension unlimited.
+#[cw_serde]
+#[derive(Default)]
+pub struct TradingLimits {
+    pub max_trade_fraction: Option<Decimal256>, // Share of TVL a single payload may trade (e.g., 0.2 = 20%)
+    pub max_routes: Option<u32>,
+    pub max_daily_turnover_usd: Option<Decimal256>,
+}
+
+#[cw_serde]
+pub struct TradingLimitsResponse {
+    pub limits: TradingLimits,
+    pub turnover_24h_usd: Decimal256,
+    pub remaining_turnover_usd: Option<Decimal256>,
+    pub max_trade_value_usd: Option<Decimal256>, // max_trade_fraction applied to the current TVL
+}
+
+/// Caps on what the vault accepts, checked when depositing and again when deposits
+/// are priced in. `None` (or no entry) leaves that dimension unlimited.
+#[cw_serde]
+#[derive(Default)]
+pub struct DepositCaps {
+    pub max_tvl_usd: Option<Decimal256>, // Vault value plus pending deposits
+    pub denom_caps: Vec<Coin>,           // Held plus pending amount per denom
+    pub max_position_usd: Option<Decimal256>, // Value of one address's shares plus its pending deposits
+}
+
+/// What `Reconcile` does with funds the vault holds but does not track.
+#[cw_serde]
+#[derive(Default)]
+pub enum SurplusPolicy {
+    /// Count the surplus as vault assets, raising the share price
+    #[default]
+    FoldIntoVault,
+    /// Send the surplus to the treasury
+    SweepToTreasury { treasury: Addr },
+}
+
+#[cw_serde]
+pub struct AssetDiscrepancy {
+    pub denom: String,
+    pub bank_balance: Uint256,
+    pub pending: Uint256, // Deposits waiting to be priced
+    pub tracked: Uint256, // VAULT_ASSETS
+    pub surplus: Uint256, // Held beyond pending + tracked
+    pub deficit: Uint256, // Tracked but missing from the bank balance
+}
+
+#[cw_serde]
+pub struct ReconciliationReport {
+    pub policy: SurplusPolicy,
+    pub discrepancies: Vec<AssetDiscrepancy>, // Only denoms that are out of balance
+}
+
+#[cw_serde]
+pub struct AllowedPool {
+    pub venue: String, // Skip swap venue name, e.g. "neutron-astroport"
+    pub pool: String,
+}
+
+#[cw_serde]
+pub struct SwapVenueAllowlist {
+    pub venue: String,
+    pub pools: Vec<String>,
+}
+
+#[cw_serde]
+pub struct SwapAllowlistResponse {
+    pub enabled: bool,
+    pub venues: Vec<SwapVenueAllowlist>,
+}
+
+/// Portfolio the operators rebalance towards.
+#[cw_serde]
+pub enum TargetAllocation {
+    // The operators' advisor picks the weights each round, within these bounds
+    Ai {
+        #[serde(default)]
+        weight_bounds: Vec<WeightBound>, // Denoms left out may take any weight
+    },
+    Fixed {
+        weights: Vec<AllocationWeight>, // Must sum to one
+    },
+}
+
+#[cw_serde]
+pub struct AllocationWeight {
+    pub denom: String,
+    pub weight: Decimal256,
+}
+
+/// Range the advisor's weight for a denom must fall in.
+#[cw_serde]
+pub struct WeightBound {
+    pub denom: String,
+    pub min_weight: Decimal256,
+    pub max_weight: Decimal256,
+}
+
+/// Strategy change waiting out the timelock.
+#[cw_serde]
+pub struct StrategyChange {
+    pub target_allocation: Option<TargetAllocation>,
+    pub timelock_seconds: Option<u64>,
+    pub proposed_at: Timestamp,
+    pub executable_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct StrategyResponse {
+    pub target_allocation: Option<TargetAllocation>,
+    pub timelock_seconds: u64,
+    pub pending_change: Option<StrategyChange>,
+}
+
+#[cw_serde]
+pub struct DepositorAllowlistResponse {
+    pub enabled: bool,
+    pub addresses: Vec<Addr>,
+}
+
+/// Emergency mode overrides the individual flags: only `Withdraw` and owner
+/// configuration remain available and WAVS envelopes are refused.
+#[cw_serde]
+#[derive(Default)]
+pub struct PauseState {
+    pub deposits_paused: bool,
+    pub price_processing_paused: bool,
+    pub trading_paused: bool,
+    pub emergency_mode: bool,
+}
+
+#[cw_serde]
+pub struct PriceGuard {
+    pub denom: String,
+    pub max_deviation_bps: u64, // Maximum move versus the stored price (100 = 1%)
+}
+
+/// What `UpdatePrices` does when a price moves more than its guard allows.
+#[cw_serde]
+#[derive(Default)]
+pub enum PriceGuardAction {
+    /// Fail the whole price update
+    #[default]
+    Reject,
+    /// Keep the stored price, hold pending deposits and skip rebalancing
+    HoldDeposits,
+}
+
+#[cw_serde]
+pub struct PriceGuardConfig {
+    pub action: PriceGuardAction,
+    pub guards: Vec<PriceGuard>,
+}
+
+#[cw_serde]
+pub struct EnvelopeStatus {
+    pub last_accepted_timestamp: Option<Timestamp>,
+    pub max_payload_age_seconds: u64,
+    pub max_clock_skew_seconds: u64,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -1,14 +1,34 @@
-use cosmwasm_std::{Coin, Decimal256, Deps, Order, StdResult, Uint256};
+use cosmwasm_std::{
+    Addr, Coin, Decimal256, Deps, Env, Order, StdError, StdResult, Timestamp, Uint256,
+};
 use cw_storage_plus::Bound;
 
 use crate::{
+    execute::{self, management_fee_for_period, rolling_turnover},
     state::{
-        StoredPriceInfo, DEPOSIT_REQUESTS, PRICES, TOTAL_PENDING_ASSETS, TOTAL_SHARES, USER_SHARES,
-        VAULT_ASSETS, VAULT_VALUE_DEPOSITED, WHITELISTED_DENOMS,
+        StoredPriceInfo, DEPOSITOR_ALLOWLIST, DEPOSITOR_ALLOWLIST_ENABLED, DEPOSIT_CAPS,
+        DEPOSIT_EXPIRY_SECONDS, DEPOSIT_REQUESTS, FEE_CONFIG, FEE_STATE, GUARDIAN,
+        LAST_ENVELOPE_TIMESTAMP, MAX_CLOCK_SKEW_SECONDS, MAX_PAYLOAD_AGE_SECONDS, MAX_SLIPPAGE_BPS,
+        NAV_SNAPSHOTS, PAUSE_STATE, PENDING_STRATEGY_CHANGE, PRICES, PRICE_GUARDS,
+        PRICE_GUARD_ACTION, SHARE_DENOM, STRATEGY_TIMELOCK_SECONDS, SURPLUS_POLICY,
+        SWAP_ALLOWLIST_ENABLED, SWAP_POOLS, SWAP_VENUES, TARGET_ALLOCATION, TOTAL_PENDING_ASSETS,
+        TOTAL_SHARES, TRADE_HISTORY, TRADING_LIMITS, USER_SHARES, VAULT_ASSETS,
+        VAULT_VALUE_DEPOSITED, WHITELISTED_DENOMS, WITHDRAWAL_QUEUE_ENABLED, WITHDRAW_REQUESTS,
     },
-    DepositRequest, PriceInfo, VaultState,
+    AccruedFees, DepositCaps, DepositRequest, DepositorAllowlistResponse, EnvelopeStatus,
+    FeeConfig, NavSnapshot, PauseState, PriceGuard, PriceGuardConfig, PriceInfo,
+    ReconciliationReport, StrategyResponse, SwapAllowlistResponse, SwapVenueAllowlist, TradeRecord,
+    TradingLimitsResponse, VaultState, WhitelistEntry, WithdrawRequest,
 };
 
+// Paginated queries return this many items unless asked, and never more than MAX_LIMIT
+const DEFAULT_LIMIT: u32 = 30;
+const MAX_LIMIT: u32 = 100;
+
+fn page_limit(limit: Option<u32>) -> usize {
+    limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize
+}
+
 pub fn total_shares(deps: Deps) -> StdResult<Uint256> {
     TOTAL_SHARES.load(deps.storage)
 }
@@ -24,6 +44,23 @@ pub fn whitelisted_denoms(deps: Deps) -> StdResult<Vec<String>> {
         .collect()
 }
 
+pub fn asset_registry(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Vec<WhitelistEntry>> {
+    WHITELISTED_DENOMS
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(page_limit(limit))
+        .map(|item| item.map(|(_, entry)| entry))
+        .collect()
+}
+
 pub fn deposit_request(deps: Deps, deposit_id: u64) -> StdResult<DepositRequest> {
     DEPOSIT_REQUESTS.load(deps.storage, deposit_id)
 }
@@ -36,7 +73,7 @@ pub fn deposit_requests(
     let start = start_after.map(Bound::exclusive);
     DEPOSIT_REQUESTS
         .range(deps.storage, start, None, Order::Ascending)
-        .take(limit.unwrap_or(30) as usize)
+        .take(page_limit(limit))
         .map(|item| item.map(|(_, deposit)| deposit))
         .collect()
 }
@@ -128,9 +165,315 @@ pub fn vault_state(deps: Deps) -> StdResult<VaultState> {
         total_pending_assets: total_pending_assets(deps)?,
         prices: prices(deps)?,
         tvl: vault_value(deps)?,
+        target_allocation: TARGET_ALLOCATION.may_load(deps.storage)?,
+    })
+}
+
+pub fn strategy(deps: Deps) -> StdResult<StrategyResponse> {
+    Ok(StrategyResponse {
+        target_allocation: TARGET_ALLOCATION.may_load(deps.storage)?,
+        timelock_seconds: STRATEGY_TIMELOCK_SECONDS.load(deps.storage)?,
+        pending_change: PENDING_STRATEGY_CHANGE.may_load(deps.storage)?,
     })
 }
 
 pub fn user_shares(deps: Deps, user: String) -> StdResult<Uint256> {
+    // Tokenized shares are freely transferable, so the bank balance is authoritative
+    if let Some(share_denom) = SHARE_DENOM.may_load(deps.storage)? {
+        return Ok(deps.querier.query_balance(user, share_denom)?.amount);
+    }
+
     USER_SHARES.load(deps.storage, user).or(Ok(Uint256::zero()))
 }
+
+pub fn share_denom(deps: Deps) -> StdResult<Option<String>> {
+    SHARE_DENOM.may_load(deps.storage)
+}
+
+pub fn fee_config(deps: Deps) -> StdResult<FeeConfig> {
+    FEE_CONFIG.load(deps.storage)
+}
+
+pub fn accrued_fees(deps: Deps, env: Env) -> StdResult<AccruedFees> {
+    let fee_config = FEE_CONFIG.load(deps.storage)?;
+    let fee_state = FEE_STATE.load(deps.storage)?;
+
+    // Management fee earned since the last accrual but not yet minted
+    let elapsed = env
+        .block
+        .time
+        .seconds()
+        .saturating_sub(fee_state.last_accrual.seconds());
+    let pending_management_fee_usd = if fee_config.fee_recipient.is_some() {
+        management_fee_for_period(
+            VAULT_VALUE_DEPOSITED.load(deps.storage)?,
+            fee_config.management_fee_rate,
+            elapsed,
+        )?
+    } else {
+        Decimal256::zero()
+    };
+
+    Ok(AccruedFees {
+        fee_recipient: fee_config.fee_recipient,
+        high_water_mark: fee_state.high_water_mark,
+        last_accrual: fee_state.last_accrual,
+        total_management_fee_shares: fee_state.total_management_fee_shares,
+        total_performance_fee_shares: fee_state.total_performance_fee_shares,
+        pending_management_fee_usd,
+    })
+}
+
+pub fn envelope_status(deps: Deps) -> StdResult<EnvelopeStatus> {
+    Ok(EnvelopeStatus {
+        last_accepted_timestamp: LAST_ENVELOPE_TIMESTAMP.may_load(deps.storage)?,
+        max_payload_age_seconds: MAX_PAYLOAD_AGE_SECONDS.load(deps.storage)?,
+        max_clock_skew_seconds: MAX_CLOCK_SKEW_SECONDS.load(deps.storage)?,
+    })
+}
+
+pub fn price_guards(deps: Deps) -> StdResult<PriceGuardConfig> {
+    let guards = PRICE_GUARDS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (denom, max_deviation_bps) = item?;
+            Ok(PriceGuard {
+                denom,
+                max_deviation_bps,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(PriceGuardConfig {
+        action: PRICE_GUARD_ACTION.load(deps.storage)?,
+        guards,
+    })
+}
+
+pub fn pause_state(deps: Deps) -> StdResult<PauseState> {
+    PAUSE_STATE.load(deps.storage)
+}
+
+pub fn guardian(deps: Deps) -> StdResult<Option<Addr>> {
+    GUARDIAN.may_load(deps.storage)
+}
+
+pub fn deposit_expiry(deps: Deps) -> StdResult<Option<u64>> {
+    DEPOSIT_EXPIRY_SECONDS.may_load(deps.storage)
+}
+
+pub fn max_slippage(deps: Deps) -> StdResult<u64> {
+    MAX_SLIPPAGE_BPS.load(deps.storage)
+}
+
+pub fn deposit_caps(deps: Deps) -> StdResult<DepositCaps> {
+    DEPOSIT_CAPS.load(deps.storage)
+}
+
+pub fn reconciliation(deps: Deps, env: Env) -> StdResult<ReconciliationReport> {
+    Ok(ReconciliationReport {
+        policy: SURPLUS_POLICY.load(deps.storage)?,
+        discrepancies: execute::asset_discrepancies(
+            deps.storage,
+            &deps.querier,
+            &env.contract.address,
+        )?,
+    })
+}
+
+pub fn depositor_allowlist(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<DepositorAllowlistResponse> {
+    let start_after = start_after
+        .map(|address| deps.api.addr_validate(&address))
+        .transpose()?;
+    let addresses = DEPOSITOR_ALLOWLIST
+        .keys(
+            deps.storage,
+            start_after.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(page_limit(limit))
+        .collect::<StdResult<_>>()?;
+
+    Ok(DepositorAllowlistResponse {
+        enabled: DEPOSITOR_ALLOWLIST_ENABLED.load(deps.storage)?,
+        addresses,
+    })
+}
+
+pub fn swap_allowlist(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<SwapAllowlistResponse> {
+    let venues = SWAP_VENUES
+        .keys(
+            deps.storage,
+            start_after.as_deref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(page_limit(limit))
+        .map(|venue| {
+            let venue = venue?;
+            let pools = SWAP_POOLS
+                .prefix(&venue)
+                .keys(deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<_>>()?;
+            Ok(SwapVenueAllowlist { venue, pools })
+        })
+        .collect::<StdResult<_>>()?;
+
+    Ok(SwapAllowlistResponse {
+        enabled: SWAP_ALLOWLIST_ENABLED.load(deps.storage)?,
+        venues,
+    })
+}
+
+pub fn trading_limits(deps: Deps, env: Env) -> StdResult<TradingLimitsResponse> {
+    let limits = TRADING_LIMITS.load(deps.storage)?;
+    let turnover_24h_usd = rolling_turnover(deps.storage, env.block.time)?;
+    let vault_value = VAULT_VALUE_DEPOSITED.load(deps.storage)?;
+
+    let max_trade_value_usd = limits
+        .max_trade_fraction
+        .map(|fraction| vault_value.checked_mul(fraction))
+        .transpose()?;
+
+    Ok(TradingLimitsResponse {
+        remaining_turnover_usd: limits
+            .max_daily_turnover_usd
+            .map(|cap| cap.saturating_sub(turnover_24h_usd)),
+        max_trade_value_usd,
+        turnover_24h_usd,
+        limits,
+    })
+}
+
+pub fn trade(deps: Deps, id: u64) -> StdResult<TradeRecord> {
+    TRADE_HISTORY.load(deps.storage, id)
+}
+
+pub fn trades(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    denom: Option<String>,
+) -> StdResult<Vec<TradeRecord>> {
+    let limit = page_limit(limit);
+
+    let Some(denom) = denom else {
+        return TRADE_HISTORY
+            .range(
+                deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .map(|item| item.map(|(_, trade)| trade))
+            .collect();
+    };
+
+    // A trade touches the denom on either side, so merge both indexes by id
+    let mut trades = Vec::new();
+    for index in [&TRADE_HISTORY.idx.in_denom, &TRADE_HISTORY.idx.out_denom] {
+        let matching = index
+            .prefix(denom.clone())
+            .range(
+                deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .map(|item| item.map(|(_, trade)| trade))
+            .collect::<StdResult<Vec<_>>>()?;
+        trades.extend(matching);
+    }
+    trades.sort_by_key(|trade| trade.id);
+    trades.dedup_by_key(|trade| trade.id);
+    trades.truncate(limit);
+
+    Ok(trades)
+}
+
+pub fn share_price(deps: Deps) -> StdResult<Decimal256> {
+    let total_shares = TOTAL_SHARES.load(deps.storage)?;
+    if total_shares.is_zero() {
+        return Ok(Decimal256::zero());
+    }
+
+    let vault_value = VAULT_VALUE_DEPOSITED.load(deps.storage)?;
+    execute::share_price(vault_value, total_shares).map_err(|e| StdError::msg(e.to_string()))
+}
+
+pub fn nav_history(
+    deps: Deps,
+    start: Option<Timestamp>,
+    end: Option<Timestamp>,
+    limit: Option<u32>,
+) -> StdResult<Vec<NavSnapshot>> {
+    NAV_SNAPSHOTS
+        .range(
+            deps.storage,
+            start.map(|time| Bound::inclusive(time.nanos())),
+            end.map(|time| Bound::inclusive(time.nanos())),
+            Order::Ascending,
+        )
+        .take(page_limit(limit))
+        .map(|item| item.map(|(_, snapshot)| snapshot))
+        .collect()
+}
+
+// Newest first
+pub fn latest_nav_snapshots(deps: Deps, count: u32) -> StdResult<Vec<NavSnapshot>> {
+    NAV_SNAPSHOTS
+        .range(deps.storage, None, None, Order::Descending)
+        .take(count.min(MAX_LIMIT) as usize)
+        .map(|item| item.map(|(_, snapshot)| snapshot))
+        .collect()
+}
+
+pub fn withdraw_request(deps: Deps, withdraw_id: u64) -> StdResult<WithdrawRequest> {
+    WITHDRAW_REQUESTS.load(deps.storage, withdraw_id)
+}
+
+pub fn withdraw_requests(
+    deps: Deps,
+    user: Option<String>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<WithdrawRequest>> {
+    let start = start_after.map(Bound::exclusive);
+    let limit = page_limit(limit);
+
+    match user {
+        Some(user) => {
+            let user = deps.api.addr_validate(&user)?;
+            WITHDRAW_REQUESTS
+                .idx
+                .user
+                .prefix(user)
+                .range(deps.storage, start, None, Order::Ascending)
+                .take(limit)
+                .map(|item| item.map(|(_, request)| request))
+                .collect()
+        }
+        None => WITHDRAW_REQUESTS
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(_, request)| request))
+            .collect(),
+    }
+}
+
+pub fn withdrawal_queue_enabled(deps: Deps) -> StdResult<bool> {
+    Ok(WITHDRAWAL_QUEUE_ENABLED
+        .may_load(deps.storage)?
+        .unwrap_or_default())
+}
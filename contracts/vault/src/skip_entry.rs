@@ -29,6 +29,15 @@ pub struct SwapRoute {
     pub operations: Vec<SwapOperation>,
 }
 
+/// Route for swapping a withdrawn `offer_denom` slice into the withdrawal denom.
+/// The amount is the pro-rata slice computed by the vault.
+#[cw_serde]
+pub struct WithdrawRoute {
+    pub swap_venue_name: String,
+    pub offer_denom: String,
+    pub operations: Vec<SwapOperation>,
+}
+
 /// Swap instructions accepted by the entry-point contract.
 #[cw_serde]
 pub struct SwapExactAssetIn {
@@ -1,9 +1,13 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Coin, Decimal256, Uint256, Uint64};
-use cw_storage_plus::{Deque, Item, Map};
+use cosmwasm_std::{Addr, Coin, Decimal256, Timestamp, Uint256, Uint64};
+use cw_storage_plus::{Deque, Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 use wavs_types::contracts::cosmwasm::service_handler::{WavsEnvelope, WavsSignatureData};
 
-use crate::{msg::PriceInfo, DepositRequest, Payload};
+use crate::{
+    msg::PriceInfo, DepositCaps, DepositRequest, FeeConfig, NavSnapshot, PauseState, Payload,
+    PriceGuardAction, StrategyChange, SurplusPolicy, TargetAllocation, TradeRecord, TradingLimits,
+    WhitelistEntry, WithdrawRequest,
+};
 
 #[cw_serde]
 pub struct StoredPriceInfo {
@@ -14,39 +18,169 @@ pub struct StoredPriceInfo {
 
 #[cw_serde]
 pub struct TradeInfo {
+    pub id: u64,
     pub in_coin: Coin,
     pub out_denom: String,
     pub timeout_timestamp: u64,
+    pub swap_venue: String,
+    pub envelope_timestamp: Option<Timestamp>,
+    #[serde(default)]
+    pub value_usd: Decimal256, // Counted towards daily turnover once the swap succeeds
+}
+
+// Single-asset withdrawal waiting on its swap replies
+#[cw_serde]
+pub struct PendingWithdrawal {
+    pub user: Addr,
+    pub denom: String,
+    pub min_out: Uint256,
+    pub amount_out: Uint256, // Withdrawal denom collected so far
+    pub refunds: Vec<Coin>,  // Slices whose swap failed, returned in-kind
+    pub outstanding_swaps: u32,
+}
+
+#[cw_serde]
+pub struct TurnoverEntry {
+    pub timestamp: Timestamp,
+    pub value_usd: Decimal256,
+}
+
+#[cw_serde]
+pub struct FeeState {
+    pub last_accrual: Timestamp,
+    pub high_water_mark: Decimal256,
+    pub total_management_fee_shares: Uint256,
+    pub total_performance_fee_shares: Uint256,
 }
 
 // Vault
 pub const VAULT_VALUE_DEPOSITED: Item<Decimal256> = Item::new("vault_value_deposited");
 pub const TOTAL_SHARES: Item<Uint256> = Item::new("total_shares");
-pub const WHITELISTED_DENOMS: Map<String, ()> = Map::new("whitelisted_denoms");
+pub const WHITELISTED_DENOMS: Map<String, WhitelistEntry> = Map::new("asset_registry");
+// Bare denom set used before whitelist entries carried metadata; drained by migrate
+pub const LEGACY_WHITELISTED_DENOMS: Map<String, ()> = Map::new("whitelisted_denoms");
 pub const DEPOSIT_REQUESTS: Map<u64, DepositRequest> = Map::new("deposit_requests");
 pub const VAULT_ASSETS: Map<String, Uint256> = Map::new("vault_assets");
+pub const SURPLUS_POLICY: Item<SurplusPolicy> = Item::new("surplus_policy");
+pub const TARGET_ALLOCATION: Item<TargetAllocation> = Item::new("target_allocation"); // unset = no rebalancing
+pub const STRATEGY_TIMELOCK_SECONDS: Item<u64> = Item::new("strategy_timelock_seconds");
+pub const PENDING_STRATEGY_CHANGE: Item<StrategyChange> = Item::new("pending_strategy_change");
 pub const TOTAL_PENDING_ASSETS: Map<String, Uint256> = Map::new("pending_assets");
+pub const USER_PENDING_ASSETS: Map<(&Addr, &str), Uint256> = Map::new("user_pending_assets"); // TOTAL_PENDING_ASSETS per depositor
+pub const DEPOSIT_CAPS: Item<DepositCaps> = Item::new("deposit_caps");
+pub const DEPOSITOR_ALLOWLIST_ENABLED: Item<bool> = Item::new("depositor_allowlist_enabled");
+pub const DEPOSITOR_ALLOWLIST: Map<&Addr, ()> = Map::new("depositor_allowlist");
+pub const SWAP_ALLOWLIST_ENABLED: Item<bool> = Item::new("swap_allowlist_enabled");
+pub const SWAP_VENUES: Map<&str, ()> = Map::new("swap_venues");
+pub const SWAP_POOLS: Map<(&str, &str), ()> = Map::new("swap_pools"); // (venue, pool)
+pub const DEPOSIT_EXPIRY_SECONDS: Item<u64> = Item::new("deposit_expiry_seconds"); // unset = never expires
 pub const DEPOSIT_ID_COUNTER: Item<u64> = Item::new("deposit_id_counter");
 pub const USER_SHARES: Map<String, Uint256> = Map::new("user_shares");
+pub const SHARE_DENOM: Item<String> = Item::new("share_denom"); // Token Factory denom; unset = shares live in USER_SHARES
 pub const PRICES: Map<String, StoredPriceInfo> = Map::new("prices"); // denom -> Price info
+pub const PRICES_UPDATED_AT: Item<Timestamp> = Item::new("prices_updated_at"); // Block time of the last accepted update
 pub const SKIP_ENTRY_POINT: Item<Addr> = Item::new("skip_entry_point");
-pub const TRADE_TRACKER: Deque<TradeInfo> = Deque::new("trade_tracker");
+pub const TRADE_ID_COUNTER: Item<u64> = Item::new("trade_id_counter");
+pub const WITHDRAW_TO_ID_COUNTER: Item<u64> = Item::new("withdraw_to_id_counter");
+pub const WITHDRAW_TO_PENDING: Map<u64, PendingWithdrawal> = Map::new("withdraw_to_pending");
+pub const PENDING_TRADES: Map<u64, TradeInfo> = Map::new("pending_trades"); // trade_id -> in-flight swap
+pub struct TradeIndexes<'a> {
+    pub in_denom: MultiIndex<'a, String, TradeRecord, u64>,
+    pub out_denom: MultiIndex<'a, String, TradeRecord, u64>,
+}
+
+impl IndexList<TradeRecord> for TradeIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<TradeRecord>> + '_> {
+        let v: Vec<&dyn Index<TradeRecord>> = vec![&self.in_denom, &self.out_denom];
+        Box::new(v.into_iter())
+    }
+}
+
+// Completed and failed trades, indexed by both sides of the swap
+pub const TRADE_HISTORY: IndexedMap<u64, TradeRecord, TradeIndexes> = IndexedMap::new(
+    "trade_history",
+    TradeIndexes {
+        in_denom: MultiIndex::new(
+            |_, trade| trade.in_coin.denom.clone(),
+            "trade_history",
+            "trade_history__in_denom",
+        ),
+        out_denom: MultiIndex::new(
+            |_, trade| trade.out_denom.clone(),
+            "trade_history",
+            "trade_history__out_denom",
+        ),
+    },
+);
+
+pub struct WithdrawRequestIndexes<'a> {
+    pub user: MultiIndex<'a, Addr, WithdrawRequest, u64>,
+}
+
+impl IndexList<WithdrawRequest> for WithdrawRequestIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<WithdrawRequest>> + '_> {
+        let v: Vec<&dyn Index<WithdrawRequest>> = vec![&self.user];
+        Box::new(v.into_iter())
+    }
+}
+
+// Queued withdrawals
+pub const WITHDRAW_REQUESTS: IndexedMap<u64, WithdrawRequest, WithdrawRequestIndexes> =
+    IndexedMap::new(
+        "withdraw_requests",
+        WithdrawRequestIndexes {
+            user: MultiIndex::new(
+                |_, request| request.user.clone(),
+                "withdraw_requests",
+                "withdraw_requests__user",
+            ),
+        },
+    );
+pub const WITHDRAW_QUEUE: Map<u64, ()> = Map::new("withdraw_queue"); // ids still pending settlement
+pub const WITHDRAW_REQUEST_ID_COUNTER: Item<u64> = Item::new("withdraw_request_id_counter");
+pub const WITHDRAWAL_QUEUE_ENABLED: Item<bool> = Item::new("withdrawal_queue_enabled");
+
+// Share price history
+pub const NAV_SNAPSHOTS: Map<u64, NavSnapshot> = Map::new("nav_snapshots"); // block time nanos -> snapshot
+pub const NAV_SNAPSHOT_COUNT: Item<u64> = Item::new("nav_snapshot_count");
+
+pub const MAX_SLIPPAGE_BPS: Item<u64> = Item::new("max_slippage_bps"); // vs. output implied by PRICES
+pub const TRADING_LIMITS: Item<TradingLimits> = Item::new("trading_limits");
+pub const TURNOVER_LOG: Deque<TurnoverEntry> = Deque::new("turnover_log"); // trades in the last 24h
+
+// Pausing
+pub const GUARDIAN: Item<Addr> = Item::new("guardian");
+pub const PAUSE_STATE: Item<PauseState> = Item::new("pause_state");
+
+// Price circuit breaker
+pub const PRICE_GUARDS: Map<String, u64> = Map::new("price_guards"); // denom -> max move in bps
+pub const PRICE_GUARD_ACTION: Item<PriceGuardAction> = Item::new("price_guard_action");
+
+// Fees
+pub const FEE_CONFIG: Item<FeeConfig> = Item::new("fee_config");
+pub const FEE_STATE: Item<FeeState> = Item::new("fee_state");
 
 // WAVS
 pub const SERVICE_MANAGER: Item<Addr> = Item::new("service-manager");
 pub const TRIGGER_MESSAGE: Map<Uint64, Vec<PriceInfo>> = Map::new("trigger-message");
 pub const SIGNATURE_DATA: Map<Uint64, WavsSignatureData> = Map::new("signature-data");
+pub const LAST_ENVELOPE_TIMESTAMP: Item<Timestamp> = Item::new("last-envelope-timestamp");
+pub const MAX_PAYLOAD_AGE_SECONDS: Item<u64> = Item::new("max-payload-age-seconds");
+pub const MAX_CLOCK_SKEW_SECONDS: Item<u64> = Item::new("max-clock-skew-seconds");
+
+pub fn decode_envelope(envelope: WavsEnvelope) -> cosmwasm_std::StdResult<Payload> {
+    let envelope = envelope.decode()?;
+    Ok(Payload::from_bytes(&envelope.payload)?)
+}
 
 pub fn save_envelope(
     storage: &mut dyn cosmwasm_std::Storage,
-    envelope: WavsEnvelope,
+    payload: &Payload,
     signature_data: WavsSignatureData,
-) -> cosmwasm_std::StdResult<Payload> {
-    let envelope = envelope.decode()?;
-    let payload = Payload::from_bytes(&envelope.payload)?;
-
+) -> cosmwasm_std::StdResult<()> {
     TRIGGER_MESSAGE.save(storage, payload.timestamp.nanos().into(), &payload.prices)?;
     SIGNATURE_DATA.save(storage, payload.timestamp.nanos().into(), &signature_data)?;
+    LAST_ENVELOPE_TIMESTAMP.save(storage, &payload.timestamp)?;
 
-    Ok(payload)
+    Ok(())
 }
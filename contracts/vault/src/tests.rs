@@ -1,14 +1,32 @@
-use cosmwasm_std::{coin, coins, Addr, Coin, Decimal256, Empty, Event, StdError, Uint128, Uint256};
-use cw_multi_test::{App, AppBuilder, AppResponse, Contract, ContractWrapper, Executor};
+use cosmwasm_std::testing::{message_info, mock_dependencies, mock_env, MockApi};
+use cosmwasm_std::{
+    coin, coins, to_json_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Decimal256, Deps, DepsMut,
+    Empty, Env, Event, MessageInfo, Reply, Response, StdError, StdResult, SubMsgResponse,
+    SubMsgResult, Timestamp, Uint128, Uint256,
+};
+use cw_multi_test::{
+    App, AppBuilder, AppResponse, BankSudo, Contract, ContractWrapper, Executor, SudoMsg,
+};
 use cw_ownable::Ownership;
 use std::str::FromStr;
 
 use crate::{
-    execute, instantiate,
-    msg::{ExecuteMsg, InstantiateMsg, PriceInfo, QueryMsg, VaultExecuteMsg, VaultQueryMsg},
-    query,
-    skip_entry::SwapRoute,
-    DepositRequest, DepositState,
+    error::ContractError,
+    execute, instantiate, migrate,
+    msg::{
+        AllocationWeight, AllowedPool, AssetDiscrepancy, DepositCaps, DepositorAllowlistResponse,
+        ExecuteMsg, InstantiateMsg, MigrateMsg, NavSnapshot, PauseState, PriceGuard,
+        PriceGuardAction, PriceGuardConfig, PriceInfo, PriceSourceId, QueryMsg,
+        ReconciliationReport, StrategyResponse, SurplusPolicy, SwapAllowlistResponse,
+        SwapVenueAllowlist, TargetAllocation, TradeRecord, TradeStatus, TradingLimits,
+        TradingLimitsResponse, VaultExecuteMsg, VaultQueryMsg, VaultState, WeightBound,
+        WhitelistEntry, WithdrawRequest, WithdrawState,
+    },
+    query, reply,
+    skip_entry::{
+        Asset as SkipAsset, ExecuteMsg as SkipExecuteMsg, SwapOperation, SwapRoute, WithdrawRoute,
+    },
+    state, token_factory, AccruedFees, DepositRequest, DepositState, EnvelopeStatus, FeeConfig,
 };
 
 const DENOM_ATOM: &str = "uatom";
@@ -161,18 +179,45 @@ pub const USER1: &str = "user1";
 pub const USER2: &str = "user2";
 
 pub fn vault_contract() -> Box<dyn Contract<Empty>> {
-    let contract = ContractWrapper::new(execute, instantiate, query);
+    let contract = ContractWrapper::new(execute, instantiate, query).with_reply(reply);
     Box::new(contract)
 }
 
+fn whitelist_entry(denom: &str) -> WhitelistEntry {
+    WhitelistEntry {
+        denom: denom.to_string(),
+        symbol: denom.trim_start_matches('u').to_uppercase(),
+        decimals: 0,
+        price_source_ids: vec![],
+        min_deposit: Uint256::zero(),
+        depositable: true,
+        tradable: true,
+    }
+}
+
+fn update_whitelist_entry(app: &mut App, vault_addr: &Addr, owner: &Addr, entry: WhitelistEntry) {
+    app.execute_contract(
+        owner.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::UpdateWhitelist {
+            to_add: Some(vec![entry]),
+            to_remove: None,
+        }),
+        &[],
+    )
+    .unwrap();
+}
+
 fn proper_instantiate() -> (App, Addr, TestAddrs) {
     let (mut app, addrs) = mock_app_with_addrs();
     let vault_code_id = app.store_code(vault_contract());
 
     let msg = InstantiateMsg {
         service_manager: addrs.service_manager.to_string(),
-        initial_whitelisted_denoms: vec![DENOM_ATOM.to_string(), DENOM_OSMO.to_string()],
+        initial_whitelist: vec![whitelist_entry(DENOM_ATOM), whitelist_entry(DENOM_OSMO)],
         skip_entry_point: addrs.skip_entry_point.to_string(),
+        share_subdenom: None,
+        target_allocation: None,
     };
 
     let vault_addr = app
@@ -189,8 +234,10 @@ fn test_instantiate() {
 
     let msg = InstantiateMsg {
         service_manager: addrs.service_manager.to_string(),
-        initial_whitelisted_denoms: vec![DENOM_ATOM.to_string(), DENOM_OSMO.to_string()],
+        initial_whitelist: vec![whitelist_entry(DENOM_ATOM), whitelist_entry(DENOM_OSMO)],
         skip_entry_point: addrs.skip_entry_point.to_string(),
+        share_subdenom: None,
+        target_allocation: None,
     };
 
     let vault_addr = app
@@ -1194,7 +1241,8 @@ fn test_price_volatility_updates() {
         .unwrap();
     assert_eq!(vault_value_after_drop, decimal(500));
 
-    // Withdrawal still returns the full 100 uatom even after price drop.
+    // Withdrawal still returns the 100 uatom (less the unit rounded in favor of the
+    // vault) even after price drop.
     let withdraw_msg = ExecuteMsg::Vault(VaultExecuteMsg::Withdraw {
         shares: initial_shares,
     });
@@ -1208,7 +1256,7 @@ fn test_price_volatility_updates() {
         balance_after.amount,
         balance_before
             .amount
-            .checked_add(Uint256::from(100u128))
+            .checked_add(Uint256::from(99u128))
             .expect("add amounts")
     );
 
@@ -1219,9 +1267,10 @@ fn test_price_volatility_updates() {
             &QueryMsg::Vault(VaultQueryMsg::GetVaultAssets {}),
         )
         .unwrap();
-    assert!(
-        final_vault_assets.iter().all(|coin| coin.amount.is_zero()),
-        "all vault asset balances should be zero after full withdrawal"
+    assert_eq!(
+        final_vault_assets,
+        coins(1, DENOM_ATOM),
+        "only the rounding dust held by the virtual shares should remain"
     );
 
     let final_total_shares: Uint256 = app
@@ -1240,7 +1289,7 @@ fn test_price_volatility_updates() {
             &QueryMsg::Vault(VaultQueryMsg::GetVaultValue {}),
         )
         .unwrap();
-    assert_eq!(final_vault_value, Decimal256::zero());
+    assert_eq!(final_vault_value, decimal(5));
 }
 
 #[test]
@@ -1371,7 +1420,7 @@ fn test_update_whitelist() {
 
     // Add new token to whitelist
     let add_msg = ExecuteMsg::Vault(VaultExecuteMsg::UpdateWhitelist {
-        to_add: Some(vec!["uwasm".to_string()]),
+        to_add: Some(vec![whitelist_entry("uwasm")]),
         to_remove: None,
     });
 
@@ -1425,7 +1474,7 @@ fn test_update_whitelist_unauthorized() {
 
     // Try to update whitelist as non-owner
     let update_msg = ExecuteMsg::Vault(VaultExecuteMsg::UpdateWhitelist {
-        to_add: Some(vec!["uwasm".to_string()]),
+        to_add: Some(vec![whitelist_entry("uwasm")]),
         to_remove: None,
     });
 
@@ -1612,6 +1661,11 @@ fn test_price_query() {
 #[test]
 fn test_high_precision_price_handling() {
     let (mut app, vault_addr, addrs) = proper_instantiate();
+    let atom = WhitelistEntry {
+        decimals: 18,
+        ..whitelist_entry(DENOM_ATOM)
+    };
+    update_whitelist_entry(&mut app, &vault_addr, &addrs.owner, atom);
 
     // Deposit a small amount that will be interpreted with 18 decimal places
     let high_precision_amount = Uint128::new(1000);
@@ -1628,7 +1682,7 @@ fn test_high_precision_price_handling() {
 
     // Update price with 18 decimal precision metadata
     let display_price = Decimal256::from_str("0.310581").unwrap();
-    execute_update_prices(
+    let res = execute_update_prices(
         &mut app,
         &vault_addr,
         vec![PriceInfo {
@@ -1652,7 +1706,12 @@ fn test_high_precision_price_handling() {
     assert_eq!(price_info.price_usd, display_price);
     assert_eq!(price_info.decimals, 18);
 
-    // Vault value should equal price * display amount (1.0)
+    // 1000 base units are worth about $3e-16, far below one share ($0.000001 at the
+    // initial price), so the deposit mints nothing and stays pending
+    assert!(find_event(&res.events, "deposit_processed").is_none());
+    let deposit_request = query_deposit_request(&app, &vault_addr, 1);
+    assert!(matches!(deposit_request.state, DepositState::Pending));
+    assert!(query_user_shares(&app, &vault_addr, &addrs.user1).is_zero());
     let vault_value: Decimal256 = app
         .wrap()
         .query_wasm_smart(
@@ -1660,12 +1719,19 @@ fn test_high_precision_price_handling() {
             &QueryMsg::Vault(VaultQueryMsg::GetVaultValue {}),
         )
         .unwrap();
-    let base_scale = Decimal256::from_ratio(
-        Uint256::from(high_precision_amount),
-        Uint256::from(1_000_000_000_000_000_000u128),
-    );
-    let expected_value = display_price.checked_mul(base_scale).unwrap();
-    assert_eq!(vault_value, expected_value);
+    assert!(vault_value.is_zero());
+
+    // The depositor gets it back by cancelling
+    let res = app
+        .execute_contract(
+            addrs.user1.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::CancelDeposit { deposit_id: 1 }),
+            &[],
+        )
+        .unwrap();
+    let event = find_event(&res.events, "deposit_cancelled").expect("deposit_cancelled missing");
+    assert_eq!(event_attr(event, "refund_uatom"), Some("1000"));
 }
 
 #[test]
@@ -1721,3 +1787,3698 @@ fn test_list_deposit_requests() {
     assert_eq!(deposits.len(), 1);
     assert_eq!(deposits[0].id, 2);
 }
+
+fn atom_price(price_usd: u128) -> Vec<PriceInfo> {
+    vec![PriceInfo {
+        denom: DENOM_ATOM.to_string(),
+        price_usd: decimal(price_usd),
+        decimals: 0,
+    }]
+}
+
+fn query_user_shares(app: &App, vault_addr: &Addr, user: &Addr) -> Uint256 {
+    app.wrap()
+        .query_wasm_smart(
+            vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetUserShares {
+                user: user.to_string(),
+            }),
+        )
+        .unwrap()
+}
+
+#[test]
+fn test_update_fee_config_validation() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+
+    let fee_config: FeeConfig = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetFeeConfig {}),
+        )
+        .unwrap();
+    assert_eq!(fee_config, FeeConfig::default());
+
+    let err = app
+        .execute_contract(
+            addrs.user1.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateFeeConfig {
+                management_fee_rate: Some(Decimal256::percent(2)),
+                performance_fee_rate: None,
+                fee_recipient: Some(addrs.user1.to_string()),
+            }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Caller is not the contract's current owner",
+    );
+
+    let err = app
+        .execute_contract(
+            addrs.owner.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateFeeConfig {
+                management_fee_rate: Some(Decimal256::percent(2)),
+                performance_fee_rate: None,
+                fee_recipient: None,
+            }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Fee recipient must be set when fees are enabled",
+    );
+
+    let err = app
+        .execute_contract(
+            addrs.owner.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateFeeConfig {
+                management_fee_rate: None,
+                performance_fee_rate: Some(Decimal256::percent(60)),
+                fee_recipient: Some(addrs.owner.to_string()),
+            }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Fee rate 0.6 exceeds maximum of 0.5",
+    );
+
+    let res = app
+        .execute_contract(
+            addrs.owner.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateFeeConfig {
+                management_fee_rate: Some(Decimal256::percent(2)),
+                performance_fee_rate: Some(Decimal256::percent(20)),
+                fee_recipient: Some(addrs.owner.to_string()),
+            }),
+            &[],
+        )
+        .unwrap();
+    let event = find_event(&res.events, "fee_config_updated").expect("fee config event missing");
+    assert_eq!(event_attr(event, "management_fee_rate"), Some("0.02"));
+    assert_eq!(event_attr(event, "performance_fee_rate"), Some("0.2"));
+    assert_eq!(
+        event_attr(event, "fee_recipient"),
+        Some(addrs.owner.as_str())
+    );
+}
+
+#[test]
+fn test_management_fee_accrues_over_time() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+
+    app.execute_contract(
+        addrs.owner.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::UpdateFeeConfig {
+            management_fee_rate: Some(Decimal256::percent(2)),
+            performance_fee_rate: None,
+            fee_recipient: Some(addrs.owner.to_string()),
+        }),
+        &[],
+    )
+    .unwrap();
+
+    app.update_block(|block| {
+        block.time = block.time.plus_seconds(execute::SECONDS_PER_YEAR);
+    });
+
+    let accrued: AccruedFees = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetAccruedFees {}),
+        )
+        .unwrap();
+    assert_eq!(accrued.pending_management_fee_usd, decimal(20));
+    assert_eq!(accrued.total_management_fee_shares, Uint256::zero());
+
+    // Fees can't be settled against a year-old vault value
+    let err = app
+        .execute_contract(
+            addrs.owner.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateFeeConfig {
+                management_fee_rate: Some(Decimal256::percent(1)),
+                performance_fee_rate: None,
+                fee_recipient: None,
+            }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Fees can only be settled against prices updated within the last 3600s",
+    );
+
+    let res = execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+
+    // $20 of a $1000 vault at the virtual share price:
+    // (1e9 + 1000) * 20 / (1000.001 - 20) shares, rounded down
+    let expected_fee_shares = Uint256::from(20_408_162u128);
+    let fee_event = find_event(&res.events, "fees_accrued").expect("fees_accrued event missing");
+    assert_eq!(
+        event_attr(fee_event, "management_fee_shares"),
+        Some(expected_fee_shares.to_string().as_str())
+    );
+    assert_eq!(event_attr(fee_event, "performance_fee_shares"), Some("0"));
+    assert_eq!(
+        query_user_shares(&app, &vault_addr, &addrs.owner),
+        expected_fee_shares
+    );
+
+    let accrued: AccruedFees = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetAccruedFees {}),
+        )
+        .unwrap();
+    assert_eq!(accrued.total_management_fee_shares, expected_fee_shares);
+    assert_eq!(accrued.pending_management_fee_usd, Decimal256::zero());
+    assert_eq!(accrued.fee_recipient, Some(addrs.owner.clone()));
+}
+
+#[test]
+fn test_performance_fee_respects_high_water_mark() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+
+    app.execute_contract(
+        addrs.owner.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::UpdateFeeConfig {
+            management_fee_rate: None,
+            performance_fee_rate: Some(Decimal256::percent(20)),
+            fee_recipient: Some(addrs.owner.to_string()),
+        }),
+        &[],
+    )
+    .unwrap();
+
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+
+    let accrued: AccruedFees = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetAccruedFees {}),
+        )
+        .unwrap();
+    assert_eq!(
+        accrued.high_water_mark,
+        Decimal256::from_str("0.000001").unwrap()
+    );
+
+    // Price doubles: a $1000 gain less the virtual assets' share of it, 20% of which is
+    // paid as (1e9 + 1000) * 199.9998 / (2000.001 - 199.9998) shares
+    let res = execute_update_prices(&mut app, &vault_addr, atom_price(20), None);
+    let expected_fee_shares = Uint256::from(111_111_037u128);
+    let fee_event = find_event(&res.events, "fees_accrued").expect("fees_accrued event missing");
+    assert_eq!(
+        event_attr(fee_event, "performance_fee_shares"),
+        Some(expected_fee_shares.to_string().as_str())
+    );
+    assert_eq!(
+        query_user_shares(&app, &vault_addr, &addrs.owner),
+        expected_fee_shares
+    );
+
+    // Drawdown and recovery back to the mark charge nothing
+    let res = execute_update_prices(&mut app, &vault_addr, atom_price(15), None);
+    assert!(find_event(&res.events, "fees_accrued").is_none());
+    let res = execute_update_prices(&mut app, &vault_addr, atom_price(20), None);
+    assert!(find_event(&res.events, "fees_accrued").is_none());
+    assert_eq!(
+        query_user_shares(&app, &vault_addr, &addrs.owner),
+        expected_fee_shares
+    );
+
+    let accrued: AccruedFees = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetAccruedFees {}),
+        )
+        .unwrap();
+    assert_eq!(accrued.total_performance_fee_shares, expected_fee_shares);
+    assert_eq!(accrued.total_management_fee_shares, Uint256::zero());
+}
+
+#[test]
+fn test_envelope_timestamp_replay_and_staleness() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    state::MAX_PAYLOAD_AGE_SECONDS
+        .save(deps.as_mut().storage, &600)
+        .unwrap();
+    state::MAX_CLOCK_SKEW_SECONDS
+        .save(deps.as_mut().storage, &60)
+        .unwrap();
+
+    let first = env.block.time.minus_seconds(60);
+    execute::validate_envelope_timestamp(deps.as_ref().storage, &env, first).unwrap();
+    state::LAST_ENVELOPE_TIMESTAMP
+        .save(deps.as_mut().storage, &first)
+        .unwrap();
+
+    // Replaying the same envelope, or an earlier one, is rejected
+    let err = execute::validate_envelope_timestamp(deps.as_ref().storage, &env, first).unwrap_err();
+    assert!(matches!(err, ContractError::StaleEnvelope { .. }));
+    let err =
+        execute::validate_envelope_timestamp(deps.as_ref().storage, &env, first.minus_seconds(1))
+            .unwrap_err();
+    assert!(matches!(err, ContractError::StaleEnvelope { .. }));
+
+    // A newer envelope is accepted until it exceeds the maximum age
+    let second = first.plus_seconds(30);
+    execute::validate_envelope_timestamp(deps.as_ref().storage, &env, second).unwrap();
+    env.block.time = second.plus_seconds(601);
+    let err =
+        execute::validate_envelope_timestamp(deps.as_ref().storage, &env, second).unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::EnvelopeTooOld {
+            max_age_seconds: 600,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_envelope_timestamp_clock_skew() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    state::MAX_PAYLOAD_AGE_SECONDS
+        .save(deps.as_mut().storage, &600)
+        .unwrap();
+    state::MAX_CLOCK_SKEW_SECONDS
+        .save(deps.as_mut().storage, &60)
+        .unwrap();
+
+    // A fast operator clock is tolerated up to the skew
+    let ahead = env.block.time.plus_seconds(60);
+    execute::validate_envelope_timestamp(deps.as_ref().storage, &env, ahead).unwrap();
+
+    // Anything further out would lock out honest envelopes, so it never gets stored
+    let far_future = env.block.time.plus_seconds(61);
+    let err =
+        execute::validate_envelope_timestamp(deps.as_ref().storage, &env, far_future).unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::EnvelopeFromFuture {
+            max_skew_seconds: 60,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_update_max_payload_age() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+
+    let status: EnvelopeStatus = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetEnvelopeStatus {}),
+        )
+        .unwrap();
+    assert_eq!(status.last_accepted_timestamp, None::<Timestamp>);
+    assert_eq!(status.max_payload_age_seconds, 3600);
+    assert_eq!(status.max_clock_skew_seconds, 60);
+
+    let err = app
+        .execute_contract(
+            addrs.user1.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateMaxPayloadAge {
+                max_age_seconds: 60,
+            }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Caller is not the contract's current owner",
+    );
+
+    let err = app
+        .execute_contract(
+            addrs.owner.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateMaxPayloadAge { max_age_seconds: 0 }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Maximum payload age must be greater than zero",
+    );
+
+    app.execute_contract(
+        addrs.owner.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::UpdateMaxPayloadAge {
+            max_age_seconds: 60,
+        }),
+        &[],
+    )
+    .unwrap();
+
+    let status: EnvelopeStatus = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetEnvelopeStatus {}),
+        )
+        .unwrap();
+    assert_eq!(status.max_payload_age_seconds, 60);
+
+    app.execute_contract(
+        addrs.owner.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::UpdateMaxClockSkew {
+            max_skew_seconds: 5,
+        }),
+        &[],
+    )
+    .unwrap();
+    let status: EnvelopeStatus = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetEnvelopeStatus {}),
+        )
+        .unwrap();
+    assert_eq!(status.max_clock_skew_seconds, 5);
+}
+
+fn set_atom_price_guard(
+    app: &mut App,
+    vault_addr: &Addr,
+    owner: &Addr,
+    max_deviation_bps: u64,
+    action: PriceGuardAction,
+) {
+    app.execute_contract(
+        owner.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::UpdatePriceGuards {
+            to_set: Some(vec![PriceGuard {
+                denom: DENOM_ATOM.to_string(),
+                max_deviation_bps,
+            }]),
+            to_remove: None,
+            action: Some(action),
+        }),
+        &[],
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_update_price_guards() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+
+    let config: PriceGuardConfig = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetPriceGuards {}),
+        )
+        .unwrap();
+    assert_eq!(config.action, PriceGuardAction::Reject);
+    assert!(config.guards.is_empty());
+
+    let update_msg = ExecuteMsg::Vault(VaultExecuteMsg::UpdatePriceGuards {
+        to_set: Some(vec![PriceGuard {
+            denom: DENOM_ATOM.to_string(),
+            max_deviation_bps: 1_000,
+        }]),
+        to_remove: None,
+        action: None,
+    });
+    let err = app
+        .execute_contract(addrs.user1.clone(), vault_addr.clone(), &update_msg, &[])
+        .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Caller is not the contract's current owner",
+    );
+
+    let err = app
+        .execute_contract(
+            addrs.owner.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdatePriceGuards {
+                to_set: Some(vec![PriceGuard {
+                    denom: DENOM_ATOM.to_string(),
+                    max_deviation_bps: 0,
+                }]),
+                to_remove: None,
+                action: None,
+            }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Price guard for uatom must allow a non-zero deviation",
+    );
+
+    set_atom_price_guard(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        1_000,
+        PriceGuardAction::HoldDeposits,
+    );
+    let config: PriceGuardConfig = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetPriceGuards {}),
+        )
+        .unwrap();
+    assert_eq!(config.action, PriceGuardAction::HoldDeposits);
+    assert_eq!(
+        config.guards,
+        vec![PriceGuard {
+            denom: DENOM_ATOM.to_string(),
+            max_deviation_bps: 1_000,
+        }]
+    );
+
+    app.execute_contract(
+        addrs.owner.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::UpdatePriceGuards {
+            to_set: None,
+            to_remove: Some(vec![DENOM_ATOM.to_string()]),
+            action: None,
+        }),
+        &[],
+    )
+    .unwrap();
+    let config: PriceGuardConfig = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetPriceGuards {}),
+        )
+        .unwrap();
+    assert!(config.guards.is_empty());
+}
+
+#[test]
+fn test_price_guard_rejects_large_move() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    set_atom_price_guard(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        1_000,
+        PriceGuardAction::Reject,
+    );
+
+    // The first price has nothing to compare against
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+
+    // A 10% move is exactly at the limit
+    execute_update_prices(&mut app, &vault_addr, atom_price(11), None);
+
+    let err = app
+        .execute_contract(
+            vault_addr.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdatePrices {
+                prices: atom_price(22),
+                swap_routes: None,
+            }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Price for uatom moved 10000 bps, exceeding the limit of 1000 bps",
+    );
+
+    let price: PriceInfo = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetPrice {
+                denom: DENOM_ATOM.to_string(),
+            }),
+        )
+        .unwrap();
+    assert_eq!(price.price_usd, decimal(11));
+}
+
+#[test]
+fn test_price_guard_holds_pending_deposits() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    set_atom_price_guard(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        1_000,
+        PriceGuardAction::HoldDeposits,
+    );
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+
+    let res = execute_update_prices(&mut app, &vault_addr, atom_price(5), None);
+    let rejected = find_event(&res.events, "price_rejected").expect("price_rejected event missing");
+    assert_eq!(event_attr(rejected, "denom"), Some(DENOM_ATOM));
+    assert_eq!(event_attr(rejected, "deviation_bps"), Some("5000"));
+    assert_eq!(event_attr(rejected, "max_deviation_bps"), Some("1000"));
+
+    // The stored price is kept and the deposit waits for an accepted price
+    let price: PriceInfo = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetPrice {
+                denom: DENOM_ATOM.to_string(),
+            }),
+        )
+        .unwrap();
+    assert_eq!(price.price_usd, decimal(10));
+    let deposit_request: DepositRequest = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetDepositRequest { deposit_id: 1 }),
+        )
+        .unwrap();
+    assert!(matches!(deposit_request.state, DepositState::Pending));
+
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+    let deposit_request: DepositRequest = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetDepositRequest { deposit_id: 1 }),
+        )
+        .unwrap();
+    assert!(matches!(
+        deposit_request.state,
+        DepositState::Completed { .. }
+    ));
+}
+
+#[test]
+fn test_price_guard_holds_fee_accrual() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    set_atom_price_guard(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        1_000,
+        PriceGuardAction::HoldDeposits,
+    );
+    app.execute_contract(
+        addrs.owner.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::UpdateFeeConfig {
+            management_fee_rate: None,
+            performance_fee_rate: Some(Decimal256::percent(20)),
+            fee_recipient: Some(addrs.owner.to_string()),
+        }),
+        &[],
+    )
+    .unwrap();
+
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &[coin(100, DENOM_ATOM), coin(100, DENOM_OSMO)],
+    )
+    .unwrap();
+    let prices = |atom: u128, osmo: u128| {
+        let mut prices = atom_price(atom);
+        prices.push(PriceInfo {
+            denom: DENOM_OSMO.to_string(),
+            price_usd: decimal(osmo),
+            decimals: 0,
+        });
+        prices
+    };
+    execute_update_prices(&mut app, &vault_addr, prices(10, 10), None);
+
+    // ATOM halving is rejected. Valued at the stale ATOM price the vault would show a
+    // $500 gain and pay a performance fee on it, though it really hasn't moved.
+    let res = execute_update_prices(&mut app, &vault_addr, prices(5, 15), None);
+    assert!(find_event(&res.events, "price_rejected").is_some());
+    assert!(find_event(&res.events, "fees_accrued").is_none());
+    assert!(query_user_shares(&app, &vault_addr, &addrs.owner).is_zero());
+
+    let accrued: AccruedFees = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetAccruedFees {}),
+        )
+        .unwrap();
+    // Still the share price the vault started at
+    assert_eq!(
+        accrued.high_water_mark,
+        Decimal256::from_str("0.000001").unwrap()
+    );
+}
+
+fn execute_vault_msg(
+    app: &mut App,
+    vault_addr: &Addr,
+    sender: &Addr,
+    msg: VaultExecuteMsg,
+) -> StdResult<AppResponse> {
+    app.execute_contract(
+        sender.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(msg),
+        &[],
+    )
+}
+
+fn query_pause_state(app: &App, vault_addr: &Addr) -> PauseState {
+    app.wrap()
+        .query_wasm_smart(
+            vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetPauseState {}),
+        )
+        .unwrap()
+}
+
+#[test]
+fn test_guardian_can_pause_but_not_unpause() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    let pause_deposits = VaultExecuteMsg::UpdatePauseState {
+        deposits: Some(true),
+        price_processing: None,
+        trading: None,
+        emergency_mode: None,
+    };
+
+    // Without a guardian only the owner may pause
+    let err =
+        execute_vault_msg(&mut app, &vault_addr, &addrs.user2, pause_deposits.clone()).unwrap_err();
+    assert_error_line(&err, "kind: Other, error: Unauthorized");
+
+    let err = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        VaultExecuteMsg::UpdateGuardian {
+            guardian: Some(addrs.user2.to_string()),
+        },
+    )
+    .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Caller is not the contract's current owner",
+    );
+
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::UpdateGuardian {
+            guardian: Some(addrs.user2.to_string()),
+        },
+    )
+    .unwrap();
+    let guardian: Option<Addr> = app
+        .wrap()
+        .query_wasm_smart(&vault_addr, &QueryMsg::Vault(VaultQueryMsg::GetGuardian {}))
+        .unwrap();
+    assert_eq!(guardian, Some(addrs.user2.clone()));
+
+    execute_vault_msg(&mut app, &vault_addr, &addrs.user2, pause_deposits).unwrap();
+    assert!(query_pause_state(&app, &vault_addr).deposits_paused);
+
+    let unpause_deposits = VaultExecuteMsg::UpdatePauseState {
+        deposits: Some(false),
+        price_processing: None,
+        trading: None,
+        emergency_mode: None,
+    };
+    let err = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.user2,
+        unpause_deposits.clone(),
+    )
+    .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Only the owner can unpause the vault",
+    );
+
+    execute_vault_msg(&mut app, &vault_addr, &addrs.owner, unpause_deposits).unwrap();
+    assert_eq!(query_pause_state(&app, &vault_addr), PauseState::default());
+}
+
+#[test]
+fn test_paused_deposits_and_trading() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::UpdatePauseState {
+            deposits: Some(true),
+            price_processing: None,
+            trading: Some(true),
+            emergency_mode: None,
+        },
+    )
+    .unwrap();
+
+    let err = app
+        .execute_contract(
+            addrs.user1.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+            &coins(100, DENOM_ATOM),
+        )
+        .unwrap_err();
+    assert_error_line(&err, "kind: Other, error: Deposits are paused");
+
+    // Prices still update while trading is paused, but routes are dropped
+    let route = SwapRoute {
+        swap_venue_name: "neutron-astroport".to_string(),
+        offer_denom: DENOM_ATOM.to_string(),
+        ask_denom: DENOM_OSMO.to_string(),
+        amount_in: Uint128::new(10),
+        estimated_amount_out: Uint128::new(10),
+        minimum_amount_out: None,
+        timeout: Timestamp::from_seconds(0),
+        operations: vec![SwapOperation {
+            pool: "pool-1".to_string(),
+            denom_in: DENOM_ATOM.to_string(),
+            denom_out: DENOM_OSMO.to_string(),
+            interface: None,
+        }],
+    };
+    let res = execute_update_prices(&mut app, &vault_addr, atom_price(10), Some(vec![route]));
+    let skipped =
+        find_event(&res.events, "rebalancing_skipped").expect("rebalancing_skipped event missing");
+    assert_eq!(event_attr(skipped, "reason"), Some("trading_paused"));
+    assert!(find_event(&res.events, "rebalancing_initiated").is_none());
+
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::UpdatePauseState {
+            deposits: None,
+            price_processing: Some(true),
+            trading: None,
+            emergency_mode: None,
+        },
+    )
+    .unwrap();
+    let err = app
+        .execute_contract(
+            vault_addr.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdatePrices {
+                prices: atom_price(10),
+                swap_routes: None,
+            }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(&err, "kind: Other, error: Price processing is paused");
+}
+
+#[test]
+fn test_emergency_mode_only_allows_withdraw() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::UpdateGuardian {
+            guardian: Some(addrs.user2.to_string()),
+        },
+    )
+    .unwrap();
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.user2,
+        VaultExecuteMsg::UpdatePauseState {
+            deposits: None,
+            price_processing: None,
+            trading: None,
+            emergency_mode: Some(true),
+        },
+    )
+    .unwrap();
+
+    let err = app
+        .execute_contract(
+            addrs.user1.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+            &coins(100, DENOM_ATOM),
+        )
+        .unwrap_err();
+    let emergency_line =
+        "kind: Other, error: Vault is in emergency mode, only withdrawals are allowed";
+    assert_error_line(&err, emergency_line);
+
+    let err = app
+        .execute_contract(
+            vault_addr.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdatePrices {
+                prices: atom_price(10),
+                swap_routes: None,
+            }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(&err, emergency_line);
+
+    let err = app
+        .execute_contract(
+            addrs.owner.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::ManualTrigger {}),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(&err, emergency_line);
+
+    let shares = query_user_shares(&app, &vault_addr, &addrs.user1);
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Withdraw { shares }),
+        &[],
+    )
+    .unwrap();
+    let balance = app.wrap().query_balance(&addrs.user1, DENOM_ATOM).unwrap();
+    assert_eq!(balance.amount, Uint256::from(999u128));
+}
+
+fn query_deposit_request(app: &App, vault_addr: &Addr, deposit_id: u64) -> DepositRequest {
+    app.wrap()
+        .query_wasm_smart(
+            vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetDepositRequest { deposit_id }),
+        )
+        .unwrap()
+}
+
+#[test]
+fn test_cancel_deposit_refunds_pending_coins() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &[coin(100, DENOM_ATOM), coin(50, DENOM_OSMO)],
+    )
+    .unwrap();
+
+    let cancel_msg = ExecuteMsg::Vault(VaultExecuteMsg::CancelDeposit { deposit_id: 1 });
+    let err = app
+        .execute_contract(addrs.user2.clone(), vault_addr.clone(), &cancel_msg, &[])
+        .unwrap_err();
+    assert_error_line(&err, "kind: Other, error: Unauthorized");
+
+    let res = app
+        .execute_contract(addrs.user1.clone(), vault_addr.clone(), &cancel_msg, &[])
+        .unwrap();
+    let event = find_event(&res.events, "deposit_cancelled").expect("deposit_cancelled missing");
+    assert_eq!(event_attr(event, "refund_uatom"), Some("100"));
+    assert_eq!(event_attr(event, "refund_uosmo"), Some("50"));
+
+    let deposit_request = query_deposit_request(&app, &vault_addr, 1);
+    assert!(matches!(deposit_request.state, DepositState::Cancelled));
+    let pending: Vec<Coin> = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetTotalPendingAssets {}),
+        )
+        .unwrap();
+    assert!(pending.is_empty());
+    let balance = app.wrap().query_balance(&addrs.user1, DENOM_ATOM).unwrap();
+    assert_eq!(balance.amount, Uint256::from(1000u128));
+    let balance = app.wrap().query_balance(&addrs.user1, DENOM_OSMO).unwrap();
+    assert_eq!(balance.amount, Uint256::from(1000u128));
+
+    let err = app
+        .execute_contract(addrs.user1.clone(), vault_addr.clone(), &cancel_msg, &[])
+        .unwrap_err();
+    assert_error_line(&err, "kind: Other, error: Deposit already cancelled: 1");
+
+    // Cancelled requests are never turned into shares
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+    assert!(matches!(
+        query_deposit_request(&app, &vault_addr, 1).state,
+        DepositState::Cancelled
+    ));
+    assert_eq!(
+        query_user_shares(&app, &vault_addr, &addrs.user1),
+        Uint256::zero()
+    );
+}
+
+#[test]
+fn test_expired_deposit_can_be_reclaimed_by_anyone() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+
+    let err = app
+        .execute_contract(
+            addrs.owner.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateDepositExpiry {
+                expiry_seconds: Some(0),
+            }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Deposit expiry must be greater than zero",
+    );
+
+    app.execute_contract(
+        addrs.owner.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::UpdateDepositExpiry {
+            expiry_seconds: Some(3600),
+        }),
+        &[],
+    )
+    .unwrap();
+    let expiry: Option<u64> = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetDepositExpiry {}),
+        )
+        .unwrap();
+    assert_eq!(expiry, Some(3600));
+
+    // No price ever arrives for osmo, so the deposit cannot be processed
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(50, DENOM_OSMO),
+    )
+    .unwrap();
+    let created_at = app.block_info().time;
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+    assert!(matches!(
+        query_deposit_request(&app, &vault_addr, 1).state,
+        DepositState::Pending
+    ));
+
+    let cancel_msg = ExecuteMsg::Vault(VaultExecuteMsg::CancelDeposit { deposit_id: 1 });
+    let err = app
+        .execute_contract(addrs.user2.clone(), vault_addr.clone(), &cancel_msg, &[])
+        .unwrap_err();
+    let expected_line = format!(
+        "kind: Other, error: Deposit 1 can only be reclaimed by its owner before {}",
+        created_at.plus_seconds(3600)
+    );
+    assert_error_line(&err, &expected_line);
+
+    app.update_block(|block| block.time = block.time.plus_seconds(3600));
+    app.execute_contract(addrs.user2.clone(), vault_addr.clone(), &cancel_msg, &[])
+        .unwrap();
+
+    // Refund goes to the depositor, not the caller
+    let balance = app.wrap().query_balance(&addrs.user1, DENOM_OSMO).unwrap();
+    assert_eq!(balance.amount, Uint256::from(1000u128));
+    let balance = app.wrap().query_balance(&addrs.user2, DENOM_OSMO).unwrap();
+    assert_eq!(balance.amount, Uint256::from(1000u128));
+}
+
+// Swaps 1:1 and fails when the requested minimum exceeds the offered amount
+fn mock_skip_execute(
+    _deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: SkipExecuteMsg,
+) -> StdResult<Response> {
+    let SkipExecuteMsg::SwapAndAction {
+        min_asset: SkipAsset::Native(min_asset),
+        post_swap_action,
+        ..
+    } = msg;
+    let crate::skip_entry::Action::Transfer { to_address } = post_swap_action;
+
+    let offered = info.funds[0].amount;
+    if min_asset.amount > offered {
+        return Err(StdError::msg("slippage exceeded"));
+    }
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address,
+            amount: vec![Coin {
+                denom: min_asset.denom.clone(),
+                amount: offered,
+            }],
+        })
+        .add_attribute("post_swap_action_amount_out", offered.to_string())
+        .add_attribute("post_swap_action_denom_out", min_asset.denom))
+}
+
+fn mock_skip_contract() -> Box<dyn Contract<Empty>> {
+    let contract = ContractWrapper::new(
+        mock_skip_execute,
+        |_: DepsMut, _: Env, _: MessageInfo, _: Empty| -> StdResult<Response> {
+            Ok(Response::new())
+        },
+        |_: Deps, _: Env, _: Empty| -> StdResult<Binary> { to_json_binary(&Empty {}) },
+    );
+    Box::new(contract)
+}
+
+fn instantiate_with_mock_skip() -> (App, Addr, TestAddrs) {
+    let (mut app, addrs) = mock_app_with_addrs();
+    let skip_code_id = app.store_code(mock_skip_contract());
+    let skip_addr = app
+        .instantiate_contract(
+            skip_code_id,
+            addrs.owner.clone(),
+            &Empty {},
+            &[],
+            "Skip",
+            None,
+        )
+        .unwrap();
+    app.sudo(SudoMsg::Bank(BankSudo::Mint {
+        to_address: skip_addr.to_string(),
+        amount: vec![coin(10_000, DENOM_ATOM), coin(10_000, DENOM_OSMO)],
+    }))
+    .unwrap();
+
+    let vault_code_id = app.store_code(vault_contract());
+    let msg = InstantiateMsg {
+        service_manager: addrs.service_manager.to_string(),
+        initial_whitelist: vec![whitelist_entry(DENOM_ATOM), whitelist_entry(DENOM_OSMO)],
+        skip_entry_point: skip_addr.to_string(),
+        share_subdenom: None,
+        target_allocation: None,
+    };
+    let vault_addr = app
+        .instantiate_contract(vault_code_id, addrs.owner.clone(), &msg, &[], "Vault", None)
+        .unwrap();
+
+    (app, vault_addr, addrs)
+}
+
+fn atom_to_osmo_route(app: &App, amount_in: u128, minimum_amount_out: u128) -> SwapRoute {
+    SwapRoute {
+        swap_venue_name: "neutron-astroport".to_string(),
+        offer_denom: DENOM_ATOM.to_string(),
+        ask_denom: DENOM_OSMO.to_string(),
+        amount_in: Uint128::new(amount_in),
+        estimated_amount_out: Uint128::new(amount_in),
+        minimum_amount_out: Some(Uint128::new(minimum_amount_out)),
+        timeout: app.block_info().time.plus_seconds(60),
+        operations: vec![SwapOperation {
+            pool: "pool-1".to_string(),
+            denom_in: DENOM_ATOM.to_string(),
+            denom_out: DENOM_OSMO.to_string(),
+            interface: None,
+        }],
+    }
+}
+
+#[test]
+fn test_failed_swap_leaves_vault_assets_untouched() {
+    let (mut app, vault_addr, addrs) = instantiate_with_mock_skip();
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+
+    let prices = vec![
+        PriceInfo {
+            denom: DENOM_ATOM.to_string(),
+            price_usd: decimal(10),
+            decimals: 0,
+        },
+        PriceInfo {
+            denom: DENOM_OSMO.to_string(),
+            price_usd: decimal(10),
+            decimals: 0,
+        },
+    ];
+    let routes = vec![
+        atom_to_osmo_route(&app, 30, 30),
+        atom_to_osmo_route(&app, 20, 25),
+    ];
+    let res = execute_update_prices(&mut app, &vault_addr, prices, Some(routes));
+
+    let completed = find_event(&res.events, "trade_completed").expect("trade_completed missing");
+    assert_eq!(event_attr(completed, "trade_id"), Some("1"));
+    assert_eq!(event_attr(completed, "out_amount"), Some("30"));
+
+    let failed = find_event(&res.events, "trade_failed").expect("trade_failed missing");
+    assert_eq!(event_attr(failed, "trade_id"), Some("2"));
+    assert_eq!(event_attr(failed, "in_amount"), Some("20"));
+    assert!(event_attr(failed, "error")
+        .unwrap()
+        .contains("slippage exceeded"));
+
+    let finalized = find_event(&res.events, "trade_finalized").expect("trade_finalized missing");
+    assert_eq!(event_attr(finalized, "vault_value_usd"), Some("1000"));
+
+    // Only the successful swap moved assets
+    let atom_balance: Uint256 = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetVaultAssetBalance {
+                denom: DENOM_ATOM.to_string(),
+            }),
+        )
+        .unwrap();
+    assert_eq!(atom_balance, Uint256::from(70u128));
+    let osmo_balance: Uint256 = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetVaultAssetBalance {
+                denom: DENOM_OSMO.to_string(),
+            }),
+        )
+        .unwrap();
+    assert_eq!(osmo_balance, Uint256::from(30u128));
+    let bank_atom = app.wrap().query_balance(&vault_addr, DENOM_ATOM).unwrap();
+    assert_eq!(bank_atom.amount, Uint256::from(70u128));
+}
+
+#[test]
+fn test_swap_routes_exceeding_max_slippage_are_skipped() {
+    let (mut app, vault_addr, addrs) = instantiate_with_mock_skip();
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+
+    let max_slippage: u64 = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetMaxSlippage {}),
+        )
+        .unwrap();
+    assert_eq!(max_slippage, 300);
+
+    let err = app
+        .execute_contract(
+            addrs.owner.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateMaxSlippage {
+                max_slippage_bps: 10_001,
+            }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Maximum slippage of 10001 bps exceeds 10000 bps",
+    );
+
+    // No osmo price yet, so the route cannot be verified
+    let route = atom_to_osmo_route(&app, 30, 30);
+    let res = execute_update_prices(&mut app, &vault_addr, atom_price(10), Some(vec![route]));
+    let rejected =
+        find_event(&res.events, "swap_route_rejected").expect("swap_route_rejected missing");
+    assert_eq!(event_attr(rejected, "reason"), Some("missing_price"));
+    assert!(find_event(&res.events, "trade_initiated").is_none());
+
+    // At equal prices 20 uatom is worth 20 uosmo; a minimum of 10 implies 50% slippage
+    let prices = vec![
+        PriceInfo {
+            denom: DENOM_ATOM.to_string(),
+            price_usd: decimal(10),
+            decimals: 0,
+        },
+        PriceInfo {
+            denom: DENOM_OSMO.to_string(),
+            price_usd: decimal(10),
+            decimals: 0,
+        },
+    ];
+    let routes = vec![
+        atom_to_osmo_route(&app, 20, 10),
+        atom_to_osmo_route(&app, 30, 30),
+    ];
+    let res = execute_update_prices(&mut app, &vault_addr, prices, Some(routes));
+
+    let rejected =
+        find_event(&res.events, "swap_route_rejected").expect("swap_route_rejected missing");
+    assert_eq!(event_attr(rejected, "route_index"), Some("0"));
+    assert_eq!(event_attr(rejected, "reason"), Some("slippage_exceeded"));
+    assert_eq!(event_attr(rejected, "slippage_bps"), Some("5000"));
+    assert_eq!(event_attr(rejected, "max_slippage_bps"), Some("300"));
+
+    let completed = find_event(&res.events, "trade_completed").expect("trade_completed missing");
+    assert_eq!(event_attr(completed, "in_amount"), Some("30"));
+    let bank_atom = app.wrap().query_balance(&vault_addr, DENOM_ATOM).unwrap();
+    assert_eq!(bank_atom.amount, Uint256::from(70u128));
+}
+
+#[test]
+fn test_expired_and_zero_amount_routes_are_rejected_alone() {
+    let (mut app, vault_addr, addrs) = instantiate_with_mock_skip();
+    deposit_atom(&mut app, &vault_addr, &addrs.user1, 100).unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), None);
+    deposit_atom(&mut app, &vault_addr, &addrs.user2, 50).unwrap();
+
+    let mut expired = atom_to_osmo_route(&app, 20, 20);
+    expired.timeout = app.block_info().time;
+    let empty = atom_to_osmo_route(&app, 0, 0);
+    let routes = vec![expired, empty, atom_to_osmo_route(&app, 30, 30)];
+    let res = execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), Some(routes));
+
+    let rejected: Vec<_> = res
+        .events
+        .iter()
+        .filter(|event| event_matches(event, "swap_route_rejected"))
+        .collect();
+    assert_eq!(rejected.len(), 2);
+    assert_eq!(event_attr(rejected[0], "route_index"), Some("0"));
+    assert_eq!(event_attr(rejected[0], "reason"), Some("expired"));
+    assert_eq!(
+        event_attr(rejected[0], "error"),
+        Some("Swap route has already expired")
+    );
+    assert_eq!(event_attr(rejected[1], "route_index"), Some("1"));
+    assert_eq!(event_attr(rejected[1], "reason"), Some("zero_amount"));
+
+    // The rest of the update still lands
+    assert!(find_event(&res.events, "price_updated").is_some());
+    assert!(find_event(&res.events, "deposit_processed").is_some());
+    let completed = find_event(&res.events, "trade_completed").expect("trade_completed missing");
+    assert_eq!(event_attr(completed, "in_amount"), Some("30"));
+}
+
+fn atom_and_osmo_prices() -> Vec<PriceInfo> {
+    vec![
+        PriceInfo {
+            denom: DENOM_ATOM.to_string(),
+            price_usd: decimal(10),
+            decimals: 0,
+        },
+        PriceInfo {
+            denom: DENOM_OSMO.to_string(),
+            price_usd: decimal(10),
+            decimals: 0,
+        },
+    ]
+}
+
+fn set_trading_limits(app: &mut App, vault_addr: &Addr, owner: &Addr, limits: TradingLimits) {
+    app.execute_contract(
+        owner.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::UpdateTradingLimits { limits }),
+        &[],
+    )
+    .unwrap();
+}
+
+fn query_trading_limits(app: &App, vault_addr: &Addr) -> TradingLimitsResponse {
+    app.wrap()
+        .query_wasm_smart(
+            vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetTradingLimits {}),
+        )
+        .unwrap()
+}
+
+#[test]
+fn test_trading_limits_skip_oversized_rebalances() {
+    let (mut app, vault_addr, addrs) = instantiate_with_mock_skip();
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), None);
+
+    let err = app
+        .execute_contract(
+            addrs.owner.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateTradingLimits {
+                limits: TradingLimits {
+                    max_trade_fraction: Some(Decimal256::percent(150)),
+                    ..TradingLimits::default()
+                },
+            }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Max trade fraction 1.5 must not exceed 1",
+    );
+
+    set_trading_limits(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        TradingLimits {
+            max_trade_fraction: None,
+            max_routes: Some(1),
+            max_daily_turnover_usd: None,
+        },
+    );
+    let routes = vec![
+        atom_to_osmo_route(&app, 10, 10),
+        atom_to_osmo_route(&app, 10, 10),
+    ];
+    let res = execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), Some(routes));
+    let skipped =
+        find_event(&res.events, "rebalancing_skipped").expect("rebalancing_skipped missing");
+    assert_eq!(event_attr(skipped, "reason"), Some("max_routes"));
+    assert!(find_event(&res.events, "trade_initiated").is_none());
+
+    // 20 uatom is $200 of a $1000 vault
+    set_trading_limits(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        TradingLimits {
+            max_trade_fraction: Some(Decimal256::percent(10)),
+            max_routes: None,
+            max_daily_turnover_usd: None,
+        },
+    );
+    let routes = vec![atom_to_osmo_route(&app, 20, 20)];
+    let res = execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), Some(routes));
+    let skipped =
+        find_event(&res.events, "rebalancing_skipped").expect("rebalancing_skipped missing");
+    assert_eq!(event_attr(skipped, "reason"), Some("max_trade_fraction"));
+    assert_eq!(event_attr(skipped, "max_trade_value_usd"), Some("100"));
+
+    let routes = vec![atom_to_osmo_route(&app, 10, 10)];
+    let res = execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), Some(routes));
+    assert!(find_event(&res.events, "trade_completed").is_some());
+}
+
+#[test]
+fn test_daily_turnover_budget_rolls_over() {
+    let (mut app, vault_addr, addrs) = instantiate_with_mock_skip();
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), None);
+
+    set_trading_limits(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        TradingLimits {
+            max_trade_fraction: None,
+            max_routes: None,
+            max_daily_turnover_usd: Some(decimal(150)),
+        },
+    );
+
+    // A swap that fails in the reply uses none of the budget
+    let routes = vec![atom_to_osmo_route(&app, 10, 11)];
+    let res = execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), Some(routes));
+    assert!(find_event(&res.events, "trade_failed").is_some());
+    let budget = query_trading_limits(&app, &vault_addr);
+    assert_eq!(budget.turnover_24h_usd, Decimal256::zero());
+
+    let routes = vec![atom_to_osmo_route(&app, 10, 10)];
+    let res = execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), Some(routes));
+    assert!(find_event(&res.events, "trade_completed").is_some());
+
+    let budget = query_trading_limits(&app, &vault_addr);
+    assert_eq!(budget.turnover_24h_usd, decimal(100));
+    assert_eq!(budget.remaining_turnover_usd, Some(decimal(50)));
+    assert_eq!(budget.max_trade_value_usd, None);
+
+    app.update_block(|block| block.time = block.time.plus_seconds(3600));
+    let routes = vec![atom_to_osmo_route(&app, 10, 10)];
+    let res = execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), Some(routes));
+    let skipped =
+        find_event(&res.events, "rebalancing_skipped").expect("rebalancing_skipped missing");
+    assert_eq!(event_attr(skipped, "reason"), Some("daily_turnover"));
+    assert_eq!(event_attr(skipped, "remaining_turnover_usd"), Some("50"));
+
+    // The first trade leaves the window 24 hours after it was made
+    app.update_block(|block| block.time = block.time.plus_seconds(23 * 3600));
+    let budget = query_trading_limits(&app, &vault_addr);
+    assert_eq!(budget.remaining_turnover_usd, Some(decimal(150)));
+
+    let routes = vec![atom_to_osmo_route(&app, 10, 10)];
+    let res = execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), Some(routes));
+    assert!(find_event(&res.events, "trade_completed").is_some());
+}
+
+fn list_trades(
+    app: &App,
+    vault_addr: &Addr,
+    start_after: Option<u64>,
+    denom: Option<&str>,
+) -> Vec<TradeRecord> {
+    app.wrap()
+        .query_wasm_smart(
+            vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::ListTrades {
+                start_after,
+                limit: None,
+                denom: denom.map(str::to_string),
+            }),
+        )
+        .unwrap()
+}
+
+#[test]
+fn test_trade_history_records_completed_and_failed_trades() {
+    let (mut app, vault_addr, addrs) = instantiate_with_mock_skip();
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), None);
+
+    let routes = vec![
+        atom_to_osmo_route(&app, 30, 30),
+        atom_to_osmo_route(&app, 20, 25),
+    ];
+    execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), Some(routes));
+
+    let mut osmo_to_atom = atom_to_osmo_route(&app, 10, 10);
+    osmo_to_atom.offer_denom = DENOM_OSMO.to_string();
+    osmo_to_atom.ask_denom = DENOM_ATOM.to_string();
+    app.update_block(|block| block.time = block.time.plus_seconds(5));
+    execute_update_prices(
+        &mut app,
+        &vault_addr,
+        atom_and_osmo_prices(),
+        Some(vec![osmo_to_atom]),
+    );
+
+    let completed: TradeRecord = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetTrade { id: 1 }),
+        )
+        .unwrap();
+    assert_eq!(completed.status, TradeStatus::Completed);
+    assert_eq!(completed.in_coin, coin(30, DENOM_ATOM));
+    assert_eq!(completed.out_coin, Some(coin(30, DENOM_OSMO)));
+    assert_eq!(completed.swap_venue, "neutron-astroport");
+    assert_eq!(completed.in_price_usd, Some(decimal(10)));
+    assert_eq!(completed.out_price_usd, Some(decimal(10)));
+
+    let failed: TradeRecord = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetTrade { id: 2 }),
+        )
+        .unwrap();
+    assert!(matches!(failed.status, TradeStatus::Failed { .. }));
+    assert_eq!(failed.out_coin, None);
+
+    let all = list_trades(&app, &vault_addr, None, None);
+    assert_eq!(
+        all.iter().map(|trade| trade.id).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(all[2].executed_at, all[0].executed_at.plus_seconds(5));
+
+    let page = list_trades(&app, &vault_addr, Some(1), None);
+    assert_eq!(
+        page.iter().map(|trade| trade.id).collect::<Vec<_>>(),
+        vec![2, 3]
+    );
+
+    // Denom filter matches either side of the swap
+    let atom_trades = list_trades(&app, &vault_addr, None, Some(DENOM_ATOM));
+    assert_eq!(
+        atom_trades.iter().map(|trade| trade.id).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    let atom_trades = list_trades(&app, &vault_addr, Some(2), Some(DENOM_ATOM));
+    assert_eq!(
+        atom_trades.iter().map(|trade| trade.id).collect::<Vec<_>>(),
+        vec![3]
+    );
+    assert!(list_trades(&app, &vault_addr, None, Some(DENOM_UNLISTED)).is_empty());
+}
+
+fn query_share_price(app: &App, vault_addr: &Addr) -> Decimal256 {
+    app.wrap()
+        .query_wasm_smart(
+            vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetSharePrice {}),
+        )
+        .unwrap()
+}
+
+#[test]
+fn test_nav_snapshots_track_share_price() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    assert_eq!(query_share_price(&app, &vault_addr), Decimal256::zero());
+
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+    let start = app.block_info().time;
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+    let first_price = query_share_price(&app, &vault_addr);
+    assert!(!first_price.is_zero());
+
+    for seconds in [60, 120] {
+        app.update_block(|block| block.time = start.plus_seconds(seconds));
+        execute_update_prices(&mut app, &vault_addr, atom_price(20), None);
+    }
+    // The reported price counts the virtual offsets, the same as minting
+    assert_eq!(
+        query_share_price(&app, &vault_addr),
+        execute::share_price(decimal(2000), Uint256::from(1_000_000_000u128)).unwrap()
+    );
+    assert_eq!(first_price, Decimal256::from_str("0.000001").unwrap());
+
+    let history: Vec<NavSnapshot> = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetNavHistory {
+                start: None,
+                end: None,
+                limit: None,
+            }),
+        )
+        .unwrap();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[0].timestamp, start);
+    assert_eq!(history[0].tvl, decimal(1000));
+    assert_eq!(history[0].share_price, first_price);
+    assert_eq!(history[0].balances, coins(100, DENOM_ATOM));
+    assert_eq!(history[1].tvl, decimal(2000));
+    assert_eq!(history[1].total_shares, history[0].total_shares);
+
+    let range: Vec<NavSnapshot> = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetNavHistory {
+                start: Some(start.plus_seconds(60)),
+                end: Some(start.plus_seconds(60)),
+                limit: None,
+            }),
+        )
+        .unwrap();
+    assert_eq!(range, vec![history[1].clone()]);
+
+    let latest: Vec<NavSnapshot> = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetLatestNavSnapshots { count: 2 }),
+        )
+        .unwrap();
+    assert_eq!(latest, vec![history[2].clone(), history[1].clone()]);
+}
+
+#[test]
+fn test_paginated_queries_cap_the_limit() {
+    let mut deps = mock_dependencies();
+    for i in 0..150 {
+        let denom = format!("denom{i:03}");
+        state::WHITELISTED_DENOMS
+            .save(
+                deps.as_mut().storage,
+                denom.clone(),
+                &whitelist_entry(&denom),
+            )
+            .unwrap();
+    }
+
+    assert_eq!(
+        query::asset_registry(deps.as_ref(), None, None)
+            .unwrap()
+            .len(),
+        30
+    );
+    assert_eq!(
+        query::asset_registry(deps.as_ref(), None, Some(u32::MAX))
+            .unwrap()
+            .len(),
+        100
+    );
+}
+
+#[test]
+fn test_nav_snapshots_are_bounded() {
+    let mut deps = mock_dependencies();
+    state::VAULT_VALUE_DEPOSITED
+        .save(deps.as_mut().storage, &Decimal256::zero())
+        .unwrap();
+    state::TOTAL_SHARES
+        .save(deps.as_mut().storage, &Uint256::zero())
+        .unwrap();
+
+    let start = mock_env().block.time;
+    for seconds in 0..execute::MAX_NAV_SNAPSHOTS + 5 {
+        execute::record_nav_snapshot(deps.as_mut().storage, start.plus_seconds(seconds)).unwrap();
+    }
+
+    let count = state::NAV_SNAPSHOTS
+        .keys(
+            deps.as_ref().storage,
+            None,
+            None,
+            cosmwasm_std::Order::Ascending,
+        )
+        .count() as u64;
+    assert_eq!(count, execute::MAX_NAV_SNAPSHOTS);
+    let oldest = state::NAV_SNAPSHOTS
+        .range(
+            deps.as_ref().storage,
+            None,
+            None,
+            cosmwasm_std::Order::Ascending,
+        )
+        .next()
+        .unwrap()
+        .unwrap()
+        .1;
+    assert_eq!(oldest.timestamp, start.plus_seconds(5));
+}
+
+fn instantiate_tokenized(deps: DepsMut, env: &Env) -> Response {
+    let api = MockApi::default();
+    instantiate(
+        deps,
+        env.clone(),
+        message_info(&api.addr_make(OWNER), &[]),
+        InstantiateMsg {
+            service_manager: api.addr_make(SERVICE_MANAGER).to_string(),
+            initial_whitelist: vec![whitelist_entry(DENOM_ATOM)],
+            skip_entry_point: api.addr_make(SKIP_ENTRY_POINT_ADDR).to_string(),
+            share_subdenom: Some("vshare".to_string()),
+            target_allocation: None,
+        },
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_token_factory_messages_encode_protobuf() {
+    let CosmosMsg::Any(create) = token_factory::create_denom_msg("a", "b") else {
+        panic!("expected an Any message");
+    };
+    assert_eq!(
+        create.type_url,
+        "/osmosis.tokenfactory.v1beta1.MsgCreateDenom"
+    );
+    assert_eq!(create.value.as_slice(), &[0x0a, 1, b'a', 0x12, 1, b'b']);
+
+    let CosmosMsg::Any(mint) = token_factory::mint_msg("a", &coin(5, "d"), "c") else {
+        panic!("expected an Any message");
+    };
+    assert_eq!(mint.type_url, "/osmosis.tokenfactory.v1beta1.MsgMint");
+    assert_eq!(
+        mint.value.as_slice(),
+        &[0x0a, 1, b'a', 0x12, 6, 0x0a, 1, b'd', 0x12, 1, b'5', 0x1a, 1, b'c']
+    );
+}
+
+#[test]
+fn test_tokenized_shares_are_minted_and_burned() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let contract = env.contract.address.to_string();
+    let user = deps.api.addr_make(USER1);
+
+    let res = instantiate_tokenized(deps.as_mut(), &env);
+    let share_denom = format!("factory/{contract}/vshare");
+    assert_eq!(
+        res.messages[0].msg,
+        token_factory::create_denom_msg(&contract, "vshare")
+    );
+    assert_eq!(
+        query::share_denom(deps.as_ref()).unwrap(),
+        Some(share_denom.clone())
+    );
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&user, &coins(100, DENOM_ATOM)),
+        ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&env.contract.address, &[]),
+        ExecuteMsg::Vault(VaultExecuteMsg::UpdatePrices {
+            prices: atom_price(10),
+            swap_routes: None,
+        }),
+    )
+    .unwrap();
+
+    // Shares are minted to the depositor rather than booked internally
+    let minted = Uint128::try_from(state::TOTAL_SHARES.load(&deps.storage).unwrap())
+        .unwrap()
+        .u128();
+    assert_eq!(
+        res.messages[0].msg,
+        token_factory::mint_msg(&contract, &coin(minted, &share_denom), user.as_str())
+    );
+    assert!(state::USER_SHARES
+        .may_load(&deps.storage, user.to_string())
+        .unwrap()
+        .is_none());
+
+    // The bank balance is what the user holds
+    deps.querier
+        .bank
+        .update_balance(&user, vec![coin(minted, &share_denom)]);
+    assert_eq!(
+        query::user_shares(deps.as_ref(), user.to_string()).unwrap(),
+        Uint256::from(minted)
+    );
+
+    // Withdrawals must attach exactly the shares being redeemed
+    let half = minted / 2;
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&user, &coins(half, &share_denom)),
+        ExecuteMsg::Vault(VaultExecuteMsg::Withdraw {
+            shares: Uint256::from(half + 1),
+        }),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::ShareFundsMismatch { .. }));
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&user, &[]),
+        ExecuteMsg::Vault(VaultExecuteMsg::Withdraw {
+            shares: Uint256::from(half),
+        }),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Payment(_)));
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&user, &coins(half, &share_denom)),
+        ExecuteMsg::Vault(VaultExecuteMsg::Withdraw {
+            shares: Uint256::from(half),
+        }),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        token_factory::burn_msg(&contract, &coin(half, &share_denom), &contract)
+    );
+    assert_eq!(
+        res.messages[1].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: user.to_string(),
+            amount: coins(49, DENOM_ATOM),
+        })
+    );
+    assert_eq!(
+        state::TOTAL_SHARES.load(&deps.storage).unwrap(),
+        Uint256::from(minted - half)
+    );
+}
+
+#[test]
+fn test_migrate_tokenizes_existing_share_balances() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+    let user_shares = Uint128::try_from(query_user_shares(&app, &vault_addr, &addrs.user1))
+        .unwrap()
+        .u128();
+
+    // Replay the internal balances into a fresh store to run the migration directly
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let contract = env.contract.address.to_string();
+    state::USER_SHARES
+        .save(
+            &mut deps.storage,
+            addrs.user1.to_string(),
+            &Uint256::from(user_shares),
+        )
+        .unwrap();
+    state::USER_SHARES
+        .save(
+            &mut deps.storage,
+            addrs.user2.to_string(),
+            &Uint256::from(7u128),
+        )
+        .unwrap();
+
+    let res = migrate(
+        deps.as_mut(),
+        env.clone(),
+        MigrateMsg {
+            share_subdenom: Some("vshare".to_string()),
+        },
+    )
+    .unwrap();
+    let share_denom = format!("factory/{contract}/vshare");
+    let mut expected_mints = [
+        (addrs.user1.to_string(), user_shares),
+        (addrs.user2.to_string(), 7),
+    ];
+    expected_mints.sort();
+    let msgs: Vec<CosmosMsg> = res.messages.iter().map(|sub| sub.msg.clone()).collect();
+    assert_eq!(
+        msgs[0],
+        token_factory::create_denom_msg(&contract, "vshare")
+    );
+    assert_eq!(
+        msgs[1..].to_vec(),
+        expected_mints
+            .iter()
+            .map(|(holder, amount)| {
+                token_factory::mint_msg(&contract, &coin(*amount, &share_denom), holder)
+            })
+            .collect::<Vec<_>>()
+    );
+    assert!(state::USER_SHARES
+        .range(&deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .next()
+        .is_none());
+
+    let err = migrate(
+        deps.as_mut(),
+        env,
+        MigrateMsg {
+            share_subdenom: Some("other".to_string()),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::SharesAlreadyTokenized { .. }));
+}
+
+#[test]
+fn test_tokenizing_mints_queued_shares_to_the_vault() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let contract = env.contract.address.to_string();
+    let owner = deps.api.addr_make(OWNER);
+    let user = deps.api.addr_make(USER1);
+    let api = MockApi::default();
+    instantiate(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&owner, &[]),
+        InstantiateMsg {
+            service_manager: api.addr_make(SERVICE_MANAGER).to_string(),
+            initial_whitelist: vec![whitelist_entry(DENOM_ATOM)],
+            skip_entry_point: api.addr_make(SKIP_ENTRY_POINT_ADDR).to_string(),
+            share_subdenom: None,
+            target_allocation: None,
+        },
+    )
+    .unwrap();
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&user, &coins(100, DENOM_ATOM)),
+        ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+    )
+    .unwrap();
+    let update_prices = ExecuteMsg::Vault(VaultExecuteMsg::UpdatePrices {
+        prices: atom_price(10),
+        swap_routes: None,
+    });
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&env.contract.address, &[]),
+        update_prices.clone(),
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&owner, &[]),
+        ExecuteMsg::Vault(VaultExecuteMsg::UpdateWithdrawalQueue { enabled: true }),
+    )
+    .unwrap();
+
+    let shares = state::USER_SHARES
+        .load(&deps.storage, user.to_string())
+        .unwrap();
+    let queued = shares / Uint256::from(2u128);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&user, &[]),
+        ExecuteMsg::Vault(VaultExecuteMsg::RequestWithdraw { shares: queued }),
+    )
+    .unwrap();
+
+    // The queued shares are minted to the vault alongside the holder's balance
+    let res = migrate(
+        deps.as_mut(),
+        env.clone(),
+        MigrateMsg {
+            share_subdenom: Some("vshare".to_string()),
+        },
+    )
+    .unwrap();
+    let share_denom = format!("factory/{contract}/vshare");
+    let msgs: Vec<CosmosMsg> = res.messages.iter().map(|sub| sub.msg.clone()).collect();
+    assert_eq!(
+        msgs[1..].to_vec(),
+        vec![
+            token_factory::mint_msg(
+                &contract,
+                &Coin::new(shares - queued, &share_denom),
+                user.as_str()
+            ),
+            token_factory::mint_msg(&contract, &Coin::new(queued, &share_denom), &contract),
+        ]
+    );
+
+    // Settlement burns the shares the vault now holds
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&env.contract.address, &[]),
+        update_prices,
+    )
+    .unwrap();
+    let msgs: Vec<CosmosMsg> = res.messages.iter().map(|sub| sub.msg.clone()).collect();
+    assert_eq!(
+        msgs,
+        vec![
+            token_factory::burn_msg(&contract, &Coin::new(queued, &share_denom), &contract),
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: user.to_string(),
+                amount: coins(49, DENOM_ATOM),
+            }),
+        ]
+    );
+}
+
+fn osmo_to_atom_withdraw_route() -> WithdrawRoute {
+    WithdrawRoute {
+        swap_venue_name: "neutron-astroport".to_string(),
+        offer_denom: DENOM_OSMO.to_string(),
+        operations: vec![SwapOperation {
+            pool: "pool-1".to_string(),
+            denom_in: DENOM_OSMO.to_string(),
+            denom_out: DENOM_ATOM.to_string(),
+            interface: None,
+        }],
+    }
+}
+
+// Two depositors each bring half of a 50/50 atom/osmo vault
+fn instantiate_split_vault() -> (App, Addr, TestAddrs) {
+    let (mut app, vault_addr, addrs) = instantiate_with_mock_skip();
+    for (user, denom) in [(&addrs.user1, DENOM_ATOM), (&addrs.user2, DENOM_OSMO)] {
+        app.execute_contract(
+            user.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+            &coins(100, denom),
+        )
+        .unwrap();
+    }
+    execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), None);
+    (app, vault_addr, addrs)
+}
+
+fn withdraw_to_atom(
+    app: &mut App,
+    vault_addr: &Addr,
+    user: &Addr,
+    min_out: u128,
+    routes: Vec<WithdrawRoute>,
+) -> StdResult<AppResponse> {
+    let shares = query_user_shares(app, vault_addr, user);
+    app.execute_contract(
+        user.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::WithdrawTo {
+            shares,
+            denom: DENOM_ATOM.to_string(),
+            min_out: Uint256::from(min_out),
+            routes,
+        }),
+        &[],
+    )
+}
+
+#[test]
+fn test_withdraw_to_swaps_other_slices_into_target_denom() {
+    let (mut app, vault_addr, addrs) = instantiate_split_vault();
+
+    let res = withdraw_to_atom(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        95,
+        vec![osmo_to_atom_withdraw_route()],
+    )
+    .unwrap();
+    let completed = find_event(&res.events, "withdraw_to_completed").unwrap();
+    assert_eq!(event_attr(completed, "amount_out"), Some("98"));
+
+    // 49 atom paid directly plus 49 osmo swapped 1:1 into atom, both slices rounded down
+    let atom = app.wrap().query_balance(&addrs.user1, DENOM_ATOM).unwrap();
+    let osmo = app.wrap().query_balance(&addrs.user1, DENOM_OSMO).unwrap();
+    assert_eq!(atom.amount, Uint256::from(998u128));
+    assert_eq!(osmo.amount, Uint256::from(1000u128));
+    assert!(query_user_shares(&app, &vault_addr, &addrs.user1).is_zero());
+
+    let assets: Vec<Coin> = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetVaultAssets {}),
+        )
+        .unwrap();
+    assert_eq!(assets, vec![coin(51, DENOM_ATOM), coin(51, DENOM_OSMO)]);
+}
+
+#[test]
+fn test_withdraw_to_refunds_in_kind_when_minimum_not_met() {
+    let (mut app, vault_addr, addrs) = instantiate_split_vault();
+
+    // The swap's share of the minimum exceeds what the mock venue returns
+    let res = withdraw_to_atom(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        150,
+        vec![osmo_to_atom_withdraw_route()],
+    )
+    .unwrap();
+    let refunded = find_event(&res.events, "withdraw_to_refunded").unwrap();
+    assert_eq!(event_attr(refunded, "amount_out"), Some("49"));
+
+    let atom = app.wrap().query_balance(&addrs.user1, DENOM_ATOM).unwrap();
+    let osmo = app.wrap().query_balance(&addrs.user1, DENOM_OSMO).unwrap();
+    assert_eq!(atom.amount, Uint256::from(949u128));
+    assert_eq!(osmo.amount, Uint256::from(1049u128));
+}
+
+#[test]
+fn test_withdraw_swap_reply_without_outstanding_swap_errors() {
+    let mut deps = mock_dependencies();
+    let user = deps.api.addr_make(USER1);
+    state::WITHDRAW_TO_PENDING
+        .save(
+            deps.as_mut().storage,
+            1,
+            &state::PendingWithdrawal {
+                user,
+                denom: DENOM_ATOM.to_string(),
+                min_out: Uint256::zero(),
+                amount_out: Uint256::zero(),
+                refunds: vec![],
+                outstanding_swaps: 0,
+            },
+        )
+        .unwrap();
+
+    let err = execute::settle_withdraw_swap(
+        deps.as_mut(),
+        Reply {
+            id: 2,
+            payload: to_json_binary(&(1u64, coin(10, DENOM_OSMO))).unwrap(),
+            gas_used: 0,
+            result: SubMsgResult::Err("swap failed".to_string()),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::UnexpectedWithdrawSwapReply { withdrawal_id: 1 }
+    ));
+}
+
+#[test]
+fn test_withdraw_swap_into_the_wrong_denom_errors() {
+    let mut deps = mock_dependencies();
+    let user = deps.api.addr_make(USER1);
+    state::WITHDRAW_TO_PENDING
+        .save(
+            deps.as_mut().storage,
+            1,
+            &state::PendingWithdrawal {
+                user,
+                denom: DENOM_ATOM.to_string(),
+                min_out: Uint256::zero(),
+                amount_out: Uint256::zero(),
+                refunds: vec![],
+                outstanding_swaps: 1,
+            },
+        )
+        .unwrap();
+
+    #[allow(deprecated)]
+    let response = SubMsgResponse {
+        events: vec![Event::new("wasm")
+            .add_attribute("post_swap_action_amount_out", "10")
+            .add_attribute("post_swap_action_denom_out", DENOM_OSMO)],
+        data: None,
+        msg_responses: vec![],
+    };
+    let err = execute::settle_withdraw_swap(
+        deps.as_mut(),
+        Reply {
+            id: 2,
+            payload: to_json_binary(&(1u64, coin(10, DENOM_OSMO))).unwrap(),
+            gas_used: 0,
+            result: SubMsgResult::Ok(response),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Swap for withdrawal 1 paid out uosmo instead of uatom"
+    );
+}
+
+#[test]
+fn test_withdraw_to_requires_route_for_every_slice() {
+    let (mut app, vault_addr, addrs) = instantiate_split_vault();
+
+    let err = withdraw_to_atom(&mut app, &vault_addr, &addrs.user1, 0, vec![]).unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: No withdrawal route swaps uosmo into uatom",
+    );
+
+    // A route that ends in another denom does not count
+    let mut route = osmo_to_atom_withdraw_route();
+    route.operations[0].denom_out = DENOM_UNLISTED.to_string();
+    let err = withdraw_to_atom(&mut app, &vault_addr, &addrs.user1, 0, vec![route]).unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: No withdrawal route swaps uosmo into uatom",
+    );
+}
+
+fn list_withdraw_requests(app: &App, vault_addr: &Addr, user: &Addr) -> Vec<WithdrawRequest> {
+    app.wrap()
+        .query_wasm_smart(
+            vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::ListWithdrawRequests {
+                user: Some(user.to_string()),
+                start_after: None,
+                limit: None,
+            }),
+        )
+        .unwrap()
+}
+
+#[test]
+fn test_request_withdraw_requires_queue() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+
+    let err = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        VaultExecuteMsg::RequestWithdraw {
+            shares: Uint256::from(1u128),
+        },
+    )
+    .unwrap_err();
+    assert_error_line(&err, "kind: Other, error: Withdrawal queue is disabled");
+
+    let err = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        VaultExecuteMsg::UpdateWithdrawalQueue { enabled: true },
+    )
+    .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Caller is not the contract's current owner",
+    );
+}
+
+#[test]
+fn test_queued_withdrawals_settle_before_deposits() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::UpdateWithdrawalQueue { enabled: true },
+    )
+    .unwrap();
+
+    // Instant withdrawals are refused while the queue is enabled
+    let shares = query_user_shares(&app, &vault_addr, &addrs.user1);
+    let err = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        VaultExecuteMsg::Withdraw { shares },
+    )
+    .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Withdrawals are queued, use RequestWithdraw",
+    );
+
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        VaultExecuteMsg::RequestWithdraw { shares },
+    )
+    .unwrap();
+    assert!(query_user_shares(&app, &vault_addr, &addrs.user1).is_zero());
+    let requests = list_withdraw_requests(&app, &vault_addr, &addrs.user1);
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].shares, shares);
+    assert_eq!(requests[0].state, WithdrawState::Pending);
+
+    app.execute_contract(
+        addrs.user2.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+
+    // The withdrawal is paid at the new price, then the deposit is priced without it
+    let res = execute_update_prices(&mut app, &vault_addr, atom_price(20), None);
+    assert!(find_event(&res.events, "withdrawal_settled").is_some());
+
+    let request: WithdrawRequest = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetWithdrawRequest { withdraw_id: 1 }),
+        )
+        .unwrap();
+    assert_eq!(
+        request.state,
+        WithdrawState::Completed {
+            value_usd: Decimal256::from_str("1999.998000001999998").unwrap(),
+            assets: coins(99, DENOM_ATOM),
+        }
+    );
+    let atom = app.wrap().query_balance(&addrs.user1, DENOM_ATOM).unwrap();
+    assert_eq!(atom.amount, Uint256::from(999u128));
+
+    let total_shares: Uint256 = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetTotalShares {}),
+        )
+        .unwrap();
+    assert_eq!(
+        query_user_shares(&app, &vault_addr, &addrs.user2),
+        total_shares
+    );
+    assert!(list_withdraw_requests(&app, &vault_addr, &addrs.user2).is_empty());
+}
+
+#[test]
+fn test_cancel_withdraw_releases_shares_in_emergency_mode() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(100, DENOM_ATOM),
+    )
+    .unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::UpdateWithdrawalQueue { enabled: true },
+    )
+    .unwrap();
+
+    let shares = query_user_shares(&app, &vault_addr, &addrs.user1);
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        VaultExecuteMsg::RequestWithdraw { shares },
+    )
+    .unwrap();
+
+    // Price updates, and with them queue settlement, stop in emergency mode
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::UpdatePauseState {
+            deposits: None,
+            price_processing: None,
+            trading: None,
+            emergency_mode: Some(true),
+        },
+    )
+    .unwrap();
+
+    let cancel = VaultExecuteMsg::CancelWithdraw { withdraw_id: 1 };
+    let err = execute_vault_msg(&mut app, &vault_addr, &addrs.user2, cancel.clone()).unwrap_err();
+    assert_error_line(&err, "kind: Other, error: Unauthorized");
+
+    let res = execute_vault_msg(&mut app, &vault_addr, &addrs.user1, cancel.clone()).unwrap();
+    assert!(find_event(&res.events, "withdrawal_cancelled").is_some());
+    assert_eq!(query_user_shares(&app, &vault_addr, &addrs.user1), shares);
+    assert_eq!(
+        list_withdraw_requests(&app, &vault_addr, &addrs.user1)[0].state,
+        WithdrawState::Cancelled
+    );
+
+    let err = execute_vault_msg(&mut app, &vault_addr, &addrs.user1, cancel).unwrap_err();
+    assert_error_line(&err, "kind: Other, error: Withdrawal already cancelled: 1");
+
+    // The returned shares leave through the emergency withdrawal
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        VaultExecuteMsg::Withdraw { shares },
+    )
+    .unwrap();
+    assert!(query_user_shares(&app, &vault_addr, &addrs.user1).is_zero());
+    let atom = app.wrap().query_balance(&addrs.user1, DENOM_ATOM).unwrap();
+    assert_eq!(atom.amount, Uint256::from(999u128));
+}
+
+// Deterministic linear congruential generator for the share math property tests
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        self.0 >> 11
+    }
+
+    // USD amount with six decimals, up to `max_usd`
+    fn usd(&mut self, max_usd: u64) -> Decimal256 {
+        Decimal256::from_atomics(self.next() % (max_usd * 1_000_000) + 1, 6).unwrap()
+    }
+}
+
+// Share price in USD atomics per share, compared by cross multiplication to avoid rounding
+fn share_price_at_least(
+    value_after: Decimal256,
+    shares_after: Uint256,
+    value_before: Decimal256,
+    shares_before: Uint256,
+) -> bool {
+    let virtual_shares = Uint256::from(execute::VIRTUAL_SHARES);
+    let lhs =
+        (value_after + execute::VIRTUAL_ASSETS_USD).atomics() * (shares_before + virtual_shares);
+    let rhs =
+        (value_before + execute::VIRTUAL_ASSETS_USD).atomics() * (shares_after + virtual_shares);
+    lhs >= rhs
+}
+
+#[test]
+fn test_share_math_mint_then_redeem_never_profits() {
+    let mut rng = Lcg(7);
+    let mut vault_value = Decimal256::zero();
+    let mut total_shares = Uint256::zero();
+
+    for _ in 0..2_000 {
+        let value_usd = rng.usd(10_000);
+        let shares = execute::shares_for_value(value_usd, vault_value, total_shares).unwrap();
+        let next_value = vault_value + value_usd;
+        let next_shares = total_shares + shares;
+
+        // Redeeming freshly minted shares never returns more than was deposited
+        let redeemed = execute::value_for_shares(shares, next_value, next_shares).unwrap();
+        assert!(redeemed <= value_usd, "{redeemed} > {value_usd}");
+
+        // Minting never dilutes existing holders
+        assert!(share_price_at_least(
+            next_value,
+            next_shares,
+            vault_value,
+            total_shares
+        ));
+
+        vault_value = next_value;
+        total_shares = next_shares;
+        // Occasional profit or loss so the share price drifts away from its initial value
+        if rng.next().is_multiple_of(4) {
+            vault_value *= Decimal256::percent(50 + rng.next() % 100);
+        }
+    }
+}
+
+#[test]
+fn test_share_math_donation_attack_is_unprofitable() {
+    let mut rng = Lcg(42);
+
+    for _ in 0..2_000 {
+        // Attacker seeds an empty vault with a dust deposit, then donates to inflate the price
+        let seed = Decimal256::from_atomics(rng.next() % 1_000 + 1, 6).unwrap();
+        let attacker_shares =
+            execute::shares_for_value(seed, Decimal256::zero(), Uint256::zero()).unwrap();
+        let donation = rng.usd(1_000_000);
+        let vault_value = seed + donation;
+
+        let victim_value = rng.usd(1_000_000);
+        let victim_shares =
+            execute::shares_for_value(victim_value, vault_value, attacker_shares).unwrap();
+        if victim_shares.is_zero() {
+            // The deposit stays pending and can be cancelled, nothing is absorbed
+            continue;
+        }
+
+        let total_value = vault_value + victim_value;
+        let total_shares = attacker_shares + victim_shares;
+        let attacker_out =
+            execute::value_for_shares(attacker_shares, total_value, total_shares).unwrap();
+        assert!(
+            attacker_out <= seed + donation,
+            "attacker spent {} and redeemed {attacker_out}",
+            seed + donation
+        );
+
+        // The victim loses at most one share, worth a thousandth of the attacker's outlay
+        let victim_out =
+            execute::value_for_shares(victim_shares, total_value, total_shares).unwrap();
+        let max_loss = (vault_value + execute::VIRTUAL_ASSETS_USD)
+            / Decimal256::from_atomics(execute::VIRTUAL_SHARES, 0).unwrap();
+        assert!(
+            victim_value - victim_out <= max_loss,
+            "victim deposited {victim_value} and redeemed {victim_out}"
+        );
+    }
+}
+
+#[test]
+fn test_dust_deposit_stays_pending() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    let atom = WhitelistEntry {
+        decimals: 18,
+        ..whitelist_entry(DENOM_ATOM)
+    };
+    update_whitelist_entry(&mut app, &vault_addr, &addrs.owner, atom);
+
+    // A single base unit at 18 decimals is worth far less than one share
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(1, DENOM_ATOM),
+    )
+    .unwrap();
+    execute_update_prices(
+        &mut app,
+        &vault_addr,
+        vec![PriceInfo {
+            denom: DENOM_ATOM.to_string(),
+            price_usd: decimal(10),
+            decimals: 18,
+        }],
+        None,
+    );
+
+    assert!(query_user_shares(&app, &vault_addr, &addrs.user1).is_zero());
+    let deposit: DepositRequest = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetDepositRequest { deposit_id: 1 }),
+        )
+        .unwrap();
+    assert_eq!(deposit.state, DepositState::Pending);
+}
+
+fn set_deposit_caps(app: &mut App, vault_addr: &Addr, owner: &Addr, caps: DepositCaps) {
+    execute_vault_msg(
+        app,
+        vault_addr,
+        owner,
+        VaultExecuteMsg::UpdateDepositCaps { caps },
+    )
+    .unwrap();
+}
+
+fn deposit_atom(
+    app: &mut App,
+    vault_addr: &Addr,
+    user: &Addr,
+    amount: u128,
+) -> StdResult<AppResponse> {
+    app.execute_contract(
+        user.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+        &coins(amount, DENOM_ATOM),
+    )
+}
+
+#[test]
+fn test_deposit_caps_reject_deposits_over_cap() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    deposit_atom(&mut app, &vault_addr, &addrs.user1, 100).unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+
+    let err = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        VaultExecuteMsg::UpdateDepositCaps {
+            caps: DepositCaps::default(),
+        },
+    )
+    .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Caller is not the contract's current owner",
+    );
+
+    let caps = DepositCaps {
+        max_tvl_usd: Some(decimal(1_500)),
+        denom_caps: vec![coin(200, DENOM_ATOM)],
+        max_position_usd: Some(decimal(1_200)),
+    };
+    set_deposit_caps(&mut app, &vault_addr, &addrs.owner, caps.clone());
+    let stored: DepositCaps = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetDepositCaps {}),
+        )
+        .unwrap();
+    assert_eq!(stored, caps);
+
+    // $1,000 held plus $600 would pass the TVL cap
+    let err = deposit_atom(&mut app, &vault_addr, &addrs.user2, 60).unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Deposit would take the vault over its TVL cap of $1500",
+    );
+
+    // Pending deposits count towards the caps
+    deposit_atom(&mut app, &vault_addr, &addrs.user2, 50).unwrap();
+    let err = deposit_atom(&mut app, &vault_addr, &addrs.user2, 1).unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Deposit would take the vault over its TVL cap of $1500",
+    );
+
+    // user1's $1,000 position plus $300 would pass the position cap
+    set_deposit_caps(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        DepositCaps {
+            max_tvl_usd: None,
+            ..caps
+        },
+    );
+    let err = deposit_atom(&mut app, &vault_addr, &addrs.user1, 30).unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Deposit would take the position over its cap of $1200",
+    );
+
+    // 100 held and 50 pending leave room for 50 more uatom
+    let err = deposit_atom(&mut app, &vault_addr, &addrs.user2, 51).unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Deposit would take uatom over its cap of 200",
+    );
+    deposit_atom(&mut app, &vault_addr, &addrs.user2, 50).unwrap();
+}
+
+#[test]
+fn test_deposit_caps_refund_excess_at_settlement() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    set_deposit_caps(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        DepositCaps {
+            max_tvl_usd: Some(decimal(1_500)),
+            denom_caps: vec![],
+            max_position_usd: None,
+        },
+    );
+
+    // Unpriced deposits only meet the USD caps when they settle
+    deposit_atom(&mut app, &vault_addr, &addrs.user1, 100).unwrap();
+    deposit_atom(&mut app, &vault_addr, &addrs.user2, 100).unwrap();
+    let res = execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+
+    // The earlier deposit fills the cap first and the later one is trimmed to fit
+    let processed =
+        find_event_with_attr(&res.events, "deposit_processed", "deposit_id", "2").unwrap();
+    assert_eq!(event_attr(processed, "value_usd"), Some("500"));
+    assert_eq!(event_attr(processed, "refund_uatom"), Some("50"));
+    let balance = app.wrap().query_balance(&addrs.user2, DENOM_ATOM).unwrap();
+    assert_eq!(balance.amount, Uint256::from(950u128));
+
+    let deposit: DepositRequest = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetDepositRequest { deposit_id: 2 }),
+        )
+        .unwrap();
+    assert_eq!(deposit.coins, coins(50, DENOM_ATOM));
+    assert_eq!(
+        deposit.state,
+        DepositState::Completed {
+            value_usd: decimal(500)
+        }
+    );
+    let vault_value: Decimal256 = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetVaultValue {}),
+        )
+        .unwrap();
+    assert_eq!(vault_value, decimal(1_500));
+
+    // A deposit accepted at the old price no longer fits once the price rises
+    set_deposit_caps(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        DepositCaps {
+            max_tvl_usd: Some(decimal(1_600)),
+            denom_caps: vec![],
+            max_position_usd: None,
+        },
+    );
+    deposit_atom(&mut app, &vault_addr, &addrs.user1, 10).unwrap();
+    let res = execute_update_prices(&mut app, &vault_addr, atom_price(11), None);
+    let cancelled = find_event(&res.events, "deposit_cancelled").unwrap();
+    assert_eq!(event_attr(cancelled, "deposit_id"), Some("3"));
+    assert_eq!(event_attr(cancelled, "reason"), Some("deposit_cap"));
+    assert_eq!(event_attr(cancelled, "refund_uatom"), Some("10"));
+
+    let pending: Vec<Coin> = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetTotalPendingAssets {}),
+        )
+        .unwrap();
+    assert!(pending.is_empty());
+    let balance = app.wrap().query_balance(&addrs.user1, DENOM_ATOM).unwrap();
+    assert_eq!(balance.amount, Uint256::from(900u128));
+}
+
+fn query_depositor_allowlist(
+    app: &App,
+    vault_addr: &Addr,
+    start_after: Option<String>,
+) -> DepositorAllowlistResponse {
+    app.wrap()
+        .query_wasm_smart(
+            vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetDepositorAllowlist {
+                start_after,
+                limit: Some(1),
+            }),
+        )
+        .unwrap()
+}
+
+#[test]
+fn test_depositor_allowlist_gates_deposits_only() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    deposit_atom(&mut app, &vault_addr, &addrs.user1, 100).unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+
+    let update = VaultExecuteMsg::UpdateDepositorAllowlist {
+        enabled: Some(true),
+        to_add: Some(vec![addrs.user1.to_string(), addrs.user2.to_string()]),
+        to_remove: None,
+    };
+    let err = execute_vault_msg(&mut app, &vault_addr, &addrs.user1, update.clone()).unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Caller is not the contract's current owner",
+    );
+    execute_vault_msg(&mut app, &vault_addr, &addrs.owner, update).unwrap();
+
+    // One address per page, in key order
+    let mut expected = [addrs.user1.clone(), addrs.user2.clone()];
+    expected.sort();
+    let first = query_depositor_allowlist(&app, &vault_addr, None);
+    assert!(first.enabled);
+    assert_eq!(first.addresses, vec![expected[0].clone()]);
+    let second = query_depositor_allowlist(&app, &vault_addr, Some(first.addresses[0].to_string()));
+    assert_eq!(second.addresses, vec![expected[1].clone()]);
+
+    deposit_atom(&mut app, &vault_addr, &addrs.user2, 10).unwrap();
+
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::UpdateDepositorAllowlist {
+            enabled: None,
+            to_add: None,
+            to_remove: Some(vec![addrs.user1.to_string()]),
+        },
+    )
+    .unwrap();
+    let err = deposit_atom(&mut app, &vault_addr, &addrs.user1, 10).unwrap_err();
+    assert_error_line(
+        &err,
+        &format!(
+            "kind: Other, error: Address {} is not allowed to deposit",
+            addrs.user1
+        ),
+    );
+
+    // Removed holders keep their shares and can still leave
+    let shares = query_user_shares(&app, &vault_addr, &addrs.user1);
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        VaultExecuteMsg::Withdraw { shares },
+    )
+    .unwrap();
+
+    // Disabling the mode opens deposits to everyone again
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::UpdateDepositorAllowlist {
+            enabled: Some(false),
+            to_add: None,
+            to_remove: None,
+        },
+    )
+    .unwrap();
+    deposit_atom(&mut app, &vault_addr, &addrs.user1, 10).unwrap();
+}
+
+fn query_reconciliation(app: &App, vault_addr: &Addr) -> ReconciliationReport {
+    app.wrap()
+        .query_wasm_smart(
+            vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetReconciliation {}),
+        )
+        .unwrap()
+}
+
+fn query_vault_assets(app: &App, vault_addr: &Addr) -> Vec<Coin> {
+    app.wrap()
+        .query_wasm_smart(
+            vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetVaultAssets {}),
+        )
+        .unwrap()
+}
+
+#[test]
+fn test_reconcile_folds_surplus_and_writes_down_deficits() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    deposit_atom(&mut app, &vault_addr, &addrs.user1, 100).unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+    deposit_atom(&mut app, &vault_addr, &addrs.user1, 10).unwrap();
+    assert!(query_reconciliation(&app, &vault_addr)
+        .discrepancies
+        .is_empty());
+
+    // A donation shows up as surplus over the pending and tracked balances
+    app.send_tokens(
+        addrs.user2.clone(),
+        vault_addr.clone(),
+        &coins(20, DENOM_ATOM),
+    )
+    .unwrap();
+    let report = query_reconciliation(&app, &vault_addr);
+    assert_eq!(report.policy, SurplusPolicy::FoldIntoVault);
+    assert_eq!(
+        report.discrepancies,
+        vec![AssetDiscrepancy {
+            denom: DENOM_ATOM.to_string(),
+            bank_balance: Uint256::from(130u128),
+            pending: Uint256::from(10u128),
+            tracked: Uint256::from(100u128),
+            surplus: Uint256::from(20u128),
+            deficit: Uint256::zero(),
+        }]
+    );
+
+    let err = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        VaultExecuteMsg::Reconcile {},
+    )
+    .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Caller is not the contract's current owner",
+    );
+    let res = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::Reconcile {},
+    )
+    .unwrap();
+    let reconciled = find_event(&res.events, "reconciled").unwrap();
+    assert_eq!(event_attr(reconciled, "surplus_uatom"), Some("20"));
+    assert_eq!(event_attr(reconciled, "vault_value_usd"), Some("1200"));
+    assert_eq!(
+        query_vault_assets(&app, &vault_addr),
+        coins(120, DENOM_ATOM)
+    );
+    assert!(query_reconciliation(&app, &vault_addr)
+        .discrepancies
+        .is_empty());
+
+    // Funds that left without being booked are written off the vault assets
+    app.init_modules(|router, _, storage| {
+        router
+            .bank
+            .init_balance(storage, &vault_addr, coins(100, DENOM_ATOM))
+    })
+    .unwrap();
+    let res = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::Reconcile {},
+    )
+    .unwrap();
+    let reconciled = find_event(&res.events, "reconciled").unwrap();
+    assert_eq!(event_attr(reconciled, "deficit_uatom"), Some("30"));
+    assert_eq!(query_vault_assets(&app, &vault_addr), coins(90, DENOM_ATOM));
+    let vault_value: Decimal256 = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetVaultValue {}),
+        )
+        .unwrap();
+    assert_eq!(vault_value, decimal(900));
+
+    // A shortfall that reaches into pending deposits is refused rather than written off
+    app.init_modules(|router, _, storage| {
+        router
+            .bank
+            .init_balance(storage, &vault_addr, coins(5, DENOM_ATOM))
+    })
+    .unwrap();
+    let err = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::Reconcile {},
+    )
+    .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Bank balance 5 of uatom does not cover the 10 held for pending deposits",
+    );
+    assert_eq!(query_vault_assets(&app, &vault_addr), coins(90, DENOM_ATOM));
+}
+
+#[test]
+fn test_reconcile_sweeps_surplus_to_treasury() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    deposit_atom(&mut app, &vault_addr, &addrs.user1, 100).unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+
+    let treasury = app.api().addr_make("treasury");
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::UpdateSurplusPolicy {
+            policy: SurplusPolicy::SweepToTreasury {
+                treasury: treasury.clone(),
+            },
+        },
+    )
+    .unwrap();
+
+    app.send_tokens(
+        addrs.user2.clone(),
+        vault_addr.clone(),
+        &coins(20, DENOM_OSMO),
+    )
+    .unwrap();
+    let res = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::Reconcile {},
+    )
+    .unwrap();
+    let reconciled = find_event(&res.events, "reconciled").unwrap();
+    assert_eq!(event_attr(reconciled, "swept_to"), Some(treasury.as_str()));
+
+    let swept = app.wrap().query_balance(&treasury, DENOM_OSMO).unwrap();
+    assert_eq!(swept.amount, Uint256::from(20u128));
+    assert_eq!(
+        query_vault_assets(&app, &vault_addr),
+        coins(100, DENOM_ATOM)
+    );
+    assert!(query_reconciliation(&app, &vault_addr)
+        .discrepancies
+        .is_empty());
+}
+
+fn query_asset_registry(
+    app: &App,
+    vault_addr: &Addr,
+    start_after: Option<&str>,
+    limit: Option<u32>,
+) -> Vec<WhitelistEntry> {
+    app.wrap()
+        .query_wasm_smart(
+            vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetAssetRegistry {
+                start_after: start_after.map(str::to_string),
+                limit,
+            }),
+        )
+        .unwrap()
+}
+
+#[test]
+fn test_asset_registry_stores_entry_metadata() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    let osmo = WhitelistEntry {
+        symbol: "OSMO".to_string(),
+        decimals: 6,
+        price_source_ids: vec![PriceSourceId {
+            source: "coingecko".to_string(),
+            id: "osmosis".to_string(),
+        }],
+        ..whitelist_entry(DENOM_OSMO)
+    };
+    let res = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::UpdateWhitelist {
+            to_add: Some(vec![osmo.clone(), whitelist_entry("uwasm")]),
+            to_remove: None,
+        },
+    )
+    .unwrap();
+    let event = find_event_with_attr(&res.events, "wasm", "method", "update_whitelist")
+        .expect("update whitelist event missing");
+    assert_eq!(event_attr(event, "tokens_added"), Some("1"));
+    assert_eq!(event_attr(event, "tokens_updated"), Some("1"));
+
+    assert_eq!(
+        query_asset_registry(&app, &vault_addr, None, None),
+        vec![
+            whitelist_entry(DENOM_ATOM),
+            osmo.clone(),
+            whitelist_entry("uwasm")
+        ]
+    );
+    assert_eq!(
+        query_asset_registry(&app, &vault_addr, Some(DENOM_ATOM), Some(1)),
+        vec![osmo]
+    );
+
+    let too_precise = WhitelistEntry {
+        decimals: 19,
+        ..whitelist_entry("uwasm")
+    };
+    let err = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::UpdateWhitelist {
+            to_add: Some(vec![too_precise]),
+            to_remove: None,
+        },
+    )
+    .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Decimal precision 19 not supported for denom: uwasm",
+    );
+}
+
+#[test]
+fn test_update_prices_rejects_decimals_mismatch() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    let atom = WhitelistEntry {
+        decimals: 6,
+        ..whitelist_entry(DENOM_ATOM)
+    };
+    update_whitelist_entry(&mut app, &vault_addr, &addrs.owner, atom);
+
+    let err = app
+        .execute_contract(
+            vault_addr.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdatePrices {
+                prices: atom_price(10),
+                swap_routes: None,
+            }),
+            &[],
+        )
+        .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Price for uatom uses 0 decimals but the asset registry has 6",
+    );
+
+    execute_update_prices(
+        &mut app,
+        &vault_addr,
+        vec![PriceInfo {
+            denom: DENOM_ATOM.to_string(),
+            price_usd: decimal(10),
+            decimals: 6,
+        }],
+        None,
+    );
+}
+
+#[test]
+fn test_deposit_respects_registry_flags() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    let atom = WhitelistEntry {
+        min_deposit: Uint256::new(50),
+        ..whitelist_entry(DENOM_ATOM)
+    };
+    let osmo = WhitelistEntry {
+        depositable: false,
+        ..whitelist_entry(DENOM_OSMO)
+    };
+    update_whitelist_entry(&mut app, &vault_addr, &addrs.owner, atom);
+    update_whitelist_entry(&mut app, &vault_addr, &addrs.owner, osmo);
+
+    let err = deposit_atom(&mut app, &vault_addr, &addrs.user1, 49).unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Deposit of uatom is below the minimum of 50",
+    );
+    deposit_atom(&mut app, &vault_addr, &addrs.user1, 50).unwrap();
+
+    let err = app
+        .execute_contract(
+            addrs.user1.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::Deposit {}),
+            &coins(100, DENOM_OSMO),
+        )
+        .unwrap_err();
+    assert_error_line(&err, "kind: Other, error: Deposits of uosmo are disabled");
+}
+
+#[test]
+fn test_swap_routes_require_tradable_denoms() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    deposit_atom(&mut app, &vault_addr, &addrs.user1, 100).unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_price(10), None);
+    let osmo = WhitelistEntry {
+        tradable: false,
+        ..whitelist_entry(DENOM_OSMO)
+    };
+    update_whitelist_entry(&mut app, &vault_addr, &addrs.owner, osmo);
+
+    // The route is dropped on its own, the rest of the update still applies
+    deposit_atom(&mut app, &vault_addr, &addrs.user2, 50).unwrap();
+    let route = atom_to_osmo_route(&app, 30, 30);
+    let res = execute_update_prices(&mut app, &vault_addr, atom_price(10), Some(vec![route]));
+    let rejected =
+        find_event(&res.events, "swap_route_rejected").expect("swap_route_rejected missing");
+    assert_eq!(event_attr(rejected, "reason"), Some("not_tradable"));
+    assert_eq!(
+        event_attr(rejected, "error"),
+        Some("Trading uosmo is disabled")
+    );
+    assert!(find_event(&res.events, "deposit_processed").is_some());
+    assert!(find_event(&res.events, "trade_initiated").is_none());
+}
+
+#[test]
+fn test_migrate_converts_legacy_whitelist() {
+    let mut deps = mock_dependencies();
+    for denom in [DENOM_ATOM, DENOM_OSMO] {
+        state::LEGACY_WHITELISTED_DENOMS
+            .save(&mut deps.storage, denom.to_string(), &())
+            .unwrap();
+    }
+    state::PRICES
+        .save(
+            &mut deps.storage,
+            DENOM_ATOM.to_string(),
+            &state::StoredPriceInfo {
+                price_usd: decimal(10),
+                decimals: 6,
+            },
+        )
+        .unwrap();
+
+    migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            share_subdenom: None,
+        },
+    )
+    .unwrap();
+
+    let registry = query::asset_registry(deps.as_ref(), None, None).unwrap();
+    assert_eq!(
+        registry,
+        vec![
+            WhitelistEntry {
+                symbol: DENOM_ATOM.to_string(),
+                decimals: 6,
+                ..whitelist_entry(DENOM_ATOM)
+            },
+            WhitelistEntry {
+                symbol: DENOM_OSMO.to_string(),
+                ..whitelist_entry(DENOM_OSMO)
+            },
+        ]
+    );
+    assert!(state::LEGACY_WHITELISTED_DENOMS.is_empty(&deps.storage));
+}
+
+fn query_strategy(app: &App, vault_addr: &Addr) -> StrategyResponse {
+    app.wrap()
+        .query_wasm_smart(vault_addr, &QueryMsg::Vault(VaultQueryMsg::GetStrategy {}))
+        .unwrap()
+}
+
+fn fixed_allocation(weights: &[(&str, &str)]) -> TargetAllocation {
+    TargetAllocation::Fixed {
+        weights: weights
+            .iter()
+            .map(|(denom, weight)| AllocationWeight {
+                denom: denom.to_string(),
+                weight: Decimal256::from_str(weight).unwrap(),
+            })
+            .collect(),
+    }
+}
+
+fn ai_allocation(bounds: &[(&str, &str, &str)]) -> TargetAllocation {
+    TargetAllocation::Ai {
+        weight_bounds: bounds
+            .iter()
+            .map(|(denom, min_weight, max_weight)| WeightBound {
+                denom: denom.to_string(),
+                min_weight: Decimal256::from_str(min_weight).unwrap(),
+                max_weight: Decimal256::from_str(max_weight).unwrap(),
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn test_strategy_change_waits_for_timelock() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    let strategy = query_strategy(&app, &vault_addr);
+    assert_eq!(strategy.target_allocation, None);
+    assert_eq!(strategy.timelock_seconds, 86_400);
+
+    let allocation = fixed_allocation(&[(DENOM_ATOM, "0.6"), (DENOM_OSMO, "0.4")]);
+    let res = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::ProposeStrategyChange {
+            target_allocation: Some(allocation.clone()),
+            timelock_seconds: Some(3600),
+        },
+    )
+    .unwrap();
+    let proposed = find_event(&res.events, "strategy_change_proposed")
+        .expect("strategy_change_proposed event missing");
+    let executable_at = app.block_info().time.plus_seconds(86_400);
+    assert_eq!(
+        event_attr(proposed, "executable_at"),
+        Some(executable_at.seconds().to_string().as_str())
+    );
+    assert_eq!(event_attr(proposed, "timelock_seconds"), Some("3600"));
+
+    let err = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::ExecuteStrategyChange {},
+    )
+    .unwrap_err();
+    assert_error_line(
+        &err,
+        &format!("kind: Other, error: Strategy change cannot be executed before {executable_at}"),
+    );
+
+    let err = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        VaultExecuteMsg::ExecuteStrategyChange {},
+    )
+    .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Caller is not the contract's current owner",
+    );
+
+    app.update_block(|block| block.time = block.time.plus_seconds(86_400));
+    let res = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::ExecuteStrategyChange {},
+    )
+    .unwrap();
+    assert!(find_event(&res.events, "strategy_change_executed").is_some());
+
+    let strategy = query_strategy(&app, &vault_addr);
+    assert_eq!(strategy.target_allocation, Some(allocation.clone()));
+    assert_eq!(strategy.timelock_seconds, 3600);
+    assert_eq!(strategy.pending_change, None);
+    let vault_state: VaultState = app
+        .wrap()
+        .query_wasm_smart(
+            &vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetVaultState {}),
+        )
+        .unwrap();
+    assert_eq!(vault_state.target_allocation, Some(allocation));
+
+    // The new timelock applies to the next proposal, which the owner can cancel
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::ProposeStrategyChange {
+            target_allocation: Some(ai_allocation(&[])),
+            timelock_seconds: None,
+        },
+    )
+    .unwrap();
+    let pending = query_strategy(&app, &vault_addr).pending_change.unwrap();
+    assert_eq!(
+        pending.executable_at,
+        app.block_info().time.plus_seconds(3600)
+    );
+
+    let res = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::CancelStrategyChange {},
+    )
+    .unwrap();
+    assert!(find_event(&res.events, "strategy_change_cancelled").is_some());
+    let err = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::ExecuteStrategyChange {},
+    )
+    .unwrap_err();
+    assert_error_line(&err, "kind: Other, error: No strategy change is pending");
+}
+
+#[test]
+fn test_strategy_change_validates_allocation() {
+    let (mut app, vault_addr, addrs) = proper_instantiate();
+    let cases = [
+        (
+            fixed_allocation(&[(DENOM_ATOM, "0.5"), (DENOM_OSMO, "0.4")]),
+            "Target allocation weights sum to 0.9, expected 1",
+        ),
+        (
+            fixed_allocation(&[(DENOM_ATOM, "0.5"), (DENOM_ATOM, "0.5")]),
+            "Duplicate target allocation for denom: uatom",
+        ),
+        (
+            fixed_allocation(&[(DENOM_ATOM, "0.5"), (DENOM_UNLISTED, "0.5")]),
+            "Token not whitelisted: utoken",
+        ),
+        (
+            ai_allocation(&[(DENOM_ATOM, "0.6", "0.5")]),
+            "Weight bounds for uatom must satisfy min <= max <= 1",
+        ),
+        (
+            ai_allocation(&[(DENOM_ATOM, "0", "1.1")]),
+            "Weight bounds for uatom must satisfy min <= max <= 1",
+        ),
+        (
+            ai_allocation(&[(DENOM_ATOM, "0.6", "1"), (DENOM_OSMO, "0.5", "1")]),
+            "Minimum weights sum to 1.1, more than 1",
+        ),
+        (
+            ai_allocation(&[(DENOM_ATOM, "0", "0.5"), (DENOM_ATOM, "0", "0.5")]),
+            "Duplicate target allocation for denom: uatom",
+        ),
+        (
+            ai_allocation(&[(DENOM_UNLISTED, "0", "0.5")]),
+            "Token not whitelisted: utoken",
+        ),
+    ];
+    for (allocation, expected) in cases {
+        let err = execute_vault_msg(
+            &mut app,
+            &vault_addr,
+            &addrs.owner,
+            VaultExecuteMsg::ProposeStrategyChange {
+                target_allocation: Some(allocation),
+                timelock_seconds: None,
+            },
+        )
+        .unwrap_err();
+        assert_error_line(&err, &format!("kind: Other, error: {expected}"));
+    }
+
+    // Bounds that leave the advisor room to allocate are accepted
+    let allocation = ai_allocation(&[(DENOM_ATOM, "0.1", "0.6"), (DENOM_OSMO, "0", "0.5")]);
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::ProposeStrategyChange {
+            target_allocation: Some(allocation.clone()),
+            timelock_seconds: None,
+        },
+    )
+    .unwrap();
+    let pending = query_strategy(&app, &vault_addr).pending_change.unwrap();
+    assert_eq!(pending.target_allocation, Some(allocation));
+
+    let err = execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::ProposeStrategyChange {
+            target_allocation: None,
+            timelock_seconds: None,
+        },
+    )
+    .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Strategy change must set a target allocation or a timelock",
+    );
+}
+
+fn update_swap_allowlist(
+    app: &mut App,
+    vault_addr: &Addr,
+    owner: &Addr,
+    venues_to_add: &[&str],
+    pools_to_add: &[(&str, &str)],
+) -> StdResult<AppResponse> {
+    execute_vault_msg(
+        app,
+        vault_addr,
+        owner,
+        VaultExecuteMsg::UpdateSwapAllowlist {
+            enabled: Some(true),
+            venues_to_add: Some(venues_to_add.iter().map(|v| v.to_string()).collect()),
+            venues_to_remove: None,
+            pools_to_add: Some(
+                pools_to_add
+                    .iter()
+                    .map(|(venue, pool)| AllowedPool {
+                        venue: venue.to_string(),
+                        pool: pool.to_string(),
+                    })
+                    .collect(),
+            ),
+            pools_to_remove: None,
+        },
+    )
+}
+
+fn query_swap_allowlist(app: &App, vault_addr: &Addr) -> SwapAllowlistResponse {
+    app.wrap()
+        .query_wasm_smart(
+            vault_addr,
+            &QueryMsg::Vault(VaultQueryMsg::GetSwapAllowlist {
+                start_after: None,
+                limit: None,
+            }),
+        )
+        .unwrap()
+}
+
+#[test]
+fn test_swap_allowlist_rejects_unlisted_venues_and_pools() {
+    let (mut app, vault_addr, addrs) = instantiate_with_mock_skip();
+    deposit_atom(&mut app, &vault_addr, &addrs.user1, 100).unwrap();
+    execute_update_prices(&mut app, &vault_addr, atom_and_osmo_prices(), None);
+
+    update_swap_allowlist(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        &["neutron-astroport"],
+        &[],
+    )
+    .unwrap();
+
+    let try_route = |app: &mut App, route: SwapRoute| {
+        app.execute_contract(
+            vault_addr.clone(),
+            vault_addr.clone(),
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdatePrices {
+                prices: atom_and_osmo_prices(),
+                swap_routes: Some(vec![route]),
+            }),
+            &[],
+        )
+    };
+
+    // Unlisted routes are dropped while the prices still land
+    let route = atom_to_osmo_route(&app, 30, 30);
+    let res = try_route(&mut app, route).unwrap();
+    let rejected =
+        find_event(&res.events, "swap_route_rejected").expect("swap_route_rejected missing");
+    assert_eq!(event_attr(rejected, "reason"), Some("not_allowlisted"));
+    assert_eq!(
+        event_attr(rejected, "error"),
+        Some("Pool pool-1 on neutron-astroport is not on the swap allowlist")
+    );
+    assert!(find_event(&res.events, "price_updated").is_some());
+    assert!(find_event(&res.events, "trade_initiated").is_none());
+
+    let mut route = atom_to_osmo_route(&app, 30, 30);
+    route.swap_venue_name = "attacker-dex".to_string();
+    let res = try_route(&mut app, route).unwrap();
+    let rejected =
+        find_event(&res.events, "swap_route_rejected").expect("swap_route_rejected missing");
+    assert_eq!(
+        event_attr(rejected, "error"),
+        Some("Swap venue attacker-dex is not on the swap allowlist")
+    );
+
+    update_swap_allowlist(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        &[],
+        &[("neutron-astroport", "pool-1")],
+    )
+    .unwrap();
+    let route = atom_to_osmo_route(&app, 30, 30);
+    let res = try_route(&mut app, route).unwrap();
+    assert!(find_event(&res.events, "trade_completed").is_some());
+
+    assert_eq!(
+        query_swap_allowlist(&app, &vault_addr),
+        SwapAllowlistResponse {
+            enabled: true,
+            venues: vec![SwapVenueAllowlist {
+                venue: "neutron-astroport".to_string(),
+                pools: vec!["pool-1".to_string()],
+            }],
+        }
+    );
+
+    // Dropping the venue drops its pools, and pools need a listed venue
+    execute_vault_msg(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        VaultExecuteMsg::UpdateSwapAllowlist {
+            enabled: None,
+            venues_to_add: None,
+            venues_to_remove: Some(vec!["neutron-astroport".to_string()]),
+            pools_to_add: None,
+            pools_to_remove: None,
+        },
+    )
+    .unwrap();
+    assert!(query_swap_allowlist(&app, &vault_addr).venues.is_empty());
+    let err = update_swap_allowlist(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        &[],
+        &[("neutron-astroport", "pool-1")],
+    )
+    .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Swap venue neutron-astroport is not on the swap allowlist",
+    );
+}
+
+#[test]
+fn test_swap_allowlist_applies_to_withdrawal_routes() {
+    let (mut app, vault_addr, addrs) = instantiate_split_vault();
+    update_swap_allowlist(
+        &mut app,
+        &vault_addr,
+        &addrs.owner,
+        &["neutron-astroport"],
+        &[],
+    )
+    .unwrap();
+
+    let err = withdraw_to_atom(
+        &mut app,
+        &vault_addr,
+        &addrs.user1,
+        0,
+        vec![osmo_to_atom_withdraw_route()],
+    )
+    .unwrap_err();
+    assert_error_line(
+        &err,
+        "kind: Other, error: Pool pool-1 on neutron-astroport is not on the swap allowlist",
+    );
+
+    // Plain withdrawals never touch a venue
+    let shares = query_user_shares(&app, &vault_addr, &addrs.user1);
+    app.execute_contract(
+        addrs.user1.clone(),
+        vault_addr.clone(),
+        &ExecuteMsg::Vault(VaultExecuteMsg::Withdraw { shares }),
+        &[],
+    )
+    .unwrap();
+}
@@ -0,0 +1,74 @@
+use cosmwasm_std::{AnyMsg, Binary, Coin, CosmosMsg};
+
+// Neutron's Token Factory module keeps the upstream Osmosis proto package
+const MSG_CREATE_DENOM: &str = "/osmosis.tokenfactory.v1beta1.MsgCreateDenom";
+const MSG_MINT: &str = "/osmosis.tokenfactory.v1beta1.MsgMint";
+const MSG_BURN: &str = "/osmosis.tokenfactory.v1beta1.MsgBurn";
+
+/// Full denom of a Token Factory token created by `creator`.
+pub fn full_denom(creator: &str, subdenom: &str) -> String {
+    format!("factory/{creator}/{subdenom}")
+}
+
+/// `MsgCreateDenom { sender, subdenom }`
+pub fn create_denom_msg(sender: &str, subdenom: &str) -> CosmosMsg {
+    let mut value = Vec::new();
+    encode_string(&mut value, 1, sender);
+    encode_string(&mut value, 2, subdenom);
+    any_msg(MSG_CREATE_DENOM, value)
+}
+
+/// `MsgMint { sender, amount, mint_to_address }`
+pub fn mint_msg(sender: &str, amount: &Coin, mint_to_address: &str) -> CosmosMsg {
+    let mut value = Vec::new();
+    encode_string(&mut value, 1, sender);
+    encode_bytes(&mut value, 2, &encode_coin(amount));
+    encode_string(&mut value, 3, mint_to_address);
+    any_msg(MSG_MINT, value)
+}
+
+/// `MsgBurn { sender, amount, burn_from_address }`
+pub fn burn_msg(sender: &str, amount: &Coin, burn_from_address: &str) -> CosmosMsg {
+    let mut value = Vec::new();
+    encode_string(&mut value, 1, sender);
+    encode_bytes(&mut value, 2, &encode_coin(amount));
+    encode_string(&mut value, 3, burn_from_address);
+    any_msg(MSG_BURN, value)
+}
+
+fn any_msg(type_url: &str, value: Vec<u8>) -> CosmosMsg {
+    CosmosMsg::Any(AnyMsg {
+        type_url: type_url.to_string(),
+        value: Binary::new(value),
+    })
+}
+
+// cosmos.base.v1beta1.Coin { denom, amount }, with the amount as a decimal string
+fn encode_coin(coin: &Coin) -> Vec<u8> {
+    let mut buf = Vec::new();
+    encode_string(&mut buf, 1, &coin.denom);
+    encode_string(&mut buf, 2, &coin.amount.to_string());
+    buf
+}
+
+fn encode_string(buf: &mut Vec<u8>, field: u32, value: &str) {
+    encode_bytes(buf, field, value.as_bytes());
+}
+
+// Length-delimited field; proto3 omits empty values
+fn encode_bytes(buf: &mut Vec<u8>, field: u32, value: &[u8]) {
+    if value.is_empty() {
+        return;
+    }
+    encode_varint(buf, u64::from(field << 3 | 2));
+    encode_varint(buf, value.len() as u64);
+    buf.extend_from_slice(value);
+}
+
+fn encode_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
@@ -0,0 +1,57 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+use vault::WhitelistEntry;
+
+/// Asset registry file: one `[[assets]]` table per whitelisted denom
+#[derive(Deserialize)]
+struct AssetRegistryFile {
+    assets: Vec<WhitelistEntry>,
+}
+
+/// Read vault whitelist entries from a TOML asset registry file.
+pub async fn load_asset_registry(path: &Path) -> anyhow::Result<Vec<WhitelistEntry>> {
+    let contents = tokio::fs::read_to_string(path).await.map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to read asset registry file {}: {}",
+            path.display(),
+            e
+        )
+    })?;
+    let file: AssetRegistryFile = toml::from_str(&contents)?;
+
+    Ok(file.assets)
+}
+
+/// Operator view of an asset, keyed by denom in its `asset_registry` config var
+#[derive(Serialize)]
+struct OperatorAssetInfo<'a> {
+    coingecko_id: Option<&'a str>,
+    decimals: u8,
+    symbol: &'a str,
+}
+
+/// Encode whitelist entries as the operator's `asset_registry` config var.
+pub fn operator_asset_registry(entries: &[WhitelistEntry]) -> anyhow::Result<String> {
+    let assets: BTreeMap<&str, OperatorAssetInfo> = entries
+        .iter()
+        .map(|entry| {
+            let coingecko_id = entry
+                .price_source_ids
+                .iter()
+                .find(|source_id| source_id.source == "coingecko")
+                .map(|source_id| source_id.id.as_str());
+
+            (
+                entry.denom.as_str(),
+                OperatorAssetInfo {
+                    coingecko_id,
+                    decimals: entry.decimals,
+                    symbol: &entry.symbol,
+                },
+            )
+        })
+        .collect();
+
+    Ok(serde_json::to_string(&assets)?)
+}
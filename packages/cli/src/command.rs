@@ -1,10 +1,10 @@
 use crate::output::OutputFormat;
-use ai_portfolio_types::TradeStrategy;
 use ai_portfolio_utils::path::repo_root;
 use clap::{Parser, ValueEnum};
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use vault::TargetAllocation;
 use wavs_types::ChainKey;
 
 #[derive(Clone, Parser)]
@@ -25,14 +25,23 @@ pub enum CliCommand {
         #[arg(long)]
         code_id: u64,
 
-        /// Initial whitelisted denominations for the vault
-        #[arg(long, value_delimiter = ',')]
-        initial_whitelisted_denoms: Vec<String>,
+        /// TOML asset registry with the vault's initial whitelist entries
+        #[arg(long, default_value_os_t = default_asset_registry_file())]
+        asset_registry_file: PathBuf,
 
         /// The Skip entry point address (if not provided, will use default for the chain)
         #[arg(long)]
         skip_entry_point: Option<String>,
 
+        /// Issue vault shares as a Token Factory denom with this subdenom
+        #[arg(long)]
+        share_subdenom: Option<String>,
+
+        /// Initial target allocation as JSON, e.g. {"fixed":{"weights":[{"denom":"untrn","weight":"1"}]}}
+        /// (later changes go through the vault's strategy timelock)
+        #[arg(long, value_parser = parse_target_allocation)]
+        target_allocation: Option<TargetAllocation>,
+
         #[clap(flatten)]
         args: CliArgs,
     },
@@ -46,6 +55,10 @@ pub enum CliCommand {
         #[arg(long)]
         new_code_id: u64,
 
+        /// Convert existing share balances into a Token Factory denom with this subdenom
+        #[arg(long)]
+        share_subdenom: Option<String>,
+
         #[clap(flatten)]
         args: CliArgs,
     },
@@ -88,9 +101,6 @@ pub enum CliCommand {
         #[arg(long)]
         cron_schedule: String,
 
-        #[arg(long)]
-        trade_strategy: TradeStrategy,
-
         #[arg(long)]
         aggregator_url: Url,
 
@@ -100,6 +110,72 @@ pub enum CliCommand {
         #[arg(long)]
         ipfs_gateway_url: Url,
 
+        /// comma-separated price sources the operator aggregates
+        #[arg(long, default_value = "coingecko,pyth,skip,astroport")]
+        price_sources: String,
+
+        /// quotes that must agree before the operator submits a price
+        #[arg(long, default_value_t = 2)]
+        price_min_sources: usize,
+
+        /// largest distance from the median price a quote may have, in basis points
+        #[arg(long, default_value_t = 200)]
+        price_max_spread_bps: u64,
+
+        /// TOML asset registry the operator reads CoinGecko ids, decimals and symbols from
+        #[arg(long, default_value_os_t = default_asset_registry_file())]
+        asset_registry_file: PathBuf,
+
+        /// run the AI advisor so every operator produces the same payload (needs --ai-model-digest)
+        #[arg(long, requires = "ai_model_digest")]
+        ai_consensus: bool,
+
+        /// digest the operators' LLM must match, as listed by the Ollama server's /api/tags
+        /// (other LLM providers can't be pinned, so this needs --llm-provider ollama)
+        #[arg(long)]
+        ai_model_digest: Option<String>,
+
+        /// JSON list of routes ({ venue, operations }) consensus mode trades along instead of
+        /// asking Skip, so every operator plans the same pools
+        #[arg(long, requires = "ai_consensus")]
+        consensus_routes_file: Option<PathBuf>,
+
+        /// in consensus mode, AI weights are rounded to multiples of this many basis points
+        #[arg(long, default_value_t = 500)]
+        ai_weight_bucket_bps: u64,
+
+        /// LLM server behind --llm-base-url, either openai or ollama
+        #[arg(long, default_value = "openai")]
+        llm_provider: String,
+
+        /// OpenAI-compatible API the AI advisor calls, up to and including the version path
+        #[arg(long, default_value = "http://localhost:11434/v1")]
+        llm_base_url: String,
+
+        /// model name sent to the LLM API
+        #[arg(long, default_value = "llama3:8b-instruct-q4_0")]
+        llm_model: String,
+
+        /// operator env var holding the LLM API key, e.g. WAVS_ENV_LLM_API_KEY
+        #[arg(long)]
+        llm_api_key_env: Option<String>,
+
+        /// LLM sampling temperature, ignored in consensus mode
+        #[arg(long, default_value_t = 0.7)]
+        llm_temperature: f32,
+
+        /// LLM nucleus sampling cutoff, ignored in consensus mode
+        #[arg(long, default_value_t = 0.9)]
+        llm_top_p: f32,
+
+        /// most tokens the LLM may generate per answer
+        #[arg(long, default_value_t = 2048)]
+        llm_max_tokens: u32,
+
+        /// how long the operator waits on the LLM API
+        #[arg(long, default_value_t = 120)]
+        llm_timeout_seconds: u64,
+
         #[clap(flatten)]
         args: CliArgs,
     },
@@ -183,9 +259,10 @@ pub enum CliCommand {
         #[arg(long)]
         contract_address: String,
 
-        /// Tokens to add to the whitelist (comma-separated)
-        #[arg(long, value_delimiter = ',')]
-        to_add: Option<Vec<String>>,
+        /// TOML asset registry whose entries are added to the whitelist, replacing the
+        /// metadata of denoms already listed
+        #[arg(long)]
+        to_add: Option<PathBuf>,
 
         /// Tokens to remove from the whitelist (comma-separated)
         #[arg(long, value_delimiter = ',')]
@@ -225,6 +302,17 @@ pub struct CliArgs {
     pub output_format: OutputFormat,
 }
 
+fn parse_target_allocation(s: &str) -> Result<TargetAllocation, String> {
+    serde_json::from_str(s).map_err(|e| format!("Invalid JSON: {e}"))
+}
+
+fn default_asset_registry_file() -> PathBuf {
+    repo_root()
+        .expect("could not determine repo root")
+        .join("backend")
+        .join("assets.toml")
+}
+
 impl CliArgs {
     pub fn output(&self) -> crate::output::Output {
         let directory = repo_root()
@@ -1,3 +1,4 @@
+mod assets;
 mod command;
 mod context;
 mod ipfs;
@@ -68,8 +69,10 @@ async fn main() -> anyhow::Result<()> {
         }
         CliCommand::InstantiateVault {
             code_id,
-            initial_whitelisted_denoms,
+            asset_registry_file,
             skip_entry_point,
+            share_subdenom,
+            target_allocation,
             args,
         } => {
             let client = ctx.signing_client().await?;
@@ -103,10 +106,14 @@ async fn main() -> anyhow::Result<()> {
                     .to_string(),
             };
 
+            let initial_whitelist = assets::load_asset_registry(&asset_registry_file).await?;
+
             let instantiate_msg = InstantiateMsg {
                 service_manager,
-                initial_whitelisted_denoms,
+                initial_whitelist,
                 skip_entry_point,
+                share_subdenom,
+                target_allocation,
             };
 
             let (contract_addr, tx_resp) = client
@@ -197,10 +204,25 @@ async fn main() -> anyhow::Result<()> {
             component_operator_cid_file,
             component_aggregator_cid_file,
             cron_schedule,
-            trade_strategy,
             aggregator_url,
             ipfs_api_url,
             ipfs_gateway_url,
+            price_sources,
+            price_min_sources,
+            price_max_spread_bps,
+            asset_registry_file,
+            ai_consensus,
+            ai_model_digest,
+            consensus_routes_file,
+            ai_weight_bucket_bps,
+            llm_provider,
+            llm_base_url,
+            llm_model,
+            llm_api_key_env,
+            llm_temperature,
+            llm_top_p,
+            llm_max_tokens,
+            llm_timeout_seconds,
             args,
         } => {
             let output_directory = args.output().directory;
@@ -213,6 +235,17 @@ async fn main() -> anyhow::Result<()> {
             let middleware_instantiation_file =
                 output_directory.join(middleware_instantiation_file);
 
+            let asset_registry = assets::operator_asset_registry(
+                &assets::load_asset_registry(&asset_registry_file).await?,
+            )?;
+
+            let consensus_routes = match &consensus_routes_file {
+                Some(path) => Some(tokio::fs::read_to_string(path).await.map_err(|e| {
+                    anyhow::anyhow!("Failed to read file {}: {}", path.display(), e)
+                })?),
+                None => None,
+            };
+
             async fn read_and_decode<T: serde::de::DeserializeOwned>(
                 path: std::path::PathBuf,
             ) -> anyhow::Result<T> {
@@ -296,18 +329,53 @@ async fn main() -> anyhow::Result<()> {
                             config: [
                                 ("chain".to_string(), args.chain.to_string()),
                                 ("address".to_string(), address.clone()),
+                                ("price_sources".to_string(), price_sources.clone()),
+                                (
+                                    "price_min_sources".to_string(),
+                                    price_min_sources.to_string(),
+                                ),
                                 (
-                                    "trade_strategy".to_string(),
-                                    serde_json::to_string(&trade_strategy)?,
+                                    "price_max_spread_bps".to_string(),
+                                    price_max_spread_bps.to_string(),
+                                ),
+                                ("asset_registry".to_string(), asset_registry),
+                                ("ai_consensus".to_string(), ai_consensus.to_string()),
+                                (
+                                    "ai_weight_bucket_bps".to_string(),
+                                    ai_weight_bucket_bps.to_string(),
+                                ),
+                                ("llm_provider".to_string(), llm_provider.clone()),
+                                ("llm_base_url".to_string(), llm_base_url.clone()),
+                                ("llm_model".to_string(), llm_model.clone()),
+                                ("llm_temperature".to_string(), llm_temperature.to_string()),
+                                ("llm_top_p".to_string(), llm_top_p.to_string()),
+                                ("llm_max_tokens".to_string(), llm_max_tokens.to_string()),
+                                (
+                                    "llm_timeout_seconds".to_string(),
+                                    llm_timeout_seconds.to_string(),
                                 ),
                             ]
                             .into_iter()
+                            .chain(
+                                ai_model_digest
+                                    .map(|digest| ("ai_model_digest".to_string(), digest)),
+                            )
+                            .chain(
+                                consensus_routes
+                                    .map(|routes| ("consensus_routes".to_string(), routes)),
+                            )
+                            .chain(
+                                llm_api_key_env
+                                    .clone()
+                                    .map(|key| ("llm_api_key_env".to_string(), key)),
+                            )
                             .collect(),
                             env_keys: [
                                 "WAVS_ENV_COINGECKO_API_KEY".to_string(),
                                 "WAVS_ENV_SKIP_API_KEY".to_string(),
                             ]
                             .into_iter()
+                            .chain(llm_api_key_env.clone())
                             .collect(),
                         };
 
@@ -494,13 +562,13 @@ async fn main() -> anyhow::Result<()> {
         CliCommand::MigrateVault {
             contract_address,
             new_code_id,
+            share_subdenom,
             args,
         } => {
             let client = ctx.signing_client().await?;
             let contract_addr = ctx.parse_address(&contract_address).await?;
 
-            // Empty migrate message
-            let migrate_msg = vault::MigrateMsg {};
+            let migrate_msg = vault::MigrateMsg { share_subdenom };
 
             let tx_resp = client
                 .contract_migrate(&contract_addr, new_code_id, &migrate_msg, None)
@@ -644,6 +712,11 @@ async fn main() -> anyhow::Result<()> {
                 ));
             }
 
+            let to_add = match to_add {
+                Some(path) => Some(assets::load_asset_registry(&path).await?),
+                None => None,
+            };
+
             let update_whitelist_msg =
                 vault::ExecuteMsg::Vault(vault::VaultExecuteMsg::UpdateWhitelist {
                     to_add: to_add.clone(),
@@ -660,7 +733,8 @@ async fn main() -> anyhow::Result<()> {
             );
 
             if let Some(ref added) = to_add {
-                println!("Tokens added to whitelist: {}", added.join(", "));
+                let denoms: Vec<&str> = added.iter().map(|entry| entry.denom.as_str()).collect();
+                println!("Tokens added to whitelist: {}", denoms.join(", "));
             }
             if let Some(ref removed) = to_remove {
                 println!("Tokens removed from whitelist: {}", removed.join(", "));
@@ -3,6 +3,19 @@ use ai_portfolio_utils::{
     client::vault::{VaultExecutor, VaultQuerier},
 };
 
+/// Whitelist entry with default metadata whose prices are quoted per base unit
+pub fn whitelist_entry(denom: &str) -> vault::msg::WhitelistEntry {
+    vault::msg::WhitelistEntry {
+        denom: denom.to_string(),
+        symbol: denom.to_string(),
+        decimals: 0,
+        price_source_ids: vec![],
+        min_deposit: cosmwasm_std::Uint256::zero(),
+        depositable: true,
+        tradable: true,
+    }
+}
+
 pub async fn get_admin(querier: &VaultQuerier, expected: &str) {
     let ownership = querier.ownership().await.unwrap();
     assert_eq!(ownership.owner.unwrap().to_string(), expected);
@@ -10,14 +23,14 @@ pub async fn get_admin(querier: &VaultQuerier, expected: &str) {
 
 pub struct VaultInstantiationProps {
     pub service_manager: String,
-    pub initial_whitelisted_denoms: Vec<String>,
+    pub initial_whitelist: Vec<vault::msg::WhitelistEntry>,
     pub skip_entry_point: String,
 }
 
 pub async fn test_vault_instantiation(querier: &VaultQuerier, props: VaultInstantiationProps) {
     let VaultInstantiationProps {
         service_manager: _,
-        initial_whitelisted_denoms,
+        mut initial_whitelist,
         skip_entry_point: _,
     } = props;
 
@@ -28,12 +41,9 @@ pub async fn test_vault_instantiation(querier: &VaultQuerier, props: VaultInstan
     let vault_value = querier.vault_value().await.unwrap();
     assert_eq!(vault_value, cosmwasm_std::Decimal256::zero());
 
-    let whitelisted_denoms = querier.whitelisted_denoms().await.unwrap();
-    assert_eq!(whitelisted_denoms.len(), initial_whitelisted_denoms.len());
-
-    for denom in &initial_whitelisted_denoms {
-        assert!(whitelisted_denoms.contains(denom));
-    }
+    let asset_registry = querier.asset_registry(None, None).await.unwrap();
+    initial_whitelist.sort_by(|a, b| a.denom.cmp(&b.denom));
+    assert_eq!(asset_registry, initial_whitelist);
 
     let vault_assets = querier.vault_assets().await.unwrap();
     assert_eq!(vault_assets.len(), 0);
@@ -75,7 +85,7 @@ pub async fn test_vault_deposit(
 }
 
 pub struct VaultWhitelistProps {
-    pub to_add: Option<Vec<String>>,
+    pub to_add: Option<Vec<vault::msg::WhitelistEntry>>,
     pub to_remove: Option<Vec<String>>,
     pub expected_final_denoms: Vec<String>,
 }
@@ -270,7 +280,7 @@ pub async fn test_vault_comprehensive_workflow(
 
     // Add new token to whitelist
     executor
-        .update_whitelist(Some(vec![new_whitelist_denom.clone()]), None)
+        .update_whitelist(Some(vec![whitelist_entry(&new_whitelist_denom)]), None)
         .await
         .unwrap();
 
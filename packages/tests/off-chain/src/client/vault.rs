@@ -1,3 +1,4 @@
+use ai_portfolio_test_common::shared_tests::vault::whitelist_entry;
 use ai_portfolio_utils::addr::AnyAddr;
 use ai_portfolio_utils::client::vault::{VaultExecutor, VaultQuerier};
 use cosmwasm_std::{Addr, Coin};
@@ -24,12 +25,14 @@ impl VaultClient {
 
         let msg = vault::msg::InstantiateMsg {
             service_manager: admin.to_string(),
-            initial_whitelisted_denoms: vec![
-                "uatom".to_string(),
-                "uosmo".to_string(),
-                "ujuno".to_string(),
+            initial_whitelist: vec![
+                whitelist_entry("uatom"),
+                whitelist_entry("uosmo"),
+                whitelist_entry("ujuno"),
             ],
             skip_entry_point: admin.to_string(),
+            share_subdenom: None,
+            target_allocation: None,
         };
 
         let address = app_client.with_app_mut(|app| {
@@ -83,7 +86,7 @@ impl VaultClient {
     /// Update whitelist (admin only) - uses the contract's owner address
     pub async fn update_whitelist(
         &self,
-        to_add: Option<Vec<String>>,
+        to_add: Option<Vec<vault::msg::WhitelistEntry>>,
         to_remove: Option<Vec<String>>,
     ) -> anyhow::Result<()> {
         // Get the actual contract owner from ownership query
@@ -5,9 +5,9 @@ use ai_portfolio_test_common::shared_tests::vault::{
     test_vault_instantiation as shared_test_instantiation,
     test_vault_price_updates as shared_test_price_updates,
     test_vault_whitelist_management as shared_test_whitelist_management,
-    test_vault_withdrawal as shared_test_withdrawal, VaultComprehensiveWorkflowProps,
-    VaultDepositProps, VaultErrorHandlingProps, VaultInstantiationProps, VaultPriceUpdateProps,
-    VaultWhitelistProps, VaultWithdrawalProps,
+    test_vault_withdrawal as shared_test_withdrawal, whitelist_entry,
+    VaultComprehensiveWorkflowProps, VaultDepositProps, VaultErrorHandlingProps,
+    VaultInstantiationProps, VaultPriceUpdateProps, VaultWhitelistProps, VaultWithdrawalProps,
 };
 use ai_portfolio_utils::tracing::tracing_init;
 use cosmwasm_std::{coin, coins, Decimal256, Uint256};
@@ -29,10 +29,10 @@ async fn test_vault_initialization() {
         &vault.querier,
         VaultInstantiationProps {
             service_manager: app_client.admin().to_string(),
-            initial_whitelisted_denoms: vec![
-                "uatom".to_string(),
-                "uosmo".to_string(),
-                "ujuno".to_string(),
+            initial_whitelist: vec![
+                whitelist_entry("uatom"),
+                whitelist_entry("uosmo"),
+                whitelist_entry("ujuno"),
             ],
             skip_entry_point: app_client.admin().to_string(),
         },
@@ -199,7 +199,7 @@ async fn test_vault_whitelist_management() {
         &vault.querier,
         &vault.executor,
         VaultWhitelistProps {
-            to_add: Some(vec!["uwasm".to_string()]),
+            to_add: Some(vec![whitelist_entry("uwasm")]),
             to_remove: None,
             expected_final_denoms: vec![
                 "uatom".to_string(),
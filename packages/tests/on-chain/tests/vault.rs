@@ -6,9 +6,10 @@ use ai_portfolio_test_common::shared_tests::vault::{
     test_vault_multiple_deposits as shared_test_multiple_deposits,
     test_vault_price_updates as shared_test_price_updates,
     test_vault_whitelist_management as shared_test_whitelist_management,
-    test_vault_withdrawal as shared_test_withdrawal, VaultComprehensiveWorkflowProps,
-    VaultDepositProps, VaultErrorHandlingProps, VaultInstantiationProps, VaultMultiDepositProps,
-    VaultPriceUpdateProps, VaultWhitelistProps, VaultWithdrawalProps,
+    test_vault_withdrawal as shared_test_withdrawal, whitelist_entry,
+    VaultComprehensiveWorkflowProps, VaultDepositProps, VaultErrorHandlingProps,
+    VaultInstantiationProps, VaultMultiDepositProps, VaultPriceUpdateProps, VaultWhitelistProps,
+    VaultWithdrawalProps,
 };
 use ai_portfolio_utils::tracing::tracing_init;
 use cosmwasm_std::{coin, coins, Decimal256, Uint256};
@@ -30,7 +31,7 @@ async fn test_vault_initialization() {
         &vault.querier,
         VaultInstantiationProps {
             service_manager: "service_manager".to_string(),
-            initial_whitelisted_denoms: vec!["uatom".to_string(), "uosmo".to_string()],
+            initial_whitelist: vec![whitelist_entry("uatom"), whitelist_entry("uosmo")],
             skip_entry_point: "skip_entry".to_string(),
         },
     )
@@ -196,7 +197,7 @@ async fn test_vault_whitelist_management() {
         &vault.querier,
         &vault.executor,
         VaultWhitelistProps {
-            to_add: Some(vec!["ujuno".to_string(), "uluna".to_string()]),
+            to_add: Some(vec![whitelist_entry("ujuno"), whitelist_entry("uluna")]),
             to_remove: Some(vec!["uosmo".to_string()]),
             expected_final_denoms: vec![
                 "uatom".to_string(),
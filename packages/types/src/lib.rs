@@ -6,14 +6,43 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub enum TradeStrategy {
-    AI, // Placeholder for now
+    AI(BTreeMap<String, WeightBounds>), // Denoms left out may take any weight
     Fixed(BTreeMap<String, Decimal256>),
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WeightBounds {
+    pub min: Decimal256,
+    pub max: Decimal256,
+}
+
+impl Default for WeightBounds {
+    fn default() -> Self {
+        Self {
+            min: Decimal256::zero(),
+            max: Decimal256::one(),
+        }
+    }
+}
+
 impl TradeStrategy {
     pub fn validate(&self) -> Result<()> {
         match self {
-            TradeStrategy::AI => {}
+            TradeStrategy::AI(bounds) => {
+                let mut total_min = Decimal256::zero();
+                for (denom, bound) in bounds {
+                    ensure!(
+                        bound.min <= bound.max && bound.max <= Decimal256::one(),
+                        "Weight bounds for {denom} must satisfy min <= max <= 1"
+                    );
+                    total_min = total_min.checked_add(bound.min)?;
+                }
+
+                ensure!(
+                    total_min <= Decimal256::one(),
+                    "Minimum weights must not sum to more than one"
+                )
+            }
             TradeStrategy::Fixed(map) => {
                 let mut total = Decimal256::zero();
                 for allocation in map.values() {
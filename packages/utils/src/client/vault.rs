@@ -2,7 +2,7 @@
 //! Define helper methods here and they'll be available for all backends
 
 use anyhow::{anyhow, Result};
-use cosmwasm_std::{Addr, Coin, Decimal256, Uint256};
+use cosmwasm_std::{Addr, Coin, Decimal256, Timestamp, Uint256};
 use serde::de::DeserializeOwned;
 use std::fmt::Debug;
 
@@ -15,7 +15,11 @@ use crate::{
 };
 
 use vault::{
-    DepositRequest, ExecuteMsg, PriceInfo, QueryMsg, VaultExecuteMsg, VaultQueryMsg, VaultState,
+    AccruedFees, AllowedPool, DepositCaps, DepositRequest, DepositorAllowlistResponse,
+    EnvelopeStatus, ExecuteMsg, FeeConfig, NavSnapshot, PauseState, PriceGuard, PriceGuardAction,
+    PriceGuardConfig, PriceInfo, QueryMsg, ReconciliationReport, StrategyResponse, SurplusPolicy,
+    SwapAllowlistResponse, TargetAllocation, TradeRecord, TradingLimits, TradingLimitsResponse,
+    VaultExecuteMsg, VaultQueryMsg, VaultState, WhitelistEntry, WithdrawRequest, WithdrawRoute,
 };
 
 #[derive(Clone)]
@@ -60,6 +64,21 @@ impl VaultQuerier {
         Ok(resp)
     }
 
+    /// Query whitelist entries with their asset metadata
+    pub async fn asset_registry(
+        &self,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<Vec<WhitelistEntry>> {
+        let resp: Vec<WhitelistEntry> = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetAssetRegistry {
+                start_after,
+                limit,
+            }))
+            .await?;
+        Ok(resp)
+    }
+
     /// Query a specific deposit request by ID
     pub async fn deposit_request(&self, deposit_id: u64) -> Result<DepositRequest> {
         let resp: DepositRequest = self
@@ -85,6 +104,111 @@ impl VaultQuerier {
         Ok(resp)
     }
 
+    /// Query a recorded trade by ID
+    pub async fn trade(&self, id: u64) -> Result<TradeRecord> {
+        let resp: TradeRecord = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetTrade { id }))
+            .await?;
+        Ok(resp)
+    }
+
+    /// List recorded trades with pagination, optionally only those touching `denom`
+    pub async fn list_trades(
+        &self,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        denom: Option<String>,
+    ) -> Result<Vec<TradeRecord>> {
+        let resp: Vec<TradeRecord> = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::ListTrades {
+                start_after,
+                limit,
+                denom,
+            }))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query the current USD value of a single share
+    pub async fn share_price(&self) -> Result<Decimal256> {
+        let resp: Decimal256 = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetSharePrice {}))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query the Token Factory share denom, if the vault issues share tokens
+    pub async fn share_denom(&self) -> Result<Option<String>> {
+        let resp: Option<String> = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetShareDenom {}))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query NAV snapshots between `start` and `end` (inclusive), oldest first
+    pub async fn nav_history(
+        &self,
+        start: Option<Timestamp>,
+        end: Option<Timestamp>,
+        limit: Option<u32>,
+    ) -> Result<Vec<NavSnapshot>> {
+        let resp: Vec<NavSnapshot> = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetNavHistory {
+                start,
+                end,
+                limit,
+            }))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query the most recent `count` NAV snapshots, newest first
+    pub async fn latest_nav_snapshots(&self, count: u32) -> Result<Vec<NavSnapshot>> {
+        let resp: Vec<NavSnapshot> = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetLatestNavSnapshots {
+                count,
+            }))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query a single queued withdrawal
+    pub async fn withdraw_request(&self, withdraw_id: u64) -> Result<WithdrawRequest> {
+        let resp: WithdrawRequest = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetWithdrawRequest {
+                withdraw_id,
+            }))
+            .await?;
+        Ok(resp)
+    }
+
+    /// List queued withdrawals with pagination, optionally only those of `user`
+    pub async fn list_withdraw_requests(
+        &self,
+        user: Option<String>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<Vec<WithdrawRequest>> {
+        let resp: Vec<WithdrawRequest> = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::ListWithdrawRequests {
+                user,
+                start_after,
+                limit,
+            }))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query whether withdrawals must go through the queue
+    pub async fn withdrawal_queue_enabled(&self) -> Result<bool> {
+        let resp: bool = self
+            .query(&QueryMsg::Vault(
+                VaultQueryMsg::GetWithdrawalQueueEnabled {},
+            ))
+            .await?;
+        Ok(resp)
+    }
+
     /// Query all vault assets
     pub async fn vault_assets(&self) -> Result<Vec<Coin>> {
         let resp: Vec<Coin> = self
@@ -145,6 +269,132 @@ impl VaultQuerier {
         Ok(resp)
     }
 
+    /// Query the fee configuration
+    pub async fn fee_config(&self) -> Result<FeeConfig> {
+        let resp: FeeConfig = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetFeeConfig {}))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query fees minted so far and management fees pending since the last accrual
+    pub async fn accrued_fees(&self) -> Result<AccruedFees> {
+        let resp: AccruedFees = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetAccruedFees {}))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query the last accepted envelope timestamp and the maximum payload age
+    pub async fn envelope_status(&self) -> Result<EnvelopeStatus> {
+        let resp: EnvelopeStatus = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetEnvelopeStatus {}))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query the price deviation guards and the action taken when one trips
+    pub async fn price_guards(&self) -> Result<PriceGuardConfig> {
+        let resp: PriceGuardConfig = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetPriceGuards {}))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query how long a deposit may stay pending before anyone can reclaim it
+    pub async fn deposit_expiry(&self) -> Result<Option<u64>> {
+        let resp: Option<u64> = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetDepositExpiry {}))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query the maximum slippage in bps accepted for swap routes
+    pub async fn max_slippage(&self) -> Result<u64> {
+        let resp: u64 = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetMaxSlippage {}))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query trading limits together with the remaining 24h turnover budget
+    pub async fn trading_limits(&self) -> Result<TradingLimitsResponse> {
+        let resp: TradingLimitsResponse = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetTradingLimits {}))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query the owner's deposit caps
+    pub async fn deposit_caps(&self) -> Result<DepositCaps> {
+        let resp: DepositCaps = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetDepositCaps {}))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query whether the depositor allowlist is enforced and one page of its addresses
+    pub async fn depositor_allowlist(
+        &self,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<DepositorAllowlistResponse> {
+        let resp: DepositorAllowlistResponse = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetDepositorAllowlist {
+                start_after,
+                limit,
+            }))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query whether the swap allowlist is enforced and the listed venues with their pools
+    pub async fn swap_allowlist(
+        &self,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<SwapAllowlistResponse> {
+        let resp: SwapAllowlistResponse = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetSwapAllowlist {
+                start_after,
+                limit,
+            }))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query the surplus policy and every denom whose bank balance is out of line with the books
+    pub async fn reconciliation(&self) -> Result<ReconciliationReport> {
+        let resp: ReconciliationReport = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetReconciliation {}))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query the active target allocation, the timelock and any pending strategy change
+    pub async fn strategy(&self) -> Result<StrategyResponse> {
+        let resp: StrategyResponse = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetStrategy {}))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query which vault operations are paused
+    pub async fn pause_state(&self) -> Result<PauseState> {
+        let resp: PauseState = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetPauseState {}))
+            .await?;
+        Ok(resp)
+    }
+
+    /// Query the guardian allowed to pause the vault
+    pub async fn guardian(&self) -> Result<Option<Addr>> {
+        let resp: Option<Addr> = self
+            .query(&QueryMsg::Vault(VaultQueryMsg::GetGuardian {}))
+            .await?;
+        Ok(resp)
+    }
+
     /// Query ownership information
     pub async fn ownership(&self) -> Result<cw_ownable::Ownership<cosmwasm_std::Addr>> {
         let resp: cw_ownable::Ownership<cosmwasm_std::Addr> = self
@@ -231,10 +481,170 @@ impl VaultExecutor {
         }
     }
 
+    /// Redeem tokenized vault shares by attaching them to the withdrawal
+    pub async fn withdraw_share_tokens(
+        &self,
+        signer: &AnyAddr,
+        shares: Coin,
+    ) -> Result<AnyTxResponse> {
+        let msg = ExecuteMsg::Vault(VaultExecuteMsg::Withdraw {
+            shares: shares.amount,
+        });
+        #[cfg(feature = "multitest")]
+        {
+            match &self.inner {
+                AnyExecutor::MultiTest { app, .. } => app
+                    .borrow_mut()
+                    .execute_contract(
+                        signer.clone().into(),
+                        self.addr.clone().into(),
+                        &msg,
+                        &[shares],
+                    )
+                    .map(AnyTxResponse::MultiTest)
+                    .map_err(|e| anyhow!("StdError: {}", e)),
+                _ => self.exec(&msg, &[shares]).await,
+            }
+        }
+        #[cfg(not(feature = "multitest"))]
+        {
+            self.exec(&msg, &[shares]).await
+        }
+    }
+
+    /// Redeem shares into a single denom, swapping the other slices through Skip.
+    /// A slice whose swap fails is returned in-kind; the rest are still swapped.
+    pub async fn withdraw_to(
+        &self,
+        signer: &AnyAddr,
+        shares: Uint256,
+        denom: String,
+        min_out: Uint256,
+        routes: Vec<WithdrawRoute>,
+    ) -> Result<AnyTxResponse> {
+        let msg = ExecuteMsg::Vault(VaultExecuteMsg::WithdrawTo {
+            shares,
+            denom,
+            min_out,
+            routes,
+        });
+        #[cfg(feature = "multitest")]
+        {
+            match &self.inner {
+                AnyExecutor::MultiTest { app, .. } => app
+                    .borrow_mut()
+                    .execute_contract(signer.clone().into(), self.addr.clone().into(), &msg, &[])
+                    .map(AnyTxResponse::MultiTest)
+                    .map_err(|e| anyhow!("StdError: {}", e)),
+                _ => self.exec(&msg, &[]).await,
+            }
+        }
+        #[cfg(not(feature = "multitest"))]
+        {
+            self.exec(&msg, &[]).await
+        }
+    }
+
+    /// Queue a withdrawal to be settled at the next price update.
+    /// Tokenized vaults require the shares to be attached as `funds`.
+    pub async fn request_withdraw(
+        &self,
+        signer: &AnyAddr,
+        shares: Uint256,
+        funds: &[Coin],
+    ) -> Result<AnyTxResponse> {
+        let msg = ExecuteMsg::Vault(VaultExecuteMsg::RequestWithdraw { shares });
+        #[cfg(feature = "multitest")]
+        {
+            match &self.inner {
+                AnyExecutor::MultiTest { app, .. } => app
+                    .borrow_mut()
+                    .execute_contract(signer.clone().into(), self.addr.clone().into(), &msg, funds)
+                    .map(AnyTxResponse::MultiTest)
+                    .map_err(|e| anyhow!("StdError: {}", e)),
+                _ => self.exec(&msg, funds).await,
+            }
+        }
+        #[cfg(not(feature = "multitest"))]
+        {
+            self.exec(&msg, funds).await
+        }
+    }
+
+    /// Cancel a queued withdrawal and return its shares to the requester
+    pub async fn cancel_withdraw(
+        &self,
+        signer: &AnyAddr,
+        withdraw_id: u64,
+    ) -> Result<AnyTxResponse> {
+        let msg = ExecuteMsg::Vault(VaultExecuteMsg::CancelWithdraw { withdraw_id });
+        #[cfg(feature = "multitest")]
+        {
+            match &self.inner {
+                AnyExecutor::MultiTest { app, .. } => app
+                    .borrow_mut()
+                    .execute_contract(signer.clone().into(), self.addr.clone().into(), &msg, &[])
+                    .map(AnyTxResponse::MultiTest)
+                    .map_err(|e| anyhow!("StdError: {}", e)),
+                _ => self.exec(&msg, &[]).await,
+            }
+        }
+        #[cfg(not(feature = "multitest"))]
+        {
+            self.exec(&msg, &[]).await
+        }
+    }
+
+    /// Cancel a pending deposit and refund its coins to the depositor
+    pub async fn cancel_deposit(&self, signer: &AnyAddr, deposit_id: u64) -> Result<AnyTxResponse> {
+        #[cfg(feature = "multitest")]
+        {
+            match &self.inner {
+                AnyExecutor::MultiTest { app, .. } => app
+                    .borrow_mut()
+                    .execute_contract(
+                        signer.clone().into(),
+                        self.addr.clone().into(),
+                        &ExecuteMsg::Vault(VaultExecuteMsg::CancelDeposit { deposit_id }),
+                        &[],
+                    )
+                    .map(AnyTxResponse::MultiTest)
+                    .map_err(|e| anyhow!("StdError: {}", e)),
+                _ => {
+                    self.exec(
+                        &ExecuteMsg::Vault(VaultExecuteMsg::CancelDeposit { deposit_id }),
+                        &[],
+                    )
+                    .await
+                }
+            }
+        }
+        #[cfg(not(feature = "multitest"))]
+        {
+            self.exec(
+                &ExecuteMsg::Vault(VaultExecuteMsg::CancelDeposit { deposit_id }),
+                &[],
+            )
+            .await
+        }
+    }
+
+    /// Set or clear the pending deposit expiry in seconds (owner only)
+    pub async fn update_deposit_expiry(
+        &self,
+        expiry_seconds: Option<u64>,
+    ) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateDepositExpiry { expiry_seconds }),
+            &[],
+        )
+        .await
+    }
+
     /// Update whitelist (owner only)
     pub async fn update_whitelist(
         &self,
-        to_add: Option<Vec<String>>,
+        to_add: Option<Vec<WhitelistEntry>>,
         to_remove: Option<Vec<String>>,
     ) -> Result<AnyTxResponse> {
         self.exec(
@@ -300,7 +710,7 @@ impl VaultExecutor {
     pub async fn update_whitelist_direct(
         &self,
         signer: &Addr,
-        to_add: Option<Vec<String>>,
+        to_add: Option<Vec<WhitelistEntry>>,
         to_remove: Option<Vec<String>>,
     ) -> Result<AnyTxResponse> {
         match &self.inner {
@@ -324,6 +734,214 @@ impl VaultExecutor {
         }
     }
 
+    /// Update fee rates and recipient (owner only)
+    pub async fn update_fee_config(
+        &self,
+        management_fee_rate: Option<Decimal256>,
+        performance_fee_rate: Option<Decimal256>,
+        fee_recipient: Option<String>,
+    ) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateFeeConfig {
+                management_fee_rate,
+                performance_fee_rate,
+                fee_recipient,
+            }),
+            &[],
+        )
+        .await
+    }
+
+    /// Update the maximum accepted envelope age in seconds (owner only)
+    pub async fn update_max_payload_age(&self, max_age_seconds: u64) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateMaxPayloadAge { max_age_seconds }),
+            &[],
+        )
+        .await
+    }
+
+    /// Update how far ahead of the block time an envelope may be stamped (owner only)
+    pub async fn update_max_clock_skew(&self, max_skew_seconds: u64) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateMaxClockSkew { max_skew_seconds }),
+            &[],
+        )
+        .await
+    }
+
+    /// Set or remove per-denom price guards and optionally change the guard action (owner only)
+    pub async fn update_price_guards(
+        &self,
+        to_set: Option<Vec<PriceGuard>>,
+        to_remove: Option<Vec<String>>,
+        action: Option<PriceGuardAction>,
+    ) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdatePriceGuards {
+                to_set,
+                to_remove,
+                action,
+            }),
+            &[],
+        )
+        .await
+    }
+
+    /// Require withdrawals to go through the queue (owner only)
+    pub async fn update_withdrawal_queue(&self, enabled: bool) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateWithdrawalQueue { enabled }),
+            &[],
+        )
+        .await
+    }
+
+    /// Update the maximum slippage in bps accepted for swap routes (owner only)
+    pub async fn update_max_slippage(&self, max_slippage_bps: u64) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateMaxSlippage { max_slippage_bps }),
+            &[],
+        )
+        .await
+    }
+
+    /// Replace the trading limits (owner only)
+    pub async fn update_trading_limits(&self, limits: TradingLimits) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateTradingLimits { limits }),
+            &[],
+        )
+        .await
+    }
+
+    /// Replace the deposit caps (owner only)
+    pub async fn update_deposit_caps(&self, caps: DepositCaps) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateDepositCaps { caps }),
+            &[],
+        )
+        .await
+    }
+
+    /// Bring tracked vault assets in line with bank balances (owner only)
+    pub async fn reconcile(&self) -> Result<AnyTxResponse> {
+        self.exec(&ExecuteMsg::Vault(VaultExecuteMsg::Reconcile {}), &[])
+            .await
+    }
+
+    /// Choose what reconciling does with untracked funds (owner only)
+    pub async fn update_surplus_policy(&self, policy: SurplusPolicy) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateSurplusPolicy { policy }),
+            &[],
+        )
+        .await
+    }
+
+    /// Toggle the swap allowlist and add or remove venues and pools (owner only)
+    pub async fn update_swap_allowlist(
+        &self,
+        enabled: Option<bool>,
+        venues_to_add: Option<Vec<String>>,
+        venues_to_remove: Option<Vec<String>>,
+        pools_to_add: Option<Vec<AllowedPool>>,
+        pools_to_remove: Option<Vec<AllowedPool>>,
+    ) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateSwapAllowlist {
+                enabled,
+                venues_to_add,
+                venues_to_remove,
+                pools_to_add,
+                pools_to_remove,
+            }),
+            &[],
+        )
+        .await
+    }
+
+    /// Queue a target allocation and/or timelock change (owner only)
+    pub async fn propose_strategy_change(
+        &self,
+        target_allocation: Option<TargetAllocation>,
+        timelock_seconds: Option<u64>,
+    ) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::ProposeStrategyChange {
+                target_allocation,
+                timelock_seconds,
+            }),
+            &[],
+        )
+        .await
+    }
+
+    /// Apply the pending strategy change once its timelock has passed (owner only)
+    pub async fn execute_strategy_change(&self) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::ExecuteStrategyChange {}),
+            &[],
+        )
+        .await
+    }
+
+    /// Drop the pending strategy change (owner only)
+    pub async fn cancel_strategy_change(&self) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::CancelStrategyChange {}),
+            &[],
+        )
+        .await
+    }
+
+    /// Toggle the depositor allowlist and add or remove addresses (owner only)
+    pub async fn update_depositor_allowlist(
+        &self,
+        enabled: Option<bool>,
+        to_add: Option<Vec<String>>,
+        to_remove: Option<Vec<String>>,
+    ) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateDepositorAllowlist {
+                enabled,
+                to_add,
+                to_remove,
+            }),
+            &[],
+        )
+        .await
+    }
+
+    /// Set or clear the guardian (owner only)
+    pub async fn update_guardian(&self, guardian: Option<String>) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdateGuardian { guardian }),
+            &[],
+        )
+        .await
+    }
+
+    /// Change pause flags; the guardian may only pause, the owner may also unpause
+    pub async fn update_pause_state(
+        &self,
+        deposits: Option<bool>,
+        price_processing: Option<bool>,
+        trading: Option<bool>,
+        emergency_mode: Option<bool>,
+    ) -> Result<AnyTxResponse> {
+        self.exec(
+            &ExecuteMsg::Vault(VaultExecuteMsg::UpdatePauseState {
+                deposits,
+                price_processing,
+                trading,
+                emergency_mode,
+            }),
+            &[],
+        )
+        .await
+    }
+
     pub async fn manual_trigger(&self) -> Result<AnyTxResponse> {
         self.exec(&ExecuteMsg::Vault(VaultExecuteMsg::ManualTrigger {}), &[])
             .await